@@ -0,0 +1,24 @@
+#include <cctype>
+#include <cstdio>
+
+const long long MAX_ABS = 1e18;
+
+int main() {
+    long long a, b;
+    if (scanf("%lld %lld", &a, &b) != 2) {
+        fprintf(stderr, "expected two integers\n");
+        return 1;
+    }
+    if (a < -MAX_ABS || a > MAX_ABS || b < -MAX_ABS || b > MAX_ABS) {
+        fprintf(stderr, "numbers must not exceed %lld by absolute value\n", MAX_ABS);
+        return 1;
+    }
+    int c;
+    while ((c = getchar()) != EOF) {
+        if (!isspace(c)) {
+            fprintf(stderr, "unexpected data after numbers\n");
+            return 1;
+        }
+    }
+    return 0;
+}
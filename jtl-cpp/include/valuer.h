@@ -55,6 +55,13 @@ struct JudgeLog {
     std::vector<JudgeLogEntry> entries;
 };
 
+using SubtaskId = uint32_t;
+
+struct SubtaskScore {
+    SubtaskId subtask_id;
+    uint32_t score;
+};
+
 struct ValuerContext {
     void* data = nullptr;
 
@@ -63,6 +70,11 @@ struct ValuerContext {
     void select_next_test(TestId next_test);
 
     void finish(int score, bool treat_as_full, const JudgeLog& judge_log);
+
+    // same as above, but also reports per-subtask scores
+    // sum of subtask scores must be equal to `score`
+    void finish(int score, bool treat_as_full, const JudgeLog& judge_log,
+                const std::vector<SubtaskScore>& subtasks);
 };
 
 struct ValuerCallbacks {
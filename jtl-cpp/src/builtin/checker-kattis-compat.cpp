@@ -0,0 +1,115 @@
+#include <cassert>
+#include <cstdlib>
+#include <cstring>
+#include <vector>
+#include <wait.h>
+
+#include "checker.h"
+#include "util.h"
+#include "unistd.h"
+
+using namespace checker;
+
+static const size_t PATH_LEN = 128;
+
+/// Kattis output validator exit codes
+static const int EXIT_ACCEPTED = 42;
+static const int EXIT_WRONG_ANSWER = 43;
+
+/// Passes validator's feedback to checker comment
+static void forward_feedback(const char* feedback_dir) {
+    char path[PATH_LEN];
+    snprintf(path, PATH_LEN, "%s/judgemessage.txt", feedback_dir);
+    FILE* f = fopen(path, "r");
+    if (!f) {
+        return;
+    }
+    char buf[1024];
+    while (fgets(buf, sizeof buf, f)) {
+        buf[strcspn(buf, "\n")] = '\0';
+        comment("%s", buf);
+    }
+    fclose(f);
+}
+
+static void remove_feedback_dir(const char* feedback_dir) {
+    for (const char* name : {"judgemessage.txt", "teammessage.txt", "score.txt"}) {
+        char path[PATH_LEN];
+        snprintf(path, PATH_LEN, "%s/%s", feedback_dir, name);
+        unlink(path);
+    }
+    rmdir(feedback_dir);
+}
+
+int main(int argc, char** argv) {
+    if (argc < 2) {
+        fprintf(stderr, "Usage: %s path_to_kattis_output_validator [validator_flags...]", argv[0]);
+        exit(1);
+    }
+    CheckerInput input = init(false);
+    char input_file[PATH_LEN];
+    char answer_file[PATH_LEN];
+
+    pid_t my_pid = getpid();
+    assert(my_pid != -1);
+
+    sprintf(input_file, "/proc/%d/fd/%d", my_pid, (int) input.fd_test);
+    sprintf(answer_file, "/proc/%d/fd/%d", my_pid, (int) input.fd_corr);
+
+    char feedback_dir[] = "/tmp/jjs-kattis-feedback-XXXXXX";
+    if (!mkdtemp(feedback_dir)) {
+        fprintf(stderr, "error: create feedback dir: %m\n");
+        return 1;
+    }
+
+    // validator is launched as `validator input answer feedback_dir [flags...] < team_output`
+    std::vector<char*> validator_argv;
+    validator_argv.push_back(argv[1]);
+    validator_argv.push_back(input_file);
+    validator_argv.push_back(answer_file);
+    validator_argv.push_back(feedback_dir);
+    for (int i = 2; i < argc; ++i) {
+        validator_argv.push_back(argv[i]);
+    }
+    validator_argv.push_back(nullptr);
+
+    int fres = fork();
+    if (fres == -1) {
+        fprintf(stderr, "fork() failed: %m\n");
+        return 1;
+    }
+    if (fres == 0) {
+        if (dup2((int) input.fd_sol, STDIN_FILENO) == -1) {
+            fprintf(stderr, "error: redirect solution output to validator stdin: %m\n");
+            exit(66);
+        }
+        execv(argv[1], validator_argv.data());
+        fprintf(stderr, "error: launch output validator %s: %d (%m)\n", argv[1], errno);
+        exit(66);
+    }
+    int wstatus;
+    if (waitpid(fres, &wstatus, 0) == -1) {
+        fprintf(stderr, "error: waitpid() failed: %m\n");
+        exit(1);
+    }
+    forward_feedback(feedback_dir);
+    remove_feedback_dir(feedback_dir);
+    if (WIFEXITED(wstatus)) {
+        int exit_code = WEXITSTATUS(wstatus);
+        switch (exit_code) {
+            case EXIT_ACCEPTED:
+                finish(Outcome::OK);
+                break;
+            case EXIT_WRONG_ANSWER:
+                finish(Outcome::WRONG_ANSWER);
+                break;
+            default: {
+                fprintf(stderr, "unexpected return code from output validator: %d\n", exit_code);
+                finish(Outcome::CHECKER_LOGIC_ERROR);
+            }
+        }
+    } else {
+        fprintf(stderr, "unexpected exit status (output validator didn't terminate normally): %d\n", wstatus);
+        exit(1);
+    }
+}
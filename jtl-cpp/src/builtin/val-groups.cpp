@@ -0,0 +1,184 @@
+// Valuer for problems with test groups (subtasks).
+// Groups are judged in order. Group is judged only if all its dependencies are passed;
+// otherwise it is skipped. Judging of group stops on first failed test.
+// Group score is given only if all its tests are passed.
+#include "valuer.h"
+#include <cassert>
+#include <cerrno>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <vector>
+
+using namespace valuer;
+
+enum class FeedbackPolicy {
+    COMPLETE,
+    ICPC,
+    NONE
+};
+
+struct Group {
+    uint32_t id = 0;
+    uint32_t score = 0;
+    FeedbackPolicy feedback = FeedbackPolicy::COMPLETE;
+    std::vector<TestId> tests;
+    std::vector<uint32_t> dependencies;
+};
+
+struct State {
+    std::vector<Group> groups;
+    std::vector<bool> passed;
+    size_t cur_group = 0;
+    size_t cur_test = 0;
+    std::vector<SubtaskScore> subtasks;
+};
+
+static JudgeLog judge_log;
+
+[[noreturn]] static void bad_config(const char* message) {
+    comment_private("error: invalid groups.txt: %s\n", message);
+    exit(1);
+}
+
+// groups.txt is written by ppc, one line per group:
+// <id> <score> <feedback> <test count> <tests...> <dependency count> <dependencies...>
+static std::vector<Group> read_config() {
+    FILE* cfg = fopen("./groups.txt", "r");
+    if (cfg == nullptr) {
+        comment_private("error: failed open groups.txt: %s\n", strerror(errno));
+        exit(1);
+    }
+    size_t group_count;
+    if (fscanf(cfg, "%zu", &group_count) != 1) {
+        bad_config("group count expected");
+    }
+    std::vector<Group> groups(group_count);
+    for (Group& group: groups) {
+        char feedback[16];
+        size_t cnt;
+        if (fscanf(cfg, "%u %u %15s %zu", &group.id, &group.score, feedback, &cnt) != 4) {
+            bad_config("group header expected");
+        }
+        if (!strcmp(feedback, "complete")) {
+            group.feedback = FeedbackPolicy::COMPLETE;
+        } else if (!strcmp(feedback, "icpc")) {
+            group.feedback = FeedbackPolicy::ICPC;
+        } else if (!strcmp(feedback, "none")) {
+            group.feedback = FeedbackPolicy::NONE;
+        } else {
+            bad_config("unknown feedback policy");
+        }
+        group.tests.resize(cnt);
+        for (TestId& test: group.tests) {
+            if (fscanf(cfg, "%u", &test) != 1) {
+                bad_config("test id expected");
+            }
+        }
+        if (fscanf(cfg, "%zu", &cnt) != 1) {
+            bad_config("dependency count expected");
+        }
+        group.dependencies.resize(cnt);
+        for (uint32_t& dep: group.dependencies) {
+            if (fscanf(cfg, "%u", &dep) != 1 || dep == 0 || dep >= group.id) {
+                bad_config("dependency must be id of previous group");
+            }
+        }
+        if (group.tests.empty()) {
+            bad_config("group must contain tests");
+        }
+    }
+    fclose(cfg);
+    return groups;
+}
+
+static State& get_state(ValuerContext* const ctx) {
+    return *(State*) ctx->data;
+}
+
+void init(ValuerContext* const ctx) {
+    auto* const state = new State;
+    state->groups = read_config();
+    ctx->data = state;
+}
+
+// Selects first test of next group which can be judged, or finishes judging
+static void advance(ValuerContext* const ctx) {
+    State& state = get_state(ctx);
+    while (state.cur_group < state.groups.size()) {
+        const Group& group = state.groups[state.cur_group];
+        bool deps_passed = true;
+        for (uint32_t dep: group.dependencies) {
+            deps_passed = deps_passed && state.passed[dep - 1];
+        }
+        if (deps_passed) {
+            state.cur_test = 0;
+            ctx->select_next_test(group.tests[0]);
+            return;
+        }
+        comment_public("group %u skipped: dependencies are not passed\n", group.id);
+        state.passed.push_back(false);
+        state.subtasks.push_back({group.id, 0});
+        ++state.cur_group;
+    }
+    uint32_t score = 0;
+    bool all_passed = true;
+    for (size_t i = 0; i < state.groups.size(); ++i) {
+        if (state.passed[i]) {
+            score += state.groups[i].score;
+        } else {
+            all_passed = false;
+        }
+    }
+    ctx->finish(score, all_passed, judge_log, state.subtasks);
+}
+
+void begin(ValuerContext* const ctx) {
+    advance(ctx);
+}
+
+void on_test_end(ValuerContext* ctx, TestId test, StatusKind status_kind, const char* status_code) {
+    State& state = get_state(ctx);
+    const Group& group = state.groups[state.cur_group];
+    assert(group.tests[state.cur_test] == test);
+    const bool test_passed = StatusKindOps::is_passed(status_kind);
+
+    JudgeLogEntry entry;
+    entry.status_kind = status_kind;
+    entry.status_code = status_code;
+    entry.test_id = test;
+    entry.score = 0;
+    if (group.feedback == FeedbackPolicy::COMPLETE) {
+        entry.components.expose_output();
+        entry.components.expose_test_data();
+        entry.components.expose_answer();
+        judge_log.entries.push_back(entry);
+    } else if (group.feedback == FeedbackPolicy::ICPC && !test_passed) {
+        judge_log.entries.push_back(entry);
+    }
+
+    ++state.cur_test;
+    if (test_passed && state.cur_test < group.tests.size()) {
+        ctx->select_next_test(group.tests[state.cur_test]);
+        return;
+    }
+    if (test_passed) {
+        comment_public("group %u passed\n", group.id);
+    } else {
+        comment_public("group %u failed on test %u (status %s)\n", group.id, test, status_code);
+    }
+    state.passed.push_back(test_passed);
+    state.subtasks.push_back({group.id, test_passed ? group.score : 0});
+    ++state.cur_group;
+    advance(ctx);
+}
+
+int main() {
+    ValuerCallbacks cbs;
+    cbs.init = init;
+    cbs.on_test_end = on_test_end;
+    cbs.begin = begin;
+    judge_log.name = "main";
+    run_valuer(cbs);
+    return 0;
+}
@@ -19,13 +19,22 @@ void valuer::ValuerContext::select_next_test(valuer::TestId next_test) {
 }
 
 void valuer::ValuerContext::finish(int score, bool treat_as_full, const JudgeLog& judge_log) {
-    printf("DONE %d %d %zu\n", score, (int) treat_as_full, judge_log.entries.size());
+    finish(score, treat_as_full, judge_log, {});
+}
+
+void valuer::ValuerContext::finish(int score, bool treat_as_full, const JudgeLog& judge_log,
+                                   const std::vector<SubtaskScore>& subtasks) {
+    printf("DONE %d %d %zu %zu\n", score, (int) treat_as_full, judge_log.entries.size(), subtasks.size());
     char format_buf[STATUS_KIND_MAX_LEN];
     for (const JudgeLogEntry& entry: judge_log.entries) {
         StatusKindOps::to_string(entry.status_kind, format_buf);
         printf("%u %s %s %u %u\n", entry.test_id, format_buf, entry.status_code.c_str(), entry.score,
                entry.components.flags);
     }
+    for (const SubtaskScore& subtask: subtasks) {
+        assert(subtask.subtask_id >= 1);
+        printf("%u %u\n", subtask.subtask_id, subtask.score);
+    }
     fflush(stdout);
     should_run = false;
 }
@@ -0,0 +1,76 @@
+//! External authentication providers
+//!
+//! Users, authenticated by external provider, are created on first login.
+//! Their logins are prefixed with provider name, e.g. `university/jsnow`.
+
+/// External authentication provider
+#[derive(Deserialize, Debug, Clone)]
+pub struct AuthProviderConfig {
+    /// Unique name of provider; also used as prefix of logins
+    pub name: String,
+    /// Groups, which are assigned to users when they are created
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(flatten)]
+    pub backend: AuthBackendConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum AuthBackendConfig {
+    Ldap(LdapConfig),
+    Oidc(OidcConfig),
+}
+
+/// Authenticates users by LDAP simple bind
+#[derive(Deserialize, Debug, Clone)]
+pub struct LdapConfig {
+    /// Server URL, e.g. `ldaps://ldap.example.org`
+    pub url: String,
+    /// DN used for bind. `{login}` is replaced with (escaped) login,
+    /// e.g. `uid={login},ou=people,dc=example,dc=org`
+    #[serde(rename = "bind-dn")]
+    pub bind_dn: String,
+    /// Network timeout, in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+/// Authenticates users by OpenID Connect authorization code flow
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcConfig {
+    #[serde(rename = "authorization-endpoint")]
+    pub authorization_endpoint: String,
+    #[serde(rename = "token-endpoint")]
+    pub token_endpoint: String,
+    #[serde(rename = "userinfo-endpoint")]
+    pub userinfo_endpoint: String,
+    #[serde(rename = "client-id")]
+    pub client_id: String,
+    #[serde(rename = "client-secret")]
+    pub client_secret: String,
+    /// URL, provider redirects user to after authorization
+    #[serde(rename = "redirect-uri")]
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// UserInfo claim, used as login of newly created users.
+    /// Users are always identified by `sub` claim.
+    #[serde(rename = "login-claim", default = "default_login_claim")]
+    pub login_claim: String,
+    /// Network timeout, in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "profile".to_string()]
+}
+
+fn default_login_claim() -> String {
+    "preferred_username".to_string()
+}
@@ -3,6 +3,16 @@ extern crate toml;
 #[macro_use]
 extern crate serde_derive;
 
+mod auth;
+mod rate_limits;
+mod roles;
+
+pub use auth::{AuthBackendConfig, AuthProviderConfig, LdapConfig, OidcConfig};
+pub use rate_limits::RateLimits;
+pub use roles::{
+    builtin_roles, Capability, Role, RoleGrant, ROLE_ADMIN, ROLE_CONTESTANT, ROLE_JUDGE,
+};
+
 use std::{
     collections::HashMap,
     env, fs,
@@ -66,6 +76,31 @@ impl Command {
     }
 }
 
+/// Regular expression over source bytes, compiled when config is loaded
+#[derive(Debug, Clone)]
+pub struct Pattern(regex::bytes::Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, regex::Error> {
+        regex::bytes::Regex::new(pattern).map(Pattern)
+    }
+
+    pub fn is_match(&self, data: &[u8]) -> bool {
+        self.0.is_match(data)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Pattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Pattern::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Toolchain {
     /// Human-readable
@@ -85,6 +120,47 @@ pub struct Toolchain {
 
     #[serde(rename = "build-limits", default)]
     pub limits: Limits,
+
+    /// Maximal size of source in bytes. If not set, only contest limit applies
+    #[serde(rename = "max-source-size", default)]
+    pub max_source_size: Option<u64>,
+
+    /// Regular expressions; sources matching any of them are rejected
+    #[serde(rename = "forbidden-patterns", default)]
+    pub forbidden_patterns: Vec<Pattern>,
+}
+
+/// Which runs are taken into account when computing problem score
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoreRuns {
+    /// Only `n` latest runs
+    Latest(u32),
+    /// Only run with the highest score
+    Best,
+    All,
+}
+
+impl Default for ScoreRuns {
+    fn default() -> Self {
+        ScoreRuns::Best
+    }
+}
+
+/// How scores of several runs are combined into problem score
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoreAggregation {
+    /// Maximal run score
+    Max,
+    /// Sum of maximal scores for each subtask (IOI-style)
+    MergeSubtasks,
+}
+
+impl Default for ScoreAggregation {
+    fn default() -> Self {
+        ScoreAggregation::Max
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -96,6 +172,25 @@ pub struct Problem {
     #[serde(default)]
     pub limits: Limits,
 
+    /// Time limits declared in problem package are multiplied by this value.
+    /// Time limit from `limits` is used as is. Must be positive.
+    #[serde(
+        rename = "time-multiplier",
+        default = "Problem::default_time_multiplier",
+        deserialize_with = "Problem::deserialize_time_multiplier"
+    )]
+    pub time_multiplier: f64,
+
+    #[serde(rename = "score-runs", default)]
+    pub score_runs: ScoreRuns,
+
+    #[serde(rename = "score-aggregation", default)]
+    pub score_aggregation: ScoreAggregation,
+
+    /// Run with at least this score is considered accepted
+    #[serde(rename = "accepted-score", default = "Problem::default_accepted_score")]
+    pub accepted_score: u32,
+
     #[serde(skip)]
     pub title: String,
 
@@ -103,6 +198,29 @@ pub struct Problem {
     pub loaded: bool,
 }
 
+impl Problem {
+    fn default_time_multiplier() -> f64 {
+        1.0
+    }
+
+    fn default_accepted_score() -> u32 {
+        100
+    }
+
+    fn deserialize_time_multiplier<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<f64, D::Error> {
+        let multiplier = <f64 as serde::Deserialize>::deserialize(deserializer)?;
+        if !multiplier.is_finite() || multiplier <= 0.0 {
+            return Err(serde::de::Error::custom(format!(
+                "time-multiplier must be positive, got {}",
+                multiplier
+            )));
+        }
+        Ok(multiplier)
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Contest {
     pub title: String,
@@ -125,6 +243,73 @@ pub struct Contest {
     /// Whether contest is visible for anonymous users
     #[serde(rename = "vis-anon")]
     pub anon_visible: bool,
+
+    /// Contest start. Penalty for solved problem includes time since this moment
+    #[serde(rename = "start-time", default)]
+    pub start_time: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Standings are frozen since this moment: results of runs submitted
+    /// later are not visible for contestants
+    #[serde(rename = "freeze-time", default)]
+    pub freeze_time: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Roles, granted in this contest in addition to implicit ones
+    #[serde(rename = "role-grants", default)]
+    pub grants: Vec<RoleGrant>,
+
+    /// Maximal size of submission source in bytes
+    #[serde(
+        rename = "max-source-size",
+        default = "Contest::default_max_source_size"
+    )]
+    pub max_source_size: u64,
+
+    /// Regular expressions; sources matching any of them are rejected.
+    /// Applied in addition to toolchain's patterns
+    #[serde(rename = "forbidden-patterns", default)]
+    pub forbidden_patterns: Vec<Pattern>,
+}
+
+impl Contest {
+    pub const DEFAULT_MAX_SOURCE_SIZE: u64 = 256 * 1024;
+
+    fn default_max_source_size() -> u64 {
+        Contest::DEFAULT_MAX_SOURCE_SIZE
+    }
+
+    /// Returns all role grants in this contest: `judges` get judge role,
+    /// and `group` members get contestant role
+    pub fn role_grants(&self) -> Vec<RoleGrant> {
+        let mut grants = self.grants.clone();
+        grants.push(RoleGrant {
+            role: ROLE_JUDGE.to_string(),
+            groups: self.judges.clone(),
+            users: Vec::new(),
+        });
+        grants.push(RoleGrant {
+            role: ROLE_CONTESTANT.to_string(),
+            groups: self.group.clone(),
+            users: Vec::new(),
+        });
+        grants
+    }
+}
+
+/// Where run sources and artifacts are stored
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlobStorage {
+    /// Files in `<sysroot>/var/blobs`. In multi-machine deployment,
+    /// this directory must be shared between frontend and invokers
+    Filesystem,
+    /// Postgres table
+    Database,
+}
+
+impl Default for BlobStorage {
+    fn default() -> Self {
+        BlobStorage::Filesystem
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -150,6 +335,25 @@ pub struct Config {
     #[serde(rename = "env-blacklist", default)]
     pub env_blacklist: Vec<String>,
 
+    /// Custom roles. Role with the same name as builtin one overrides it
+    #[serde(default)]
+    pub roles: Vec<Role>,
+
+    /// Roles, granted globally, i.e. in all contests
+    #[serde(rename = "role-grants", default)]
+    pub grants: Vec<RoleGrant>,
+
+    /// External authentication providers
+    #[serde(rename = "auth-providers", default)]
+    pub auth_providers: Vec<AuthProviderConfig>,
+
+    /// Limits on submissions and login attempts
+    #[serde(rename = "rate-limits", default)]
+    pub rate_limits: RateLimits,
+
+    #[serde(rename = "blob-storage", default)]
+    pub blob_storage: BlobStorage,
+
     #[serde(skip)]
     pub contests: Vec<Contest>,
 
@@ -198,10 +402,77 @@ impl Config {
 
     pub fn find_contest(&self, name: &str) -> Option<&Contest> {
         match name {
-            "TODO" => Some(&self.contests[0]),
+            "TODO" => self.contests.first(),
             _ => None,
         }
     }
+
+    /// Re-reads manifest of problem `name`, e.g. after new version was installed.
+    /// Does nothing if problem is not used in contests.
+    pub fn reload_problem(&mut self, name: &str) -> std::io::Result<()> {
+        if !self.problems.contains_key(name) {
+            return Ok(());
+        }
+        let manifest = read_problem_manifest(&self.sysroot, name)?;
+        let contest_problems = self
+            .contests
+            .iter_mut()
+            .flat_map(|contest| contest.problems.iter_mut());
+        for problem in self.problems.values_mut().chain(contest_problems) {
+            if problem.name == name {
+                problem.title = manifest.title.clone();
+                problem.loaded = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns id of contest, used when request does not specify one
+    pub fn default_contest_id(&self) -> Option<&'static str> {
+        self.find_contest("TODO").map(|_| "TODO")
+    }
+
+    pub fn find_role(&self, name: &str) -> Option<Role> {
+        self.roles
+            .iter()
+            .find(|role| role.name == name)
+            .cloned()
+            .or_else(|| builtin_roles().into_iter().find(|role| role.name == name))
+    }
+
+    /// Returns global role grants, including builtin grant of admin role to root user
+    pub fn global_role_grants(&self) -> Vec<RoleGrant> {
+        let mut grants = self.grants.clone();
+        grants.push(RoleGrant {
+            role: ROLE_ADMIN.to_string(),
+            groups: Vec::new(),
+            users: vec!["Global/Root".to_string()],
+        });
+        grants
+    }
+}
+
+/// Returns directory of compiled problem package.
+/// Current version, installed by `jjs-ppc install`, takes precedence over
+/// package, which was placed to `var/problems/<name>` manually.
+pub fn problem_dir(sysroot: &Path, name: &str) -> PathBuf {
+    let problems_dir = sysroot.join("var/problems");
+    let current = problems_dir.join(".versions").join(name).join(".current");
+    if current.exists() {
+        current
+    } else {
+        problems_dir.join(name)
+    }
+}
+
+/// Reads manifest of compiled problem package
+pub fn read_problem_manifest(sysroot: &Path, name: &str) -> std::io::Result<pom::Problem> {
+    let path = problem_dir(sysroot, name).join("manifest.json");
+    let with_path = |err: &dyn std::fmt::Display| format!("{}: {}", path.display(), err);
+    let file =
+        fs::File::open(&path).map_err(|err| std::io::Error::new(err.kind(), with_path(&err)))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, with_path(&err)))
 }
 
 pub fn parse_file(path: PathBuf) -> Config {
@@ -263,26 +534,16 @@ pub fn get_config() -> Config {
         let contest_cfg = fs::read_to_string(contest_cfg_path).expect("failed read contest config");
         let mut contest: Contest = toml::from_str(&contest_cfg).expect("failed parse contest");
         for problem in contest.problems.iter_mut() {
-            let problem_manifest_path = sysroot
-                .join("var/problems")
-                .join(&problem.name)
-                .join("manifest.json");
-
-            let problem_manifest_file = match fs::File::open(&problem_manifest_path) {
-                Ok(reader) => reader,
+            let problem_manifest = match read_problem_manifest(&sysroot, &problem.name) {
+                Ok(manifest) => manifest,
                 Err(err) => {
                     eprintln!(
-                        "Error: couldn't open manifest {} for problem {}: {}",
-                        problem_manifest_path.display(),
-                        &problem.name,
-                        err
+                        "Error: couldn't load manifest for problem {}: {}",
+                        &problem.name, err
                     );
                     exit(1);
                 }
             };
-
-            let problem_manifest: pom::Problem =
-                serde_json::from_reader(std::io::BufReader::new(problem_manifest_file)).unwrap();
             problem.title = problem_manifest.title;
             problem.loaded = true;
             c.problems.insert(problem.name.clone(), problem.clone());
@@ -0,0 +1,88 @@
+//! Limits on frequency of submissions and login attempts
+use std::net::IpAddr;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateLimits {
+    /// How many runs user can submit for one problem during a minute.
+    /// Not limited if not set
+    #[serde(rename = "submissions-per-minute", default)]
+    pub submissions_per_minute: Option<u32>,
+
+    /// How many failed login attempts for one login are allowed before backoff starts
+    #[serde(
+        rename = "login-free-attempts",
+        default = "RateLimits::default_login_free_attempts"
+    )]
+    pub login_free_attempts: u32,
+
+    /// Same as `login-free-attempts`, but counted for all logins from one IP address
+    #[serde(
+        rename = "ip-free-attempts",
+        default = "RateLimits::default_ip_free_attempts"
+    )]
+    pub ip_free_attempts: u32,
+
+    /// Delay after first attempt exceeding free ones, in seconds.
+    /// Each next failed attempt doubles delay
+    #[serde(
+        rename = "login-backoff-base",
+        default = "RateLimits::default_login_backoff_base"
+    )]
+    pub login_backoff_base: u64,
+
+    /// Maximal delay between login attempts, in seconds
+    #[serde(
+        rename = "login-backoff-max",
+        default = "RateLimits::default_login_backoff_max"
+    )]
+    pub login_backoff_max: u64,
+
+    /// Failed attempts are forgotten after this amount of seconds without failures
+    #[serde(
+        rename = "login-backoff-reset",
+        default = "RateLimits::default_login_backoff_reset"
+    )]
+    pub login_backoff_reset: u64,
+
+    /// Addresses of reverse proxies. X-Real-IP header is only used for requests
+    /// coming from these addresses, otherwise clients could choose IP they are
+    /// limited by.
+    #[serde(rename = "trusted-proxies", default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimits {
+    fn default_login_free_attempts() -> u32 {
+        5
+    }
+
+    fn default_ip_free_attempts() -> u32 {
+        20
+    }
+
+    fn default_login_backoff_base() -> u64 {
+        1
+    }
+
+    fn default_login_backoff_max() -> u64 {
+        300
+    }
+
+    fn default_login_backoff_reset() -> u64 {
+        3600
+    }
+}
+
+impl Default for RateLimits {
+    fn default() -> RateLimits {
+        RateLimits {
+            submissions_per_minute: None,
+            login_free_attempts: RateLimits::default_login_free_attempts(),
+            ip_free_attempts: RateLimits::default_ip_free_attempts(),
+            login_backoff_base: RateLimits::default_login_backoff_base(),
+            login_backoff_max: RateLimits::default_login_backoff_max(),
+            login_backoff_reset: RateLimits::default_login_backoff_reset(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
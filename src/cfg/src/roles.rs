@@ -0,0 +1,94 @@
+//! Role-based permission model
+//!
+//! Role is named set of capabilities. Roles are granted to users and groups,
+//! either globally (in `jjs.toml`) or for particular contest (in contest config).
+
+/// Action, which can be allowed to user
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Submit runs
+    Submit,
+    /// View runs of other users
+    ViewAllRuns,
+    /// Rejudge, modify and delete runs
+    Rejudge,
+    /// Create and modify users, manage their sessions
+    ManageUsers,
+    /// Answer clarification requests
+    AnswerClarifications,
+    /// View standings ignoring freeze
+    ViewFrozenStandings,
+    /// View log of privileged actions
+    ViewAuditLog,
+    /// Install problem packages
+    InstallProblems,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 8] = [
+        Capability::Submit,
+        Capability::ViewAllRuns,
+        Capability::Rejudge,
+        Capability::ManageUsers,
+        Capability::AnswerClarifications,
+        Capability::ViewFrozenStandings,
+        Capability::ViewAuditLog,
+        Capability::InstallProblems,
+    ];
+}
+
+/// Named set of capabilities
+#[derive(Deserialize, Debug, Clone)]
+pub struct Role {
+    pub name: String,
+    pub capabilities: Vec<Capability>,
+}
+
+/// Grants role to users and to members of groups
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoleGrant {
+    pub role: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+impl RoleGrant {
+    pub fn applies_to(&self, user: &str, user_groups: &[String]) -> bool {
+        self.users.iter().any(|u| u == user) || self.groups.iter().any(|g| user_groups.contains(g))
+    }
+}
+
+/// Has all capabilities. Granted to root user globally.
+pub const ROLE_ADMIN: &str = "admin";
+/// Granted to contest judges
+pub const ROLE_JUDGE: &str = "judge";
+/// Granted to groups, registered for contest
+pub const ROLE_CONTESTANT: &str = "contestant";
+
+/// Roles, which are available without explicit definition in config
+pub fn builtin_roles() -> Vec<Role> {
+    vec![
+        Role {
+            name: ROLE_ADMIN.to_string(),
+            capabilities: Capability::ALL.to_vec(),
+        },
+        Role {
+            name: ROLE_JUDGE.to_string(),
+            capabilities: vec![
+                Capability::Submit,
+                Capability::ViewAllRuns,
+                Capability::Rejudge,
+                Capability::AnswerClarifications,
+                Capability::ViewFrozenStandings,
+                Capability::ViewAuditLog,
+            ],
+        },
+        Role {
+            name: ROLE_CONTESTANT.to_string(),
+            capabilities: vec![Capability::Submit],
+        },
+    ]
+}
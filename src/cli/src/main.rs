@@ -1,6 +1,7 @@
 mod api_version;
 mod contests;
 mod queries;
+mod standings;
 mod submissions;
 mod submit;
 
@@ -26,6 +27,8 @@ enum SubOpt {
     Submit(submit::Opt),
     ManageSubmissions(submissions::Opt),
     Contests(contests::Opt),
+    /// Export standings to HTML, CSV or JSON
+    Standings(standings::Opt),
     #[structopt(name = "api-version")]
     ApiVersion,
 }
@@ -70,6 +73,11 @@ fn main() {
         SubOpt::ManageSubmissions(sopt) => submissions::exec(sopt, &common),
         SubOpt::Contests(sopt) => contests::exec(sopt, &common),
         SubOpt::ApiVersion => api_version::exec(&common),
+        SubOpt::Standings(sopt) => {
+            // standings are printed as is, not as JSON
+            standings::exec(sopt, &common);
+            return;
+        }
     };
 
     let data = serde_json::to_string_pretty(&data).unwrap();
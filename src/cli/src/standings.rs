@@ -0,0 +1,40 @@
+use graphql_client::GraphQLQuery;
+use std::{path::PathBuf, process::exit};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Opt {
+    /// Export format: html, csv or json
+    #[structopt(long = "format", short = "f", default_value = "html")]
+    format: String,
+    /// Write standings to this file instead of stdout
+    #[structopt(long = "out", short = "o")]
+    out: Option<PathBuf>,
+}
+
+pub fn exec(opt: Opt, common: &super::CommonParams) {
+    let vars = crate::queries::export_standings::Variables { format: opt.format };
+    let res = common
+        .client
+        .query::<_, crate::queries::export_standings::ResponseData>(
+            &crate::queries::ExportStandings::build_query(vars),
+        )
+        .expect("network error")
+        .into_result();
+    let standings = match res {
+        Ok(data) => data.standings_export,
+        Err(e) => {
+            eprintln!("error: {}", e[0]);
+            exit(1);
+        }
+    };
+    match opt.out {
+        Some(path) => {
+            if let Err(err) = std::fs::write(&path, standings) {
+                eprintln!("error: failed to write {}: {}", path.display(), err);
+                exit(1);
+            }
+        }
+        None => print!("{}", standings),
+    }
+}
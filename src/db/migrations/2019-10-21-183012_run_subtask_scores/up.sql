@@ -0,0 +1,4 @@
+-- Per-subtask scores, reported by valuer.
+-- Element with index i (1-based) is score for subtask i.
+ALTER TABLE runs
+    ADD COLUMN subtask_scores INTEGER[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,6 @@
+DROP INDEX runs_revision_index;
+DROP TRIGGER runs_bump_revision_trigger ON runs;
+DROP FUNCTION runs_bump_revision;
+ALTER TABLE runs
+    DROP COLUMN revision;
+DROP SEQUENCE run_revision_seq;
@@ -0,0 +1,22 @@
+-- Revision is bumped on every run modification.
+-- It allows clients to fetch only runs that were changed since last fetch.
+CREATE SEQUENCE run_revision_seq START WITH 1 MINVALUE 1;
+
+ALTER TABLE runs
+    ADD COLUMN revision BIGINT NOT NULL DEFAULT nextval('run_revision_seq');
+
+CREATE FUNCTION runs_bump_revision() RETURNS TRIGGER AS
+$$
+BEGIN
+    NEW.revision := nextval('run_revision_seq');
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER runs_bump_revision_trigger
+    BEFORE UPDATE
+    ON runs
+    FOR EACH ROW
+EXECUTE PROCEDURE runs_bump_revision();
+
+CREATE INDEX runs_revision_index ON runs (revision);
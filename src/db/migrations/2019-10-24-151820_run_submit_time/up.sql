@@ -0,0 +1,2 @@
+ALTER TABLE runs
+    ADD COLUMN submit_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
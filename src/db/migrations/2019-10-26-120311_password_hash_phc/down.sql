@@ -0,0 +1,3 @@
+-- Note: fails if some PHC hashes were already stored
+ALTER TABLE users
+    ALTER COLUMN password_hash TYPE CHAR(128);
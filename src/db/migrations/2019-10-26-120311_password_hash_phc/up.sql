@@ -0,0 +1,4 @@
+-- Password hashes are now stored in PHC string format, which is longer than hex-encoded SHA3-512.
+-- Legacy hashes are exactly 128 chars long, so no padding is lost.
+ALTER TABLE users
+    ALTER COLUMN password_hash TYPE VARCHAR(256);
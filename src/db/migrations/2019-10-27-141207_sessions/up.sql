@@ -0,0 +1,12 @@
+-- Sessions, i.e. issued API tokens, which can be listed and revoked
+
+CREATE TABLE sessions
+(
+    id         UUID UNIQUE PRIMARY KEY                          NOT NULL,
+    user_id    UUID REFERENCES users (id) ON DELETE CASCADE     NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE                         NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE                         NOT NULL,
+    revoked    BOOLEAN                                          NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX sessions_user_id_index ON sessions (user_id);
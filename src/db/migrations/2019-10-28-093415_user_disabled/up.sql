@@ -0,0 +1,4 @@
+-- Disabled users can not log in, but their runs are kept
+
+ALTER TABLE users
+    ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+DROP INDEX audit_log_action_index;
+DROP TRIGGER audit_log_append_only_trigger ON audit_log;
+DROP FUNCTION audit_log_forbid_modification;
+DROP TABLE audit_log;
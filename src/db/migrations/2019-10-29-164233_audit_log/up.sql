@@ -0,0 +1,29 @@
+-- Append-only log of privileged actions.
+-- Actor is not a foreign key, so that records outlive deleted users.
+CREATE TABLE audit_log
+(
+    id         SERIAL PRIMARY KEY                                 NOT NULL,
+    time       TIMESTAMP WITH TIME ZONE DEFAULT now()             NOT NULL,
+    actor_id   UUID,
+    actor_name VARCHAR(100)                                       NOT NULL,
+    action     VARCHAR(100)                                       NOT NULL,
+    target     VARCHAR(200)                                       NOT NULL,
+    -- JSON-encoded state of target before and after action
+    before     TEXT,
+    after      TEXT
+);
+
+CREATE FUNCTION audit_log_forbid_modification() RETURNS TRIGGER AS
+$$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only_trigger
+    BEFORE UPDATE OR DELETE
+    ON audit_log
+    FOR EACH ROW
+EXECUTE PROCEDURE audit_log_forbid_modification();
+
+CREATE INDEX audit_log_action_index ON audit_log (action);
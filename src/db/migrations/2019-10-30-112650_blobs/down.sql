@@ -0,0 +1,2 @@
+DROP TABLE run_blobs;
+DROP TABLE blobs;
@@ -0,0 +1,17 @@
+-- Content-addressed blobs; used when blob storage is database
+
+CREATE TABLE blobs
+(
+    hash VARCHAR(64) UNIQUE PRIMARY KEY NOT NULL,
+    data BYTEA                          NOT NULL
+);
+
+-- Named files of run (source, build artifact, judge logs), referencing blobs by hash
+
+CREATE TABLE run_blobs
+(
+    run_id INTEGER REFERENCES runs (id) ON DELETE CASCADE NOT NULL,
+    name   VARCHAR(100)                                  NOT NULL,
+    hash   VARCHAR(64)                                   NOT NULL,
+    PRIMARY KEY (run_id, name)
+);
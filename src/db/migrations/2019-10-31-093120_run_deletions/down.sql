@@ -0,0 +1,3 @@
+DROP TRIGGER runs_record_deletion_trigger ON runs;
+DROP FUNCTION runs_record_deletion;
+DROP TABLE run_deletions;
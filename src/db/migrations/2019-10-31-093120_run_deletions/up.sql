@@ -0,0 +1,24 @@
+-- Deleted runs are recorded with new revision, so that clients fetching
+-- changed runs (e.g. monitor) also learn about deletions.
+
+CREATE TABLE run_deletions
+(
+    run_id   INTEGER PRIMARY KEY                           NOT NULL,
+    revision BIGINT DEFAULT nextval('run_revision_seq') NOT NULL
+);
+
+CREATE INDEX run_deletions_revision_index ON run_deletions (revision);
+
+CREATE FUNCTION runs_record_deletion() RETURNS TRIGGER AS
+$$
+BEGIN
+    INSERT INTO run_deletions (run_id) VALUES (OLD.id);
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER runs_record_deletion_trigger
+    AFTER DELETE
+    ON runs
+    FOR EACH ROW
+EXECUTE PROCEDURE runs_record_deletion();
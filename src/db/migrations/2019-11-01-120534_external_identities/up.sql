@@ -0,0 +1,10 @@
+-- Links users of external authentication providers to local users.
+-- External id is stable identifier, assigned by provider (e.g. OpenID Connect `sub`)
+
+CREATE TABLE external_identities
+(
+    provider    VARCHAR(100)                                 NOT NULL,
+    external_id VARCHAR(255)                                 NOT NULL,
+    user_id     UUID REFERENCES users (id) ON DELETE CASCADE NOT NULL,
+    PRIMARY KEY (provider, external_id)
+);
@@ -0,0 +1,345 @@
+//! Content-addressed storage for run sources and artifacts
+//!
+//! Blobs are identified by hash of their contents; database only stores
+//! mapping from run files (see `RunBlob`) to hashes.
+use crate::{
+    repo::Repo,
+    schema::{Blob, BlobHash, RunId},
+    Error,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Name of run file, containing source
+pub const RUN_SOURCE: &str = "source";
+/// Name of run file, containing build artifact
+pub const RUN_BUILD: &str = "build";
+
+/// Name of run file, containing judge log of given invocation
+pub fn run_judge_log(invoke_revision: i32) -> String {
+    format!("i-{}/log.json", invoke_revision)
+}
+
+pub fn hash(data: &[u8]) -> BlobHash {
+    use sha3::Digest;
+    let mut hasher = sha3::Sha3_256::new();
+    hasher.input(data);
+    format!("{:x}", hasher.result())
+}
+
+fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .bytes()
+            .all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c))
+}
+
+pub trait BlobStore: std::fmt::Debug + Send + Sync {
+    /// Stores data and returns its hash
+    fn put(&self, data: &[u8]) -> Result<BlobHash, Error>;
+    fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Error>;
+    /// Returns hashes of all stored blobs
+    fn list(&self) -> Result<Vec<BlobHash>, Error>;
+    /// Does nothing if blob does not exist
+    fn delete(&self, hash: &str) -> Result<(), Error>;
+}
+
+/// Loads named file of run.
+///
+/// Runs, created before blob storage was introduced, have no run blobs: their
+/// files are read from `<sysroot>/var/submissions/s-<run id>/<name>` instead.
+pub fn load_run_file(
+    conn: &dyn Repo,
+    store: &dyn BlobStore,
+    sysroot: &Path,
+    run_id: RunId,
+    name: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    if let Some(run_blob) = conn.run_blob_try_load(run_id, name)? {
+        return store.get(&run_blob.hash);
+    }
+    let legacy_path = sysroot
+        .join("var/submissions")
+        .join(format!("s-{}", run_id))
+        .join(name);
+    match std::fs::read(legacy_path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Deletes blobs which are not referenced by any run (e.g. because run was
+/// deleted or rejudged).
+///
+/// Blob is stored before run file referencing it is created, so blob is only
+/// deleted if it is found unreferenced by two subsequent `collect` calls.
+/// Interval between calls must be much larger than time needed to create run.
+#[derive(Debug, Default)]
+pub struct GarbageCollector {
+    /// Blobs, found unreferenced by previous call
+    candidates: HashSet<BlobHash>,
+}
+
+impl GarbageCollector {
+    pub fn new() -> GarbageCollector {
+        GarbageCollector::default()
+    }
+
+    /// Returns number of deleted blobs
+    pub fn collect(&mut self, conn: &dyn Repo, store: &dyn BlobStore) -> Result<usize, Error> {
+        let referenced: HashSet<BlobHash> = conn.run_blob_hashes()?.into_iter().collect();
+        let mut unreferenced = HashSet::new();
+        let mut deleted = 0;
+        for hash in store.list()? {
+            if referenced.contains(&hash) {
+                continue;
+            }
+            if self.candidates.contains(&hash) {
+                store.delete(&hash)?;
+                deleted += 1;
+            } else {
+                unreferenced.insert(hash);
+            }
+        }
+        self.candidates = unreferenced;
+        Ok(deleted)
+    }
+}
+
+/// Stores blobs as files `<root>/<first two digits of hash>/<hash>`.
+/// Root can be placed on shared filesystem.
+#[derive(Debug)]
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new(root: &Path) -> FsBlobStore {
+        FsBlobStore {
+            root: root.to_path_buf(),
+        }
+    }
+
+    fn blob_path(&self, hash: &str) -> Result<PathBuf, Error> {
+        // hash is used as file name, so it must not contain e.g. '/'
+        if !is_valid_hash(hash) {
+            return Err(Error::string("FsBlobStore: malformed blob hash"));
+        }
+        Ok(self.root.join(&hash[..2]).join(hash))
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    fn put(&self, data: &[u8]) -> Result<BlobHash, Error> {
+        let hash = hash(data);
+        let path = self.blob_path(&hash)?;
+        if path.exists() {
+            return Ok(hash);
+        }
+        let dir = path.parent().expect("blob path has parent");
+        std::fs::create_dir_all(dir)?;
+        // concurrent readers must never see partially written blob
+        let temp_path = dir.join(format!("{}.{}.tmp", hash, uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, data)?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(hash)
+    }
+
+    fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.blob_path(hash)?;
+        match std::fs::read(path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<BlobHash>, Error> {
+        let dirs = match std::fs::read_dir(&self.root) {
+            Ok(dirs) => dirs,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut hashes = Vec::new();
+        for dir in dirs {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for item in std::fs::read_dir(dir.path())? {
+                // temporary files of unfinished `put`s are skipped here
+                if let Some(name) = item?.file_name().to_str() {
+                    if is_valid_hash(name) {
+                        hashes.push(name.to_string());
+                    }
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    fn delete(&self, hash: &str) -> Result<(), Error> {
+        match std::fs::remove_file(self.blob_path(hash)?) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Stores blobs in database, so that no shared filesystem is needed
+#[derive(Debug)]
+pub struct DbBlobStore {
+    conn: Arc<dyn Repo>,
+}
+
+impl DbBlobStore {
+    pub fn new(conn: Arc<dyn Repo>) -> DbBlobStore {
+        DbBlobStore { conn }
+    }
+}
+
+impl BlobStore for DbBlobStore {
+    fn put(&self, data: &[u8]) -> Result<BlobHash, Error> {
+        let hash = hash(data);
+        self.conn.blob_put(Blob {
+            hash: hash.clone(),
+            data: data.to_vec(),
+        })?;
+        Ok(hash)
+    }
+
+    fn get(&self, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.conn.blob_try_load(hash)?.map(|blob| blob.data))
+    }
+
+    fn list(&self) -> Result<Vec<BlobHash>, Error> {
+        self.conn.blob_list()
+    }
+
+    fn delete(&self, hash: &str) -> Result<(), Error> {
+        self.conn.blob_delete(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_store(store: &dyn BlobStore) {
+        let hash = store.put(b"int main() {}").unwrap();
+        assert_eq!(hash, super::hash(b"int main() {}"));
+        assert_eq!(store.put(b"int main() {}").unwrap(), hash);
+        assert_eq!(store.get(&hash).unwrap().unwrap(), b"int main() {}");
+        let other_hash = store.put(b"").unwrap();
+        assert_ne!(other_hash, hash);
+        assert_eq!(store.get(&other_hash).unwrap().unwrap(), b"");
+        assert_eq!(store.get(&super::hash(b"missing")).unwrap(), None);
+        let mut hashes = store.list().unwrap();
+        hashes.sort();
+        let mut expected = vec![hash.clone(), other_hash.clone()];
+        expected.sort();
+        assert_eq!(hashes, expected);
+        store.delete(&other_hash).unwrap();
+        store.delete(&other_hash).unwrap();
+        assert_eq!(store.get(&other_hash).unwrap(), None);
+        assert_eq!(store.list().unwrap(), vec![hash]);
+    }
+
+    fn new_run(conn: &dyn Repo) -> RunId {
+        conn.run_new(crate::schema::NewRun {
+            toolchain_id: "cpp".to_string(),
+            status_code: "QUEUE_JUDGE".to_string(),
+            status_kind: "QUEUE".to_string(),
+            problem_id: "a-plus-b".to_string(),
+            score: 0,
+            rejudge_id: 1,
+            user_id: uuid::Uuid::new_v4(),
+        })
+        .unwrap()
+        .id
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(
+            hash(b""),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+    }
+
+    #[test]
+    fn test_fs_store() {
+        let root = std::env::temp_dir().join(format!("jjs-db-blobs-{}", uuid::Uuid::new_v4()));
+        let store = FsBlobStore::new(&root);
+        check_store(&store);
+        assert!(store.get("../../etc/passwd").is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_db_store() {
+        let conn: Arc<dyn Repo> = Arc::new(crate::repo::MemoryRepo::new());
+        check_store(&DbBlobStore::new(conn));
+    }
+
+    #[test]
+    fn test_garbage_collector() {
+        let conn: Arc<dyn Repo> = Arc::new(crate::repo::MemoryRepo::new());
+        let store = DbBlobStore::new(conn.clone());
+        let run_id = new_run(&*conn);
+        let set_source = |hash: &str| {
+            conn.run_blob_set(crate::schema::RunBlob {
+                run_id,
+                name: RUN_SOURCE.to_string(),
+                hash: hash.to_string(),
+            })
+            .unwrap()
+        };
+        let old = store.put(b"old").unwrap();
+        set_source(&old);
+        let mut gc = GarbageCollector::new();
+        assert_eq!(gc.collect(&*conn, &store).unwrap(), 0);
+        // blob is not referenced yet, e.g. run is being created
+        let new = store.put(b"new").unwrap();
+        assert_eq!(gc.collect(&*conn, &store).unwrap(), 0);
+        set_source(&new);
+        assert_eq!(gc.collect(&*conn, &store).unwrap(), 0);
+        assert!(store.get(&old).unwrap().is_some());
+        assert_eq!(gc.collect(&*conn, &store).unwrap(), 1);
+        assert_eq!(store.get(&old).unwrap(), None);
+        assert!(store.get(&new).unwrap().is_some());
+        // run files are deleted together with run
+        conn.run_delete(run_id).unwrap();
+        gc.collect(&*conn, &store).unwrap();
+        assert_eq!(gc.collect(&*conn, &store).unwrap(), 1);
+        assert_eq!(store.list().unwrap(), Vec::<BlobHash>::new());
+    }
+
+    #[test]
+    fn test_legacy_run_files() {
+        let sysroot = std::env::temp_dir().join(format!("jjs-db-legacy-{}", uuid::Uuid::new_v4()));
+        let conn: Arc<dyn Repo> = Arc::new(crate::repo::MemoryRepo::new());
+        let store = DbBlobStore::new(conn.clone());
+        let run_id = new_run(&*conn);
+        let legacy_dir = sysroot.join(format!("var/submissions/s-{}", run_id));
+        std::fs::create_dir_all(&legacy_dir).unwrap();
+        std::fs::write(legacy_dir.join(RUN_SOURCE), b"legacy").unwrap();
+        let load = |name| load_run_file(&*conn, &store, &sysroot, run_id, name).unwrap();
+        assert_eq!(load(RUN_SOURCE), Some(b"legacy".to_vec()));
+        assert_eq!(load(RUN_BUILD), None);
+        // run blobs take precedence
+        conn.run_blob_set(crate::schema::RunBlob {
+            run_id,
+            name: RUN_SOURCE.to_string(),
+            hash: store.put(b"new").unwrap(),
+        })
+        .unwrap();
+        assert_eq!(load(RUN_SOURCE), Some(b"new".to_vec()));
+        std::fs::remove_dir_all(&sysroot).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate diesel;
 
+pub mod blob;
 pub mod connect;
 pub mod repo;
 pub mod schema;
@@ -18,6 +19,9 @@ pub enum Error {
     Diesel {
         source: diesel::result::Error,
     },
+    Io {
+        source: std::io::Error,
+    },
     Other {
         source: Box<dyn std::error::Error + 'static>,
     },
@@ -29,6 +33,12 @@ impl From<r2d2::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Error {
+        Error::Io { source }
+    }
+}
+
 impl From<diesel::result::Error> for Error {
     fn from(source: diesel::result::Error) -> Error {
         Error::Diesel { source }
@@ -17,8 +17,20 @@ pub trait RunsRepo: std::fmt::Debug + Send + Sync {
     }
     fn run_update(&self, run_id: RunId, patch: RunPatch) -> Result<(), Error>;
     fn run_delete(&self, run_id: RunId) -> Result<(), Error>;
-    fn run_select(&self, with_run_id: Option<RunId>, limit: Option<u32>)
-    -> Result<Vec<Run>, Error>;
+    /// Returns runs matching `filter` in given order.
+    /// `after` is a cursor: if set, only runs following run with this id
+    /// (in given order) are returned.
+    fn run_select(
+        &self,
+        filter: &RunFilter,
+        order: RunOrder,
+        after: Option<RunId>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Run>, Error>;
+    /// Returns all runs with revision greater than `since`, ordered by revision
+    fn run_select_changed(&self, since: RunRevision) -> Result<Vec<Run>, Error>;
+    /// Returns all runs deleted with revision greater than `since`, ordered by revision
+    fn run_select_deleted(&self, since: RunRevision) -> Result<Vec<RunDeletion>, Error>;
 }
 
 pub trait InvocationRequestsRepo: Send + Sync {
@@ -29,6 +41,60 @@ pub trait InvocationRequestsRepo: Send + Sync {
 pub trait UsersRepo: Send + Sync {
     fn user_new(&self, user_data: NewUser) -> Result<User, Error>;
     fn user_try_load_by_login(&self, login: &str) -> Result<Option<User>, Error>;
+    fn user_try_load_by_id(&self, user_id: UserId) -> Result<Option<User>, Error>;
+    fn user_update(&self, user_id: UserId, patch: UserPatch) -> Result<(), Error>;
+    /// Returns users, ordered by login.
+    /// If `search` is set, only users whose login contains it are returned.
+    fn user_list(&self, search: Option<&str>, offset: u32, limit: u32) -> Result<Vec<User>, Error>;
+    /// Deletes user together with all sessions.
+    /// Returns false (and does nothing) if user has runs.
+    fn user_delete(&self, user_id: UserId) -> Result<bool, Error>;
+    fn external_identity_new(&self, identity: ExternalIdentity) -> Result<(), Error>;
+    fn external_identity_try_load(
+        &self,
+        provider: &str,
+        external_id: &str,
+    ) -> Result<Option<ExternalIdentity>, Error>;
 }
 
-pub trait Repo: RunsRepo + InvocationRequestsRepo + UsersRepo {}
+pub trait SessionsRepo: Send + Sync {
+    fn session_new(&self, session_data: NewSession) -> Result<Session, Error>;
+    fn session_try_load(&self, session_id: SessionId) -> Result<Option<Session>, Error>;
+    /// Returns all sessions of user, including expired and revoked
+    fn session_list(&self, user_id: UserId) -> Result<Vec<Session>, Error>;
+    fn session_revoke(&self, session_id: SessionId) -> Result<(), Error>;
+}
+
+pub trait AuditRepo: Send + Sync {
+    fn audit_append(&self, record_data: NewAuditRecord) -> Result<AuditRecord, Error>;
+    /// Returns records, newest first.
+    /// If `before` is set, only records with smaller id are returned.
+    fn audit_select(
+        &self,
+        action: Option<&str>,
+        before: Option<AuditRecordId>,
+        limit: u32,
+    ) -> Result<Vec<AuditRecord>, Error>;
+}
+
+pub trait BlobsRepo: Send + Sync {
+    /// Stores blob. Does nothing if blob with this hash already exists.
+    fn blob_put(&self, blob: Blob) -> Result<(), Error>;
+    fn blob_try_load(&self, hash: &str) -> Result<Option<Blob>, Error>;
+    /// Returns hashes of all stored blobs
+    fn blob_list(&self) -> Result<Vec<BlobHash>, Error>;
+    fn blob_delete(&self, hash: &str) -> Result<(), Error>;
+    /// Sets blob for named file of run, replacing previous one
+    fn run_blob_set(&self, run_blob: RunBlob) -> Result<(), Error>;
+    fn run_blob_try_load(&self, run_id: RunId, name: &str) -> Result<Option<RunBlob>, Error>;
+    /// Returns hashes of all blobs, referenced by some run
+    fn run_blob_hashes(&self) -> Result<Vec<BlobHash>, Error>;
+}
+
+pub trait Repo:
+    RunsRepo + InvocationRequestsRepo + UsersRepo + SessionsRepo + AuditRepo + BlobsRepo
+{
+    /// Calls `f` with repo, whose changes are either all applied (if `f`
+    /// succeeded) or all discarded (if `f` failed).
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Repo) -> Result<(), Error>) -> Result<(), Error>;
+}
@@ -1,10 +1,38 @@
-use super::{InvocationRequestsRepo, Repo, RunsRepo, UsersRepo};
+use super::{
+    AuditRepo, BlobsRepo, InvocationRequestsRepo, Repo, RunsRepo, SessionsRepo, UsersRepo,
+};
 use crate::{schema::*, Error};
-use diesel::{prelude::*, r2d2::ConnectionManager};
+use diesel::{connection::TransactionManager, prelude::*, r2d2::ConnectionManager};
 use r2d2::{Pool, PooledConnection};
+use std::sync::{Mutex, MutexGuard};
+
+type PgPool = Pool<ConnectionManager<PgConnection>>;
+type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
+
+enum ConnSource {
+    Pool(PgPool),
+    /// Repo, created by `transaction`, uses single connection
+    Transaction(Mutex<PgPooledConnection>),
+}
+
+enum ConnGuard<'a> {
+    Pooled(PgPooledConnection),
+    Locked(MutexGuard<'a, PgPooledConnection>),
+}
+
+impl std::ops::Deref for ConnGuard<'_> {
+    type Target = PgPooledConnection;
+
+    fn deref(&self) -> &PgPooledConnection {
+        match self {
+            ConnGuard::Pooled(conn) => conn,
+            ConnGuard::Locked(conn) => &*conn,
+        }
+    }
+}
 
 pub struct DieselRepo {
-    pool: Pool<ConnectionManager<PgConnection>>,
+    source: ConnSource,
 }
 
 impl std::fmt::Debug for DieselRepo {
@@ -14,20 +42,25 @@ impl std::fmt::Debug for DieselRepo {
 }
 
 impl DieselRepo {
-    fn conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
-        self.pool.get().map_err(Into::into)
+    fn conn(&self) -> Result<ConnGuard<'_>, Error> {
+        match &self.source {
+            ConnSource::Pool(pool) => Ok(ConnGuard::Pooled(pool.get()?)),
+            ConnSource::Transaction(conn) => Ok(ConnGuard::Locked(conn.lock().unwrap())),
+        }
     }
 
     pub(crate) fn new(conn_url: &str) -> Result<DieselRepo, Error> {
         let conn_manager = ConnectionManager::new(conn_url);
         let pool = Pool::new(conn_manager)?;
-        Ok(DieselRepo { pool })
+        Ok(DieselRepo {
+            source: ConnSource::Pool(pool),
+        })
     }
 }
 
 mod impl_users {
     use super::*;
-    use crate::schema::users::dsl::*;
+    use crate::schema::{external_identities, users::dsl::*};
 
     impl UsersRepo for DieselRepo {
         fn user_new(&self, user_data: NewUser) -> Result<User, Error> {
@@ -36,10 +69,11 @@ mod impl_users {
                 username: user_data.username,
                 password_hash: user_data.password_hash,
                 groups: user_data.groups,
+                disabled: false,
             };
             diesel::insert_into(users)
                 .values(&user)
-                .execute(&self.conn()?)?;
+                .execute(&*self.conn()?)?;
 
             Ok(user)
         }
@@ -47,7 +81,89 @@ mod impl_users {
         fn user_try_load_by_login(&self, login: &str) -> Result<Option<User>, Error> {
             Ok(users
                 .filter(username.eq(login))
-                .load(&self.conn()?)?
+                .load(&*self.conn()?)?
+                .into_iter()
+                .next())
+        }
+
+        fn user_try_load_by_id(&self, user_id: UserId) -> Result<Option<User>, Error> {
+            Ok(users
+                .filter(id.eq(user_id))
+                .load(&*self.conn()?)?
+                .into_iter()
+                .next())
+        }
+
+        fn user_update(&self, user_id: UserId, patch: UserPatch) -> Result<(), Error> {
+            diesel::update(users)
+                .filter(id.eq(user_id))
+                .set(&patch)
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+
+        fn user_list(
+            &self,
+            search: Option<&str>,
+            offset: u32,
+            limit: u32,
+        ) -> Result<Vec<User>, Error> {
+            let mut query = users
+                .order(username.asc())
+                .offset(i64::from(offset))
+                .limit(i64::from(limit))
+                .into_boxed();
+            if let Some(search) = search {
+                let pattern = search
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                query = query.filter(username.like(format!("%{}%", pattern)));
+            }
+            query.load(&*self.conn()?).map_err(Into::into)
+        }
+
+        fn user_delete(&self, user_id: UserId) -> Result<bool, Error> {
+            use crate::schema::runs;
+            let conn = self.conn()?;
+            conn.transaction::<_, Error, _>(|| {
+                // lock user, so that no run can be created for it concurrently
+                users
+                    .filter(id.eq(user_id))
+                    .select(id)
+                    .for_update()
+                    .load::<UserId>(&*conn)?;
+                let has_runs = diesel::select(diesel::dsl::exists(
+                    runs::table.filter(runs::user_id.eq(user_id)),
+                ))
+                .get_result::<bool>(&*conn)?;
+                if has_runs {
+                    return Ok(false);
+                }
+                // sessions are deleted by cascade
+                diesel::delete(users.filter(id.eq(user_id))).execute(&*conn)?;
+                Ok(true)
+            })
+        }
+
+        fn external_identity_new(&self, identity: ExternalIdentity) -> Result<(), Error> {
+            diesel::insert_into(external_identities::table)
+                .values(&identity)
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+
+        fn external_identity_try_load(
+            &self,
+            with_provider: &str,
+            with_external_id: &str,
+        ) -> Result<Option<ExternalIdentity>, Error> {
+            Ok(external_identities::table
+                .filter(external_identities::provider.eq(with_provider))
+                .filter(external_identities::external_id.eq(with_external_id))
+                .load(&*self.conn()?)?
                 .into_iter()
                 .next())
         }
@@ -65,7 +181,7 @@ mod impl_inv_reqs {
         ) -> Result<InvocationRequest, Error> {
             diesel::insert_into(invocation_requests)
                 .values(&inv_req_data)
-                .get_result(&self.conn()?)
+                .get_result(&*self.conn()?)
                 .map_err(Into::into)
         }
 
@@ -74,13 +190,13 @@ mod impl_inv_reqs {
             conn.transaction::<_, diesel::result::Error, _>(|| {
                 let waiting_submission = invocation_requests
                     .limit(1)
-                    .load::<InvocationRequest>(&conn)?;
+                    .load::<InvocationRequest>(&*conn)?;
                 let waiting_submission = waiting_submission.into_iter().next();
                 match waiting_submission {
                     Some(s) => {
                         diesel::delete(invocation_requests)
                             .filter(id.eq(s.id))
-                            .execute(&conn)?;
+                            .execute(&*conn)?;
 
                         Ok(Some(s))
                     }
@@ -100,14 +216,14 @@ mod impl_runs {
         fn run_new(&self, run_data: NewRun) -> Result<Run, Error> {
             diesel::insert_into(runs)
                 .values(&run_data)
-                .get_result(&self.conn()?)
+                .get_result(&*self.conn()?)
                 .map_err(Into::into)
         }
 
         fn run_try_load(&self, run_id: i32) -> Result<Option<Run>, Error> {
             Ok(runs
                 .filter(id.eq(run_id))
-                .load::<Run>(&self.conn()?)?
+                .load::<Run>(&*self.conn()?)?
                 .into_iter()
                 .next())
         }
@@ -116,7 +232,7 @@ mod impl_runs {
             diesel::update(runs)
                 .filter(id.eq(run_id))
                 .set(&patch)
-                .execute(&self.conn()?)
+                .execute(&*self.conn()?)
                 .map(|_| ())
                 .map_err(Into::into)
         }
@@ -124,25 +240,243 @@ mod impl_runs {
         fn run_delete(&self, run_id: RunId) -> Result<(), Error> {
             diesel::delete(runs)
                 .filter(id.eq(run_id))
-                .execute(&self.conn()?)
+                .execute(&*self.conn()?)
                 .map(|_| ())
                 .map_err(Into::into)
         }
 
         fn run_select(
             &self,
-            with_run_id: Option<RunId>,
+            filter: &RunFilter,
+            order: RunOrder,
+            after: Option<RunId>,
             limit: Option<u32>,
         ) -> Result<Vec<Run>, Error> {
-            let mut query = runs.into_boxed();
+            let mut query = match order {
+                RunOrder::OldestFirst => runs.order(id.asc()).into_boxed(),
+                RunOrder::NewestFirst => runs.order(id.desc()).into_boxed(),
+            };
 
-            if let Some(rid) = with_run_id {
+            if let Some(rid) = filter.id {
                 query = query.filter(id.eq(rid));
             }
+            if let Some(uid) = filter.user_id {
+                query = query.filter(user_id.eq(uid));
+            }
+            if let Some(problem_ids) = &filter.problem_ids {
+                query = query.filter(problem_id.eq_any(problem_ids.clone()));
+            }
+            if let Some(kind) = &filter.status_kind {
+                query = query.filter(status_kind.eq(kind.clone()));
+            }
+            if let Some(since) = filter.submitted_since {
+                query = query.filter(submit_time.ge(since));
+            }
+            if let Some(before) = filter.submitted_before {
+                query = query.filter(submit_time.lt(before));
+            }
+            if let Some(after) = after {
+                query = match order {
+                    RunOrder::OldestFirst => query.filter(id.gt(after)),
+                    RunOrder::NewestFirst => query.filter(id.lt(after)),
+                };
+            }
             let limit = limit.map(i64::from).unwrap_or(i64::max_value());
-            Ok(query.limit(limit).load(&self.conn()?)?)
+            Ok(query.limit(limit).load(&*self.conn()?)?)
+        }
+
+        fn run_select_changed(&self, since: RunRevision) -> Result<Vec<Run>, Error> {
+            runs.filter(revision.gt(since))
+                .order(revision.asc())
+                .load(&*self.conn()?)
+                .map_err(Into::into)
+        }
+
+        fn run_select_deleted(&self, since: RunRevision) -> Result<Vec<RunDeletion>, Error> {
+            use crate::schema::run_deletions;
+            run_deletions::table
+                .filter(run_deletions::revision.gt(since))
+                .order(run_deletions::revision.asc())
+                .load(&*self.conn()?)
+                .map_err(Into::into)
+        }
+    }
+}
+
+mod impl_sessions {
+    use super::*;
+    use crate::schema::sessions::dsl::*;
+
+    impl SessionsRepo for DieselRepo {
+        fn session_new(&self, session_data: NewSession) -> Result<Session, Error> {
+            let session = Session {
+                id: uuid::Uuid::new_v4(),
+                user_id: session_data.user_id,
+                created_at: session_data.created_at,
+                expires_at: session_data.expires_at,
+                revoked: false,
+            };
+            diesel::insert_into(sessions)
+                .values(&session)
+                .execute(&*self.conn()?)?;
+
+            Ok(session)
+        }
+
+        fn session_try_load(&self, session_id: SessionId) -> Result<Option<Session>, Error> {
+            Ok(sessions
+                .filter(id.eq(session_id))
+                .load(&*self.conn()?)?
+                .into_iter()
+                .next())
+        }
+
+        fn session_list(&self, with_user_id: UserId) -> Result<Vec<Session>, Error> {
+            sessions
+                .filter(user_id.eq(with_user_id))
+                .order(created_at.asc())
+                .load(&*self.conn()?)
+                .map_err(Into::into)
+        }
+
+        fn session_revoke(&self, session_id: SessionId) -> Result<(), Error> {
+            diesel::update(sessions)
+                .filter(id.eq(session_id))
+                .set(revoked.eq(true))
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+    }
+}
+
+mod impl_audit {
+    use super::*;
+    use crate::schema::audit_log::dsl::*;
+
+    impl AuditRepo for DieselRepo {
+        fn audit_append(&self, record_data: NewAuditRecord) -> Result<AuditRecord, Error> {
+            diesel::insert_into(audit_log)
+                .values(&record_data)
+                .get_result(&*self.conn()?)
+                .map_err(Into::into)
+        }
+
+        fn audit_select(
+            &self,
+            with_action: Option<&str>,
+            before_id: Option<AuditRecordId>,
+            limit: u32,
+        ) -> Result<Vec<AuditRecord>, Error> {
+            let mut query = audit_log
+                .order(id.desc())
+                .limit(i64::from(limit))
+                .into_boxed();
+            if let Some(with_action) = with_action {
+                query = query.filter(action.eq(with_action));
+            }
+            if let Some(before_id) = before_id {
+                query = query.filter(id.lt(before_id));
+            }
+            query.load(&*self.conn()?).map_err(Into::into)
         }
     }
 }
 
-impl Repo for DieselRepo {}
+mod impl_blobs {
+    use super::*;
+    use crate::schema::{blobs, run_blobs};
+
+    impl BlobsRepo for DieselRepo {
+        fn blob_put(&self, blob: Blob) -> Result<(), Error> {
+            diesel::insert_into(blobs::table)
+                .values(&blob)
+                .on_conflict(blobs::hash)
+                .do_nothing()
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+
+        fn blob_try_load(&self, blob_hash: &str) -> Result<Option<Blob>, Error> {
+            Ok(blobs::table
+                .filter(blobs::hash.eq(blob_hash))
+                .load(&*self.conn()?)?
+                .into_iter()
+                .next())
+        }
+
+        fn blob_list(&self) -> Result<Vec<BlobHash>, Error> {
+            blobs::table
+                .select(blobs::hash)
+                .load(&*self.conn()?)
+                .map_err(Into::into)
+        }
+
+        fn blob_delete(&self, blob_hash: &str) -> Result<(), Error> {
+            diesel::delete(blobs::table.filter(blobs::hash.eq(blob_hash)))
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+
+        fn run_blob_set(&self, run_blob: RunBlob) -> Result<(), Error> {
+            diesel::insert_into(run_blobs::table)
+                .values(&run_blob)
+                .on_conflict((run_blobs::run_id, run_blobs::name))
+                .do_update()
+                .set(run_blobs::hash.eq(&run_blob.hash))
+                .execute(&*self.conn()?)
+                .map(|_| ())
+                .map_err(Into::into)
+        }
+
+        fn run_blob_try_load(
+            &self,
+            with_run_id: RunId,
+            with_name: &str,
+        ) -> Result<Option<RunBlob>, Error> {
+            Ok(run_blobs::table
+                .filter(run_blobs::run_id.eq(with_run_id))
+                .filter(run_blobs::name.eq(with_name))
+                .load(&*self.conn()?)?
+                .into_iter()
+                .next())
+        }
+
+        fn run_blob_hashes(&self) -> Result<Vec<BlobHash>, Error> {
+            run_blobs::table
+                .select(run_blobs::hash)
+                .distinct()
+                .load(&*self.conn()?)
+                .map_err(Into::into)
+        }
+    }
+}
+
+impl Repo for DieselRepo {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Repo) -> Result<(), Error>) -> Result<(), Error> {
+        let pool = match &self.source {
+            ConnSource::Pool(pool) => pool,
+            // already inside transaction
+            ConnSource::Transaction(_) => return f(self),
+        };
+        let conn = pool.get()?;
+        let manager = conn.transaction_manager();
+        manager.begin_transaction(&*conn)?;
+        let repo = DieselRepo {
+            source: ConnSource::Transaction(Mutex::new(conn)),
+        };
+        let res = f(&repo);
+        let conn = match repo.source {
+            ConnSource::Transaction(conn) => conn.into_inner().unwrap(),
+            ConnSource::Pool(_) => unreachable!(),
+        };
+        let manager = conn.transaction_manager();
+        match res {
+            Ok(()) => manager.commit_transaction(&*conn)?,
+            Err(_) => manager.rollback_transaction(&*conn)?,
+        }
+        res
+    }
+}
@@ -1,13 +1,30 @@
-use super::{InvocationRequestsRepo, Repo, RunsRepo, UsersRepo};
+use super::{
+    AuditRepo, BlobsRepo, InvocationRequestsRepo, Repo, RunsRepo, SessionsRepo, UsersRepo,
+};
 use crate::{schema::*, Error};
-use std::{convert::TryFrom, sync::Mutex};
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct Data {
     // None if run was deleted
     runs: Vec<Option<Run>>,
     inv_reqs: Vec<InvocationRequest>,
-    users: Vec<User>,
+    // None if user was deleted
+    users: Vec<Option<User>>,
+    sessions: Vec<Session>,
+    external_identities: Vec<ExternalIdentity>,
+    audit_log: Vec<AuditRecord>,
+    blobs: HashMap<BlobHash, Vec<u8>>,
+    run_blobs: HashMap<(RunId, String), BlobHash>,
+    run_deletions: Vec<RunDeletion>,
+    last_run_revision: RunRevision,
+}
+
+impl Data {
+    fn next_run_revision(&mut self) -> RunRevision {
+        self.last_run_revision += 1;
+        self.last_run_revision
+    }
 }
 
 #[derive(Debug, Default)]
@@ -39,6 +56,7 @@ impl RunsRepo for MemoryRepo {
     fn run_new(&self, run_data: NewRun) -> Result<Run, Error> {
         let mut data = self.conn.lock().unwrap();
         let run_id = data.runs.len() as RunId;
+        let revision = data.next_run_revision();
         let run = Run {
             id: run_id,
             toolchain_id: run_data.toolchain_id,
@@ -48,6 +66,9 @@ impl RunsRepo for MemoryRepo {
             score: run_data.score,
             rejudge_id: run_data.rejudge_id,
             user_id: run_data.user_id,
+            subtask_scores: Vec::new(),
+            revision,
+            submit_time: chrono::Utc::now(),
         };
         data.runs.push(Some(run.clone()));
         Ok(run)
@@ -62,6 +83,7 @@ impl RunsRepo for MemoryRepo {
     fn run_update(&self, run_id: i32, patch: RunPatch) -> Result<(), Error> {
         let mut data = self.conn.lock().unwrap();
         let idx = run_id as usize;
+        let revision = data.next_run_revision();
         let cur = match data.runs.get_mut(idx) {
             Some(Some(x)) => x,
             None | Some(None) => return Err(Error::string("run_update@memory: unknown run id")),
@@ -78,6 +100,10 @@ impl RunsRepo for MemoryRepo {
         if let Some(new_rejudge_id) = patch.rejudge_id {
             cur.rejudge_id = new_rejudge_id;
         }
+        if let Some(new_subtask_scores) = patch.subtask_scores {
+            cur.subtask_scores = new_subtask_scores;
+        }
+        cur.revision = revision;
 
         Ok(())
     }
@@ -89,6 +115,10 @@ impl RunsRepo for MemoryRepo {
             None => return Err(Error::string("run_delete@memory: unknown run id")),
         };
         if cur.take().is_some() {
+            let revision = data.next_run_revision();
+            data.run_deletions.push(RunDeletion { run_id, revision });
+            data.run_blobs
+                .retain(|(blob_run_id, _), _| *blob_run_id != run_id);
             Ok(())
         } else {
             Err(Error::string("run_delete@memory: run already deleted"))
@@ -97,27 +127,73 @@ impl RunsRepo for MemoryRepo {
 
     fn run_select(
         &self,
-        with_run_id: Option<RunId>,
+        filter: &RunFilter,
+        order: RunOrder,
+        after: Option<RunId>,
         limit: Option<u32>,
     ) -> Result<Vec<Run>, Error> {
         let lim = limit
             .map(|x| usize::try_from(x).unwrap())
             .unwrap_or(usize::max_value());
-        if lim == 0 {
-            return Ok(Vec::new());
-        }
-        match with_run_id {
-            Some(r) => Ok(self
-                .run_try_load(r)
-                .into_iter()
-                .filter_map(std::convert::identity)
-                .collect()),
-            None => {
-                let data = self.conn.lock().unwrap();
-                let cnt = std::cmp::min(lim, data.runs.len());
-                Ok(data.runs[..cnt].iter().filter_map(|x| x.clone()).collect())
-            }
-        }
+        let data = self.conn.lock().unwrap();
+        let matches = |run: &&Run| {
+            filter.id.map_or(true, |rid| run.id == rid)
+                && filter.user_id.map_or(true, |uid| run.user_id == uid)
+                && filter
+                    .problem_ids
+                    .as_ref()
+                    .map_or(true, |ids| ids.contains(&run.problem_id))
+                && filter
+                    .status_kind
+                    .as_ref()
+                    .map_or(true, |kind| &run.status_kind == kind)
+                && filter
+                    .submitted_since
+                    .map_or(true, |since| run.submit_time >= since)
+                && filter
+                    .submitted_before
+                    .map_or(true, |before| run.submit_time < before)
+        };
+        // runs are stored ordered by id
+        let all_runs = data.runs.iter().filter_map(|run| run.as_ref());
+        let selected: Box<dyn Iterator<Item = &Run>> = match order {
+            RunOrder::OldestFirst => Box::new(
+                all_runs
+                    .filter(|run| after.map_or(true, |after| run.id > after))
+                    .filter(matches),
+            ),
+            RunOrder::NewestFirst => Box::new(
+                all_runs
+                    .rev()
+                    .filter(|run| after.map_or(true, |after| run.id < after))
+                    .filter(matches),
+            ),
+        };
+        Ok(selected.take(lim).cloned().collect())
+    }
+
+    fn run_select_changed(&self, since: RunRevision) -> Result<Vec<Run>, Error> {
+        let data = self.conn.lock().unwrap();
+        let mut changed = data
+            .runs
+            .iter()
+            .filter_map(|run| run.as_ref())
+            .filter(|run| run.revision > since)
+            .cloned()
+            .collect::<Vec<_>>();
+        changed.sort_by_key(|run| run.revision);
+        Ok(changed)
+    }
+
+    fn run_select_deleted(&self, since: RunRevision) -> Result<Vec<RunDeletion>, Error> {
+        let data = self.conn.lock().unwrap();
+        // deletions are stored ordered by revision
+        Ok(data
+            .run_deletions
+            .iter()
+            .filter(|deletion| deletion.revision > since)
+            .cloned()
+            .collect())
     }
 }
 
@@ -150,8 +226,9 @@ impl UsersRepo for MemoryRepo {
             username: user_data.username,
             password_hash: user_data.password_hash,
             groups: user_data.groups,
+            disabled: false,
         };
-        data.users.push(user.clone());
+        data.users.push(Some(user.clone()));
         Ok(user)
     }
 
@@ -160,13 +237,270 @@ impl UsersRepo for MemoryRepo {
         let res = data
             .users
             .iter()
+            .flatten()
             .find(|user| user.username == login)
             .cloned();
         Ok(res)
     }
+
+    fn user_try_load_by_id(&self, user_id: UserId) -> Result<Option<User>, Error> {
+        let data = self.conn.lock().unwrap();
+        let res = data
+            .users
+            .iter()
+            .flatten()
+            .find(|user| user.id == user_id)
+            .cloned();
+        Ok(res)
+    }
+
+    fn user_update(&self, user_id: UserId, patch: UserPatch) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        let user = match data
+            .users
+            .iter_mut()
+            .flatten()
+            .find(|user| user.id == user_id)
+        {
+            Some(user) => user,
+            None => return Err(Error::string("user_update@memory: unknown user id")),
+        };
+        if let Some(new_password_hash) = patch.password_hash {
+            user.password_hash = Some(new_password_hash);
+        }
+        if let Some(new_groups) = patch.groups {
+            user.groups = new_groups;
+        }
+        if let Some(new_disabled) = patch.disabled {
+            user.disabled = new_disabled;
+        }
+        Ok(())
+    }
+
+    fn user_list(&self, search: Option<&str>, offset: u32, limit: u32) -> Result<Vec<User>, Error> {
+        let data = self.conn.lock().unwrap();
+        let mut res = data
+            .users
+            .iter()
+            .flatten()
+            .filter(|user| search.map_or(true, |search| user.username.contains(search)))
+            .cloned()
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(res
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    fn user_delete(&self, user_id: UserId) -> Result<bool, Error> {
+        let mut data = self.conn.lock().unwrap();
+        let has_runs = data.runs.iter().flatten().any(|run| run.user_id == user_id);
+        if has_runs {
+            return Ok(false);
+        }
+        let slot = match data
+            .users
+            .iter_mut()
+            .find(|user| user.as_ref().map(|user| user.id) == Some(user_id))
+        {
+            Some(slot) => slot,
+            None => return Err(Error::string("user_delete@memory: unknown user id")),
+        };
+        *slot = None;
+        data.sessions.retain(|session| session.user_id != user_id);
+        data.external_identities
+            .retain(|identity| identity.user_id != user_id);
+        Ok(true)
+    }
+
+    fn external_identity_new(&self, identity: ExternalIdentity) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        let exists = data.external_identities.iter().any(|other| {
+            other.provider == identity.provider && other.external_id == identity.external_id
+        });
+        if exists {
+            return Err(Error::string(
+                "external_identity_new@memory: identity already exists",
+            ));
+        }
+        data.external_identities.push(identity);
+        Ok(())
+    }
+
+    fn external_identity_try_load(
+        &self,
+        provider: &str,
+        external_id: &str,
+    ) -> Result<Option<ExternalIdentity>, Error> {
+        let data = self.conn.lock().unwrap();
+        Ok(data
+            .external_identities
+            .iter()
+            .find(|identity| identity.provider == provider && identity.external_id == external_id)
+            .cloned())
+    }
+}
+
+impl SessionsRepo for MemoryRepo {
+    fn session_new(&self, session_data: NewSession) -> Result<Session, Error> {
+        let mut data = self.conn.lock().unwrap();
+        let session = Session {
+            id: uuid::Uuid::new_v4(),
+            user_id: session_data.user_id,
+            created_at: session_data.created_at,
+            expires_at: session_data.expires_at,
+            revoked: false,
+        };
+        data.sessions.push(session.clone());
+        Ok(session)
+    }
+
+    fn session_try_load(&self, session_id: SessionId) -> Result<Option<Session>, Error> {
+        let data = self.conn.lock().unwrap();
+        let res = data
+            .sessions
+            .iter()
+            .find(|session| session.id == session_id)
+            .cloned();
+        Ok(res)
+    }
+
+    fn session_list(&self, user_id: UserId) -> Result<Vec<Session>, Error> {
+        let data = self.conn.lock().unwrap();
+        let res = data
+            .sessions
+            .iter()
+            .filter(|session| session.user_id == user_id)
+            .cloned()
+            .collect();
+        Ok(res)
+    }
+
+    fn session_revoke(&self, session_id: SessionId) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        match data
+            .sessions
+            .iter_mut()
+            .find(|session| session.id == session_id)
+        {
+            Some(session) => {
+                session.revoked = true;
+                Ok(())
+            }
+            None => Err(Error::string("session_revoke@memory: unknown session id")),
+        }
+    }
+}
+
+impl AuditRepo for MemoryRepo {
+    fn audit_append(&self, record_data: NewAuditRecord) -> Result<AuditRecord, Error> {
+        let mut data = self.conn.lock().unwrap();
+        let record = AuditRecord {
+            id: data.audit_log.len() as AuditRecordId + 1,
+            time: chrono::Utc::now(),
+            actor_id: record_data.actor_id,
+            actor_name: record_data.actor_name,
+            action: record_data.action,
+            target: record_data.target,
+            before: record_data.before,
+            after: record_data.after,
+        };
+        data.audit_log.push(record.clone());
+        Ok(record)
+    }
+
+    fn audit_select(
+        &self,
+        action: Option<&str>,
+        before: Option<AuditRecordId>,
+        limit: u32,
+    ) -> Result<Vec<AuditRecord>, Error> {
+        let data = self.conn.lock().unwrap();
+        let res = data
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|record| action.map_or(true, |action| record.action == action))
+            .filter(|record| before.map_or(true, |before| record.id < before))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        Ok(res)
+    }
+}
+
+impl BlobsRepo for MemoryRepo {
+    fn blob_put(&self, blob: Blob) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        data.blobs.entry(blob.hash).or_insert(blob.data);
+        Ok(())
+    }
+
+    fn blob_try_load(&self, hash: &str) -> Result<Option<Blob>, Error> {
+        let data = self.conn.lock().unwrap();
+        Ok(data.blobs.get(hash).map(|blob_data| Blob {
+            hash: hash.to_string(),
+            data: blob_data.clone(),
+        }))
+    }
+
+    fn blob_list(&self) -> Result<Vec<BlobHash>, Error> {
+        let data = self.conn.lock().unwrap();
+        Ok(data.blobs.keys().cloned().collect())
+    }
+
+    fn blob_delete(&self, hash: &str) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        data.blobs.remove(hash);
+        Ok(())
+    }
+
+    fn run_blob_set(&self, run_blob: RunBlob) -> Result<(), Error> {
+        let mut data = self.conn.lock().unwrap();
+        match data.runs.get(run_blob.run_id as usize) {
+            Some(Some(_)) => (),
+            _ => return Err(Error::string("run_blob_set@memory: unknown run id")),
+        }
+        data.run_blobs
+            .insert((run_blob.run_id, run_blob.name), run_blob.hash);
+        Ok(())
+    }
+
+    fn run_blob_try_load(&self, run_id: RunId, name: &str) -> Result<Option<RunBlob>, Error> {
+        let data = self.conn.lock().unwrap();
+        Ok(data
+            .run_blobs
+            .get(&(run_id, name.to_string()))
+            .map(|hash| RunBlob {
+                run_id,
+                name: name.to_string(),
+                hash: hash.clone(),
+            }))
+    }
+
+    fn run_blob_hashes(&self) -> Result<Vec<BlobHash>, Error> {
+        let data = self.conn.lock().unwrap();
+        let mut hashes: Vec<BlobHash> = data.run_blobs.values().cloned().collect();
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes)
+    }
 }
 
-impl Repo for MemoryRepo {}
+impl Repo for MemoryRepo {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn Repo) -> Result<(), Error>) -> Result<(), Error> {
+        // lock is held for whole transaction, so transactions are serialized
+        let mut data = self.conn.lock().unwrap();
+        let repo = MemoryRepo {
+            conn: Mutex::new(data.clone()),
+        };
+        f(&repo)?;
+        *data = repo.conn.into_inner().unwrap();
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -215,6 +549,7 @@ mod tests {
                 status_kind: Some("2".to_string()),
                 score: Some(3),
                 rejudge_id: Some(4),
+                subtask_scores: Some(vec![5, 6]),
             };
             repo.run_update(0, patch).unwrap();
             let patched_run = repo.run_load(0).unwrap();
@@ -223,6 +558,350 @@ mod tests {
             assert_eq!(patched_run.status_kind, "2");
             assert_eq!(patched_run.score, 3);
             assert_eq!(patched_run.rejudge_id, 4);
+            assert_eq!(patched_run.subtask_scores, vec![5, 6]);
+        }
+
+        #[test]
+        fn test_select() {
+            let repo = MemoryRepo::new();
+            let users = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
+            for i in 0..6 {
+                let new_run = NewRun {
+                    toolchain_id: "cpp".to_string(),
+                    status_code: "0".to_string(),
+                    status_kind: if i % 3 == 0 { "Accepted" } else { "Rejected" }.to_string(),
+                    problem_id: format!("problem-{}", i % 3),
+                    score: 0,
+                    rejudge_id: 0,
+                    user_id: users[i % 2],
+                };
+                repo.run_new(new_run).unwrap();
+            }
+            repo.run_delete(4).unwrap();
+
+            let ids = |filter: &RunFilter, order, after, limit| {
+                repo.run_select(filter, order, after, limit)
+                    .unwrap()
+                    .into_iter()
+                    .map(|run| run.id)
+                    .collect::<Vec<_>>()
+            };
+            let all = RunFilter::default();
+            assert_eq!(
+                ids(&all, RunOrder::OldestFirst, None, None),
+                [0, 1, 2, 3, 5]
+            );
+            assert_eq!(
+                ids(&all, RunOrder::NewestFirst, None, None),
+                [5, 3, 2, 1, 0]
+            );
+            // pagination
+            assert_eq!(ids(&all, RunOrder::OldestFirst, None, Some(2)), [0, 1]);
+            assert_eq!(ids(&all, RunOrder::OldestFirst, Some(1), Some(2)), [2, 3]);
+            assert_eq!(ids(&all, RunOrder::OldestFirst, Some(3), Some(2)), [5]);
+            assert_eq!(ids(&all, RunOrder::NewestFirst, Some(5), Some(2)), [3, 2]);
+            // filters
+            let by_user = RunFilter {
+                user_id: Some(users[1]),
+                ..Default::default()
+            };
+            assert_eq!(ids(&by_user, RunOrder::OldestFirst, None, None), [1, 3, 5]);
+            let by_problem = RunFilter {
+                problem_ids: Some(vec!["problem-0".to_string(), "problem-2".to_string()]),
+                ..Default::default()
+            };
+            assert_eq!(
+                ids(&by_problem, RunOrder::OldestFirst, None, None),
+                [0, 2, 3, 5]
+            );
+            let by_user_and_status = RunFilter {
+                user_id: Some(users[1]),
+                status_kind: Some("Accepted".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                ids(&by_user_and_status, RunOrder::OldestFirst, None, None),
+                [3]
+            );
+            let submit_time = repo.run_load(2).unwrap().submit_time;
+            let by_time = RunFilter {
+                submitted_since: Some(submit_time),
+                submitted_before: Some(submit_time + chrono::Duration::seconds(1)),
+                ..Default::default()
+            };
+            assert!(ids(&by_time, RunOrder::OldestFirst, None, None).contains(&2));
+            let by_time = RunFilter {
+                submitted_before: Some(submit_time),
+                ..Default::default()
+            };
+            assert!(!ids(&by_time, RunOrder::OldestFirst, None, None).contains(&2));
+        }
+
+        #[test]
+        fn test_select_changed() {
+            let repo = MemoryRepo::new();
+            for _ in 0..3 {
+                let new_run = NewRun {
+                    toolchain_id: "0".to_string(),
+                    status_code: "0".to_string(),
+                    status_kind: "0".to_string(),
+                    problem_id: "0".to_string(),
+                    score: 0,
+                    rejudge_id: 0,
+                    user_id: uuid::Uuid::new_v4(),
+                };
+                repo.run_new(new_run).unwrap();
+            }
+            let all_runs = repo.run_select_changed(0).unwrap();
+            assert_eq!(all_runs.len(), 3);
+            let last_revision = all_runs.last().unwrap().revision;
+            assert!(repo.run_select_changed(last_revision).unwrap().is_empty());
+
+            repo.run_update(1, RunPatch::default()).unwrap();
+            let changed = repo.run_select_changed(last_revision).unwrap();
+            assert_eq!(changed.len(), 1);
+            assert_eq!(changed[0].id, 1);
+
+            assert!(repo.run_select_deleted(0).unwrap().is_empty());
+            repo.run_delete(2).unwrap();
+            let deleted = repo.run_select_deleted(changed[0].revision).unwrap();
+            assert_eq!(deleted.len(), 1);
+            assert_eq!(deleted[0].run_id, 2);
+            assert!(deleted[0].revision > changed[0].revision);
+        }
+    }
+
+    mod sessions {
+        use super::*;
+
+        #[test]
+        fn test_revoke() {
+            let repo = MemoryRepo::new();
+            let user_id = repo
+                .user_try_load_by_login("Global/Root")
+                .unwrap()
+                .unwrap()
+                .id;
+            let now = chrono::Utc::now();
+            let new_session = || NewSession {
+                user_id,
+                created_at: now,
+                expires_at: now + chrono::Duration::hours(1),
+            };
+            let first = repo.session_new(new_session()).unwrap();
+            let second = repo.session_new(new_session()).unwrap();
+            assert_eq!(repo.session_list(user_id).unwrap().len(), 2);
+
+            repo.session_revoke(first.id).unwrap();
+            assert!(repo.session_try_load(first.id).unwrap().unwrap().revoked);
+            assert!(!repo.session_try_load(second.id).unwrap().unwrap().revoked);
+            assert!(repo.session_revoke(uuid::Uuid::new_v4()).is_err());
+        }
+    }
+
+    mod users {
+        use super::*;
+
+        fn new_user(repo: &MemoryRepo, name: &str) -> User {
+            repo.user_new(NewUser {
+                username: name.to_string(),
+                password_hash: None,
+                groups: vec![],
+            })
+            .unwrap()
+        }
+
+        #[test]
+        fn test_list() {
+            let repo = MemoryRepo::new();
+            new_user(&repo, "Bob");
+            new_user(&repo, "Alice");
+            new_user(&repo, "Alice_2");
+            let logins = |search, offset, limit| {
+                repo.user_list(search, offset, limit)
+                    .unwrap()
+                    .into_iter()
+                    .map(|user| user.username)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                logins(None, 0, 10),
+                ["Alice", "Alice_2", "Bob", "Global/Guest", "Global/Root"]
+            );
+            assert_eq!(logins(None, 1, 2), ["Alice_2", "Bob"]);
+            assert_eq!(logins(Some("Alice"), 0, 10), ["Alice", "Alice_2"]);
+            assert_eq!(logins(Some("Global/"), 1, 10), ["Global/Root"]);
+        }
+
+        #[test]
+        fn test_update_and_delete() {
+            let repo = MemoryRepo::new();
+            let alice = new_user(&repo, "Alice");
+            let bob = new_user(&repo, "Bob");
+            repo.user_update(
+                alice.id,
+                UserPatch {
+                    groups: Some(vec!["Judges".to_string()]),
+                    disabled: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let alice = repo.user_try_load_by_id(alice.id).unwrap().unwrap();
+            assert_eq!(alice.groups, ["Judges"]);
+            assert!(alice.disabled);
+
+            repo.run_new(NewRun {
+                toolchain_id: "cpp".to_string(),
+                status_code: "QUEUE_JUDGE".to_string(),
+                status_kind: "QUEUE".to_string(),
+                problem_id: "A".to_string(),
+                score: 0,
+                rejudge_id: 0,
+                user_id: bob.id,
+            })
+            .unwrap();
+            let now = chrono::Utc::now();
+            repo.session_new(NewSession {
+                user_id: alice.id,
+                created_at: now,
+                expires_at: now,
+            })
+            .unwrap();
+
+            assert!(repo.user_delete(alice.id).unwrap());
+            assert!(repo.user_try_load_by_login("Alice").unwrap().is_none());
+            assert!(repo.session_list(alice.id).unwrap().is_empty());
+            // users with runs can not be deleted
+            assert!(!repo.user_delete(bob.id).unwrap());
+            assert!(repo.user_try_load_by_login("Bob").unwrap().is_some());
+            assert!(repo.user_delete(alice.id).is_err());
+        }
+
+        #[test]
+        fn test_external_identities() {
+            let repo = MemoryRepo::new();
+            let alice = new_user(&repo, "oidc/alice");
+            let identity = ExternalIdentity {
+                provider: "oidc".to_string(),
+                external_id: "42".to_string(),
+                user_id: alice.id,
+            };
+            assert_eq!(repo.external_identity_try_load("oidc", "42").unwrap(), None);
+            repo.external_identity_new(identity.clone()).unwrap();
+            assert!(repo.external_identity_new(identity.clone()).is_err());
+            assert_eq!(
+                repo.external_identity_try_load("oidc", "42").unwrap(),
+                Some(identity)
+            );
+            assert_eq!(repo.external_identity_try_load("ldap", "42").unwrap(), None);
+            assert!(repo.user_delete(alice.id).unwrap());
+            assert_eq!(repo.external_identity_try_load("oidc", "42").unwrap(), None);
+        }
+    }
+
+    mod audit {
+        use super::*;
+
+        fn new_record(action: &str, target: &str) -> NewAuditRecord {
+            NewAuditRecord {
+                actor_id: None,
+                actor_name: "Global/Root".to_string(),
+                action: action.to_string(),
+                target: target.to_string(),
+                before: None,
+                after: Some("{}".to_string()),
+            }
+        }
+
+        #[test]
+        fn test_select() {
+            let repo = MemoryRepo::new();
+            let first = repo
+                .audit_append(new_record("run.delete", "run/1"))
+                .unwrap();
+            repo.audit_append(new_record("user.create", "user/JonSnow"))
+                .unwrap();
+            let third = repo
+                .audit_append(new_record("run.delete", "run/2"))
+                .unwrap();
+            assert!(first.id < third.id);
+
+            let targets = |action, before, limit| {
+                repo.audit_select(action, before, limit)
+                    .unwrap()
+                    .into_iter()
+                    .map(|record| record.target)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(targets(None, None, 10), ["run/2", "user/JonSnow", "run/1"]);
+            assert_eq!(targets(None, None, 1), ["run/2"]);
+            assert_eq!(targets(Some("run.delete"), None, 10), ["run/2", "run/1"]);
+            assert_eq!(targets(None, Some(third.id), 10), ["user/JonSnow", "run/1"]);
+        }
+
+        #[test]
+        fn test_transaction() {
+            let repo = MemoryRepo::new();
+            repo.transaction(&mut |repo| {
+                repo.audit_append(new_record("user.create", "user/JonSnow"))?;
+                Ok(())
+            })
+            .unwrap();
+            let res = repo.transaction(&mut |repo| {
+                repo.audit_append(new_record("run.delete", "run/1"))?;
+                Err(Error::string("failed"))
+            });
+            assert!(res.is_err());
+            let records = repo.audit_select(None, None, 10).unwrap();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].target, "user/JonSnow");
+        }
+    }
+
+    mod blobs {
+        use super::*;
+
+        #[test]
+        fn test_run_blobs() {
+            let repo = MemoryRepo::new();
+            let run = repo
+                .run_new(NewRun {
+                    toolchain_id: "cpp".to_string(),
+                    status_code: "QUEUE_JUDGE".to_string(),
+                    status_kind: "QUEUE".to_string(),
+                    problem_id: "a-plus-b".to_string(),
+                    score: 0,
+                    rejudge_id: 1,
+                    user_id: uuid::Uuid::new_v4(),
+                })
+                .unwrap();
+            let run_blob = |name: &str, hash: &str| RunBlob {
+                run_id: run.id,
+                name: name.to_string(),
+                hash: hash.to_string(),
+            };
+            assert_eq!(repo.run_blob_try_load(run.id, "source").unwrap(), None);
+            repo.run_blob_set(run_blob("source", "00")).unwrap();
+            repo.run_blob_set(run_blob("build", "01")).unwrap();
+            repo.run_blob_set(run_blob("build", "02")).unwrap();
+            assert_eq!(
+                repo.run_blob_try_load(run.id, "source").unwrap(),
+                Some(run_blob("source", "00"))
+            );
+            assert_eq!(
+                repo.run_blob_try_load(run.id, "build").unwrap(),
+                Some(run_blob("build", "02"))
+            );
+            assert!(repo
+                .run_blob_set(RunBlob {
+                    run_id: run.id + 1,
+                    ..run_blob("source", "00")
+                })
+                .is_err());
+
+            repo.run_delete(run.id).unwrap();
+            assert_eq!(repo.run_blob_try_load(run.id, "source").unwrap(), None);
         }
     }
 }
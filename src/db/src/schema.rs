@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
 
 pub type RunId = i32;
+pub type RunRevision = i64;
 pub type InvocationRequestId = i32;
 pub type UserId = uuid::Uuid;
 pub type ProblemId = String;
+pub type SessionId = uuid::Uuid;
+pub type AuditRecordId = i32;
+/// Hex-encoded SHA3-256 of blob contents
+pub type BlobHash = String;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Queryable, PartialEq, Eq)]
 pub struct Run {
@@ -15,6 +20,12 @@ pub struct Run {
     pub score: i32,
     pub rejudge_id: i32,
     pub user_id: UserId,
+    /// Score for each subtask; i-th element corresponds to subtask i + 1.
+    /// Empty if valuer did not report subtasks.
+    pub subtask_scores: Vec<i32>,
+    /// Increased on every modification of run
+    pub revision: RunRevision,
+    pub submit_time: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Insertable)]
@@ -38,6 +49,44 @@ pub struct RunPatch {
     pub score: Option<i32>,
     #[column_name = "rejudge_id"]
     pub rejudge_id: Option<i32>,
+    #[column_name = "subtask_scores"]
+    pub subtask_scores: Option<Vec<i32>>,
+}
+
+/// Record about deleted run
+#[derive(Serialize, Deserialize, Debug, Clone, Queryable, PartialEq, Eq)]
+pub struct RunDeletion {
+    pub run_id: RunId,
+    /// Revision, assigned on deletion
+    pub revision: RunRevision,
+}
+
+/// Conditions for `RunsRepo::run_select`. Unset fields do not restrict runs.
+#[derive(Debug, Clone, Default)]
+pub struct RunFilter {
+    pub id: Option<RunId>,
+    pub user_id: Option<UserId>,
+    /// Run matches if its problem is any of these
+    pub problem_ids: Option<Vec<ProblemId>>,
+    pub status_kind: Option<String>,
+    /// Only runs submitted at this moment or later
+    pub submitted_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only runs submitted earlier than this moment
+    pub submitted_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Order of runs, returned by `RunsRepo::run_select`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOrder {
+    /// Ascending by id, i.e. by submission time
+    OldestFirst,
+    NewestFirst,
+}
+
+impl Default for RunOrder {
+    fn default() -> Self {
+        RunOrder::OldestFirst
+    }
 }
 
 #[derive(Queryable, Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +109,8 @@ pub struct User {
     pub username: String,
     pub password_hash: Option<String>,
     pub groups: Vec<String>,
+    /// Disabled user can not log in
+    pub disabled: bool,
 }
 
 pub struct NewUser {
@@ -68,6 +119,84 @@ pub struct NewUser {
     pub groups: Vec<String>,
 }
 
+#[derive(AsChangeset, Default)]
+#[table_name = "users"]
+pub struct UserPatch {
+    pub password_hash: Option<String>,
+    pub groups: Option<Vec<String>>,
+    pub disabled: Option<bool>,
+}
+
+/// Links user of external authentication provider to local user
+#[derive(Serialize, Deserialize, Debug, Clone, Queryable, Insertable, PartialEq, Eq)]
+#[table_name = "external_identities"]
+pub struct ExternalIdentity {
+    /// Name of provider
+    pub provider: String,
+    /// Stable user identifier, assigned by provider
+    pub external_id: String,
+    pub user_id: UserId,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Queryable, Insertable, PartialEq, Eq)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Revoked session can not be used anymore (e.g. after logout)
+    pub revoked: bool,
+}
+
+pub struct NewSession {
+    pub user_id: UserId,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
 use diesel::sql_types::*;
 
 include!("./schema_raw.rs");
+
+#[derive(Serialize, Deserialize, Debug, Clone, Queryable, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub id: AuditRecordId,
+    pub time: chrono::DateTime<chrono::Utc>,
+    /// None if action was not performed by user, e.g. token was issued to local root
+    pub actor_id: Option<UserId>,
+    pub actor_name: String,
+    pub action: String,
+    /// Human-readable identifier of affected entity, e.g. `run/42`
+    pub target: String,
+    /// JSON-encoded state of target before action
+    pub before: Option<String>,
+    /// JSON-encoded state of target after action
+    pub after: Option<String>,
+}
+
+#[derive(Insertable)]
+#[table_name = "audit_log"]
+pub struct NewAuditRecord {
+    pub actor_id: Option<UserId>,
+    pub actor_name: String,
+    pub action: String,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable, PartialEq, Eq)]
+#[table_name = "blobs"]
+pub struct Blob {
+    pub hash: BlobHash,
+    pub data: Vec<u8>,
+}
+
+/// Named file of run, e.g. source
+#[derive(Debug, Clone, Queryable, Insertable, PartialEq, Eq)]
+#[table_name = "run_blobs"]
+pub struct RunBlob {
+    pub run_id: RunId,
+    pub name: String,
+    pub hash: BlobHash,
+}
@@ -1,3 +1,37 @@
+table! {
+    use super::*;
+
+    audit_log (id) {
+        id -> Int4,
+        time -> Timestamptz,
+        actor_id -> Nullable<Uuid>,
+        actor_name -> Varchar,
+        action -> Varchar,
+        target -> Varchar,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+    }
+}
+
+table! {
+    use super::*;
+
+    blobs (hash) {
+        hash -> Varchar,
+        data -> Bytea,
+    }
+}
+
+table! {
+    use super::*;
+
+    external_identities (provider, external_id) {
+        provider -> Varchar,
+        external_id -> Varchar,
+        user_id -> Uuid,
+    }
+}
+
 table! {
     use super::*;
 
@@ -8,6 +42,25 @@ table! {
     }
 }
 
+table! {
+    use super::*;
+
+    run_deletions (run_id) {
+        run_id -> Int4,
+        revision -> Int8,
+    }
+}
+
+table! {
+    use super::*;
+
+    run_blobs (run_id, name) {
+        run_id -> Int4,
+        name -> Varchar,
+        hash -> Varchar,
+    }
+}
+
 table! {
     use super::*;
 
@@ -20,6 +73,21 @@ table! {
         score -> Int4,
         rejudge_id -> Int4,
         user_id -> Uuid,
+        subtask_scores -> Array<Int4>,
+        revision -> Int8,
+        submit_time -> Timestamptz,
+    }
+}
+
+table! {
+    use super::*;
+
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        revoked -> Bool,
     }
 }
 
@@ -29,16 +97,26 @@ table! {
     users (id) {
         id -> Uuid,
         username -> Varchar,
-        password_hash -> Nullable<Bpchar>,
+        password_hash -> Nullable<Varchar>,
         groups -> Array<Text>,
+        disabled -> Bool,
     }
 }
 
+joinable!(external_identities -> users (user_id));
 joinable!(invocation_requests -> runs (run_id));
+joinable!(run_blobs -> runs (run_id));
 joinable!(runs -> users (user_id));
+joinable!(sessions -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    audit_log,
+    blobs,
+    external_identities,
     invocation_requests,
+    run_blobs,
+    run_deletions,
     runs,
+    sessions,
     users,
 );
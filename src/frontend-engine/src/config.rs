@@ -83,7 +83,13 @@ impl FrontendConfig {
         let unix_socket_path =
             env::var("JJS_UNIX_SOCKET_PATH").unwrap_or_else(|_| "/tmp/jjs-auth-sock".to_string());
 
-        let token_mgr = crate::security::TokenMgr::new(db_conn.clone(), secret.clone().into());
+        let token_ttl = env::var("JJS_TOKEN_TTL")
+            .map_err(|_| ())
+            .and_then(|s| s.parse().map_err(|_| ()))
+            .unwrap_or(crate::security::DEFAULT_TOKEN_TTL_SECS);
+
+        let token_mgr = crate::security::TokenMgr::new(db_conn.clone(), secret.clone().into())
+            .with_ttl(chrono::Duration::seconds(token_ttl));
 
         FrontendConfig {
             port,
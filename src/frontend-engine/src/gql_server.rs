@@ -1,7 +1,9 @@
+mod audit;
 mod auth;
 mod context;
 mod misc;
 mod monitor;
+mod problems;
 mod queries;
 mod runs;
 mod schema;
@@ -14,6 +16,7 @@ struct ErrorExtension(juniper::Object<juniper::DefaultScalarValue>);
 impl ErrorExtension {
     const KEY_DEV_BACKTRACE: &'static str = "trace";
     const KEY_ERROR_CODE: &'static str = "errorCode";
+    const KEY_RETRY_AFTER: &'static str = "retryAfter";
 
     fn new() -> Self {
         Self(juniper::Object::with_capacity(0))
@@ -35,6 +38,14 @@ impl ErrorExtension {
             .add_field(Self::KEY_ERROR_CODE, juniper::Value::scalar(error_code));
     }
 
+    /// Sets number of seconds, after which request can be retried
+    fn set_retry_after(&mut self, retry_after: std::time::Duration) {
+        // round up, so that retry is not rejected again
+        let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        self.0
+            .add_field(Self::KEY_RETRY_AFTER, juniper::Value::scalar(secs as i32));
+    }
+
     fn into_value(self) -> juniper::Value<juniper::DefaultScalarValue> {
         juniper::Value::Object(self.0)
     }
@@ -70,6 +81,12 @@ impl ApiError {
     pub fn access_denied(ctx: &Context) -> Self {
         Self::new(ctx, "AccessDenied")
     }
+
+    pub fn rate_limited(ctx: &Context, error_code: &str, retry_after: std::time::Duration) -> Self {
+        let mut err = Self::new(ctx, error_code);
+        err.extension.set_retry_after(retry_after);
+        err
+    }
 }
 
 mod impl_display {
@@ -206,6 +223,16 @@ impl StrErrorMsgUtil for str {
     }
 }
 
+/// Renders public standings; used by non-GraphQL export route
+pub(crate) fn export_standings(
+    ctx: &Context,
+    format: ranker::ExportFormat,
+) -> Result<String, String> {
+    monitor::default_contest(ctx)
+        .and_then(|contest| monitor::export_standings(ctx, contest, format))
+        .map_err(|err| err.to_string())
+}
+
 mod prelude {
     pub(super) use super::{
         schema, ApiError, ApiResult, Context, ErrorExtension, ResultToApiUtil as _,
@@ -0,0 +1,114 @@
+//! Log of privileged actions
+use super::prelude::*;
+use serde::Serialize;
+
+/// Part of user, which is written to audit log (i.e. without password hash)
+#[derive(Serialize)]
+struct UserSnapshot<'a> {
+    login: &'a str,
+    groups: &'a [String],
+    disabled: bool,
+}
+
+pub(super) fn user_snapshot(user: &db::schema::User) -> serde_json::Value {
+    serde_json::to_value(UserSnapshot {
+        login: &user.username,
+        groups: &user.groups,
+        disabled: user.disabled,
+    })
+    .unwrap()
+}
+
+pub(super) fn run_snapshot(run: &db::schema::Run) -> serde_json::Value {
+    serde_json::to_value(run).unwrap()
+}
+
+pub(super) fn session_snapshot(session: &db::schema::Session) -> serde_json::Value {
+    serde_json::to_value(session).unwrap()
+}
+
+/// Appends record about action, performed by current user.
+///
+/// `db` should be the transaction, in which action itself is performed, so
+/// that action is never applied without record.
+pub(super) fn record(
+    ctx: &Context,
+    db: &dyn db::DbConn,
+    action: &str,
+    target: String,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) -> Result<(), db::Error> {
+    let record = db::schema::NewAuditRecord {
+        actor_id: Some(ctx.token.user_id()),
+        actor_name: ctx.token.user_name().to_string(),
+        action: action.to_string(),
+        target,
+        before: before.map(|value| value.to_string()),
+        after: after.map(|value| value.to_string()),
+    };
+    db.audit_append(record)?;
+    Ok(())
+}
+
+const MAX_LIST_LIMIT: i32 = 100;
+
+pub(super) fn list(
+    ctx: &Context,
+    action: Option<String>,
+    before: Option<i32>,
+    limit: Option<i32>,
+) -> ApiResult<Vec<schema::AuditRecord>> {
+    if !ctx.access().user_can_view_audit_log().internal(ctx)? {
+        return Err(ApiError::access_denied(ctx));
+    }
+    let limit = match limit {
+        Some(limit) if limit >= 0 => std::cmp::min(limit, MAX_LIST_LIMIT),
+        Some(_) => return Err(ApiError::new(ctx, "InvalidLimit")),
+        None => MAX_LIST_LIMIT,
+    };
+    let access = ctx.access();
+    let mut records = Vec::new();
+    let mut before = before;
+    // records, which user can not view, are skipped
+    while records.len() < limit as usize {
+        let batch = ctx
+            .db
+            .audit_select(
+                action.as_ref().map(String::as_str),
+                before,
+                MAX_LIST_LIMIT as u32,
+            )
+            .internal(ctx)?;
+        before = match batch.last() {
+            Some(record) => Some(record.id),
+            None => break,
+        };
+        for record in batch {
+            if records.len() == limit as usize {
+                break;
+            }
+            let problem = record_problem(&record);
+            if access
+                .user_can_view_audit_record(problem.as_ref().map(String::as_str))
+                .internal(ctx)?
+            {
+                records.push(record);
+            }
+        }
+    }
+    Ok(records.iter().map(Into::into).collect())
+}
+
+/// Returns problem of run, which is target of record
+fn record_problem(record: &db::schema::AuditRecord) -> Option<String> {
+    if !record.target.starts_with("run/") {
+        return None;
+    }
+    record
+        .after
+        .iter()
+        .chain(record.before.iter())
+        .filter_map(|snapshot| serde_json::from_str::<serde_json::Value>(snapshot).ok())
+        .find_map(|snapshot| Some(snapshot.get("problem_id")?.as_str()?.to_string()))
+}
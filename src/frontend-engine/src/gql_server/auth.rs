@@ -1,17 +1,31 @@
-use super::prelude::*;
+use super::{audit, prelude::*};
+use crate::{
+    password,
+    security::{Credentials, ExternalIdentity, LoginAttempt, ProviderError, RegisteredProvider},
+};
 
 pub(super) fn simple(
     ctx: &Context,
     login: String,
     password: String,
 ) -> ApiResult<schema::SessionToken> {
+    let attempt = begin_login(ctx, &login)?;
     let mut success = false;
     let mut reject_reason = "";
     if let Some(user) = ctx.db.user_try_load_by_login(&login).internal(ctx)? {
-        if let Some(password_hash) = user.password_hash {
-            success = crate::password::check_password_hash(&password, &password_hash);
+        if user.disabled {
+            reject_reason = "UserDisabled";
+        } else if let Some(password_hash) = user.password_hash {
+            success = password::check_password_hash(&password, &password_hash);
             if !success {
                 reject_reason = "IncorrectPassword";
+            } else if password::needs_rehash(&password_hash) {
+                // transparently migrate user to current hashing scheme
+                let patch = db::schema::UserPatch {
+                    password_hash: Some(password::get_password_hash(&password)),
+                    ..Default::default()
+                };
+                ctx.db.user_update(user.id, patch).internal(ctx)?;
             }
         } else {
             reject_reason = "PasswordAuthNotAvailable";
@@ -20,14 +34,10 @@ pub(super) fn simple(
         reject_reason = "UnknownUser";
     }
     if success {
-        let token = ctx.token_mgr.create_token(&login).internal(ctx)?;
-        let buf = ctx.token_mgr.serialize(&token);
-        let sess = schema::SessionToken {
-            data: buf,
-            raw_data: None, //TODO
-        };
-        Ok(sess)
+        attempt.succeeded();
+        issue_session(ctx, &login)
     } else {
+        // attempt is left counted as failed
         let mut ext = ErrorExtension::new();
         ext.set_error_code(reject_reason);
         let err = ApiError {
@@ -39,3 +49,201 @@ pub(super) fn simple(
         Err(err)
     }
 }
+
+/// Rejects login attempt if there were too many failed attempts recently
+fn begin_login<'a>(ctx: &'a Context, login: &str) -> ApiResult<LoginAttempt<'a>> {
+    match ctx.rate_limiter.login_attempt(login, ctx.client_ip) {
+        Ok(attempt) => Ok(attempt),
+        Err(retry_after) => Err(ApiError::rate_limited(
+            ctx,
+            "TooManyLoginAttempts",
+            retry_after,
+        )),
+    }
+}
+
+fn issue_session(ctx: &Context, login: &str) -> ApiResult<schema::SessionToken> {
+    let token = ctx.token_mgr.create_session_token(login).internal(ctx)?;
+    let buf = ctx.token_mgr.serialize(&token);
+    Ok(schema::SessionToken {
+        data: buf,
+        raw_data: None, //TODO
+    })
+}
+
+pub(super) fn list_providers(ctx: &Context) -> Vec<schema::AuthProvider> {
+    ctx.auth_providers
+        .list()
+        .iter()
+        .map(|p| schema::AuthProvider {
+            name: p.name.clone(),
+            kind: p.provider.kind().to_string(),
+        })
+        .collect()
+}
+
+pub(super) fn provider_url(ctx: &Context, provider: &str, state: &str) -> ApiResult<String> {
+    let provider = match ctx.auth_providers.find(provider) {
+        Some(provider) => provider,
+        None => return Err(ApiError::new(ctx, "UnknownAuthProvider")),
+    };
+    match provider.provider.authorization_url(state) {
+        Some(url) => Ok(url),
+        None => Err(ApiError::new(ctx, "UnsupportedCredentials")),
+    }
+}
+
+/// Authenticates user by external provider, creating local user on first login
+pub(super) fn external(
+    ctx: &Context,
+    provider: &str,
+    credentials: Credentials,
+) -> ApiResult<schema::SessionToken> {
+    let provider = match ctx.auth_providers.find(provider) {
+        Some(provider) => provider,
+        None => return Err(ApiError::new(ctx, "UnknownAuthProvider")),
+    };
+    // passwords can be guessed, unlike authorization codes
+    let attempt_login = match &credentials {
+        Credentials::Password { login, .. } => {
+            Some(format!("{}/{}", provider.name, login.to_lowercase()))
+        }
+        Credentials::AuthorizationCode { .. } => None,
+    };
+    let attempt = match &attempt_login {
+        Some(attempt_login) => Some(begin_login(ctx, attempt_login)?),
+        None => None,
+    };
+    let identity = match provider.provider.authenticate(credentials) {
+        Ok(Some(identity)) => identity,
+        // attempt is left counted as failed
+        Ok(None) => return Err(ApiError::new(ctx, "ExternalAuthRejected")),
+        Err(err) => {
+            if let Some(attempt) = attempt {
+                attempt.cancelled();
+            }
+            return match err {
+                ProviderError::UnsupportedCredentials => {
+                    Err(ApiError::new(ctx, "UnsupportedCredentials"))
+                }
+                err => Err(err).internal(ctx),
+            };
+        }
+    };
+    if let Some(attempt) = attempt {
+        attempt.succeeded();
+    }
+    let user = match ctx
+        .db
+        .external_identity_try_load(&provider.name, &identity.id)
+        .internal(ctx)?
+    {
+        Some(mapping) => match ctx.db.user_try_load_by_id(mapping.user_id).internal(ctx)? {
+            Some(user) => user,
+            None => return Err(ApiError::new(ctx, "UnknownUser")),
+        },
+        None => create_external_user(ctx, provider, &identity)?,
+    };
+    if user.disabled {
+        return Err(ApiError::new(ctx, "UserDisabled"));
+    }
+    issue_session(ctx, &user.username)
+}
+
+/// Creates local user for external identity, seen for the first time
+fn create_external_user(
+    ctx: &Context,
+    provider: &RegisteredProvider,
+    identity: &ExternalIdentity,
+) -> ApiResult<db::schema::User> {
+    let login = provider.local_login(identity);
+    let user = match ctx.db.user_try_load_by_login(&login).internal(ctx)? {
+        Some(_) => return Err(ApiError::new(ctx, "ExternalLoginTaken")),
+        None => {
+            let new_user = db::schema::NewUser {
+                username: login,
+                password_hash: None,
+                groups: provider.groups.clone(),
+            };
+            ctx.db.user_new(new_user).internal(ctx)?
+        }
+    };
+    let mapping = db::schema::ExternalIdentity {
+        provider: provider.name.clone(),
+        external_id: identity.id.clone(),
+        user_id: user.id,
+    };
+    ctx.db.external_identity_new(mapping).internal(ctx)?;
+    Ok(user)
+}
+
+/// Revokes session of current token
+pub(super) fn logout(ctx: &Context) -> ApiResult<bool> {
+    ctx.token_mgr.revoke(&ctx.token).internal(ctx)
+}
+
+fn load_user_for_sessions(ctx: &Context, login: Option<String>) -> ApiResult<db::schema::User> {
+    let user = match login {
+        Some(login) => ctx.db.user_try_load_by_login(&login).internal(ctx)?,
+        None => ctx
+            .db
+            .user_try_load_by_id(ctx.token.user_id())
+            .internal(ctx)?,
+    };
+    let user = match user {
+        Some(user) => user,
+        None => return Err(ApiError::new(ctx, "UnknownUser")),
+    };
+    if !ctx
+        .access()
+        .user_can_manage_sessions(user.id)
+        .internal(ctx)?
+    {
+        return Err(ApiError::access_denied(ctx));
+    }
+    Ok(user)
+}
+
+pub(super) fn list_sessions(
+    ctx: &Context,
+    login: Option<String>,
+) -> ApiResult<Vec<schema::Session>> {
+    let user = load_user_for_sessions(ctx, login)?;
+    let sessions = ctx.db.session_list(user.id).internal(ctx)?;
+    Ok(sessions.iter().map(Into::into).collect())
+}
+
+pub(super) fn revoke_session(ctx: &Context, id: schema::SessionId) -> ApiResult<()> {
+    let session = match ctx.db.session_try_load(id).internal(ctx)? {
+        Some(session) => session,
+        None => return Err(ApiError::new(ctx, "UnknownSession")),
+    };
+    if !ctx
+        .access()
+        .user_can_manage_sessions(session.user_id)
+        .internal(ctx)?
+    {
+        return Err(ApiError::access_denied(ctx));
+    }
+    ctx.db
+        .transaction(&mut |db| {
+            db.session_revoke(id)?;
+            // revoking own session is not a privileged action
+            if session.user_id == ctx.token.user_id() {
+                return Ok(());
+            }
+            let after = db::schema::Session {
+                revoked: true,
+                ..session.clone()
+            };
+            audit::record(
+                ctx,
+                db,
+                "session.revoke",
+                format!("session/{}", id),
+                Some(audit::session_snapshot(&session)),
+                Some(audit::session_snapshot(&after)),
+            )
+        })
+        .internal(ctx)
+}
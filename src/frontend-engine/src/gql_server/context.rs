@@ -1,15 +1,32 @@
-use crate::security::{AccessChecker, Token, TokenMgr, TokenMgrError};
-use std::sync::Arc;
+use crate::security::{
+    AccessChecker, AuthProviders, ProviderError, RateLimiter, Token, TokenMgr, TokenMgrError,
+};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, RwLock},
+};
 
 pub(crate) type DbPool = Arc<dyn db::DbConn>;
+pub(crate) type MonitorCacheHandle = Arc<Mutex<super::monitor::MonitorCaches>>;
+/// Config, shared between requests. Each request uses config snapshot, taken
+/// when request starts; snapshot is replaced when problem is installed.
+pub(crate) type ConfigHandle = Arc<RwLock<Arc<cfg::Config>>>;
 
 //TODO: Do not clone Context on every request
 pub(crate) struct ContextData {
     pub(crate) db: DbPool,
     pub(crate) cfg: Arc<cfg::Config>,
+    pub(crate) cfg_handle: ConfigHandle,
     pub(crate) env: crate::config::Env,
     pub(crate) token_mgr: TokenMgr,
     pub(crate) token: Token,
+    pub(crate) monitor_cache: MonitorCacheHandle,
+    pub(crate) auth_providers: Arc<AuthProviders>,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+    pub(crate) blobs: Arc<dyn db::blob::BlobStore>,
+    /// Address of client, if known. Used for rate limiting
+    pub(crate) client_ip: Option<IpAddr>,
 }
 
 impl ContextData {
@@ -33,6 +50,33 @@ impl std::ops::Deref for Context {
     }
 }
 
+/// Address of client. X-Real-IP header is only taken into account for
+/// requests from trusted proxies.
+pub(crate) struct ClientIp(pub(crate) Option<IpAddr>);
+
+impl<'a, 'r> rocket::request::FromRequest<'a, 'r> for ClientIp {
+    type Error = TokenMgrError;
+
+    fn from_request(
+        request: &'a rocket::request::Request<'r>,
+    ) -> rocket::request::Outcome<Self, Self::Error> {
+        let factory: rocket::State<ContextFactory> = request
+            .guard::<rocket::State<ContextFactory>>()
+            .expect("State<ContextFactory> missing");
+        let remote = request.remote().map(|addr| addr.ip());
+        let from_proxy = remote.map_or(false, |ip| {
+            let cfg = factory.cfg.read().unwrap();
+            cfg.rate_limits.trusted_proxies.contains(&ip)
+        });
+        let ip = if from_proxy {
+            request.real_ip().or(remote)
+        } else {
+            remote
+        };
+        rocket::Outcome::Success(ClientIp(ip))
+    }
+}
+
 impl<'a, 'r> rocket::request::FromRequest<'a, 'r> for ContextData {
     type Error = TokenMgrError;
 
@@ -73,10 +117,16 @@ impl<'a, 'r> rocket::request::FromRequest<'a, 'r> for ContextData {
 
         rocket::Outcome::Success(ContextData {
             db: factory.pool.clone(),
-            cfg: factory.cfg.clone(),
+            cfg: factory.cfg.read().unwrap().clone(),
+            cfg_handle: factory.cfg.clone(),
             env: *env,
             token_mgr,
             token,
+            monitor_cache: factory.monitor_cache.clone(),
+            auth_providers: factory.auth_providers.clone(),
+            rate_limiter: factory.rate_limiter.clone(),
+            blobs: factory.blobs.clone(),
+            client_ip: request.guard::<ClientIp>()?.0,
         })
     }
 }
@@ -95,10 +145,37 @@ impl<'a, 'r> rocket::request::FromRequest<'a, 'r> for Context {
 
 pub(crate) struct ContextFactory {
     pub(crate) pool: DbPool,
-    pub(crate) cfg: Arc<cfg::Config>,
+    pub(crate) cfg: ConfigHandle,
+    pub(crate) monitor_cache: MonitorCacheHandle,
+    pub(crate) auth_providers: Arc<AuthProviders>,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+    pub(crate) blobs: Arc<dyn db::blob::BlobStore>,
 }
 
 impl ContextFactory {
+    pub(crate) fn new(
+        pool: DbPool,
+        cfg: Arc<cfg::Config>,
+    ) -> Result<ContextFactory, ProviderError> {
+        let monitor_cache = Arc::new(Mutex::new(HashMap::new()));
+        let auth_providers = Arc::new(AuthProviders::new(&cfg.auth_providers)?);
+        let rate_limiter = Arc::new(RateLimiter::new(cfg.rate_limits.clone()));
+        let blobs: Arc<dyn db::blob::BlobStore> = match cfg.blob_storage {
+            cfg::BlobStorage::Filesystem => {
+                Arc::new(db::blob::FsBlobStore::new(&cfg.sysroot.join("var/blobs")))
+            }
+            cfg::BlobStorage::Database => Arc::new(db::blob::DbBlobStore::new(pool.clone())),
+        };
+        Ok(ContextFactory {
+            pool,
+            cfg: Arc::new(RwLock::new(cfg)),
+            monitor_cache,
+            auth_providers,
+            rate_limiter,
+            blobs,
+        })
+    }
+
     /// Creates context, not bound to particular request
     pub(crate) fn create_context_data_unrestricted(&self) -> ContextData {
         let secret_key = Arc::new([]);
@@ -109,10 +186,16 @@ impl ContextFactory {
         };
         ContextData {
             db: self.pool.clone(),
-            cfg: self.cfg.clone(),
+            cfg: self.cfg.read().unwrap().clone(),
+            cfg_handle: self.cfg.clone(),
             env: crate::config::Env::Dev,
             token_mgr,
             token,
+            monitor_cache: self.monitor_cache.clone(),
+            auth_providers: self.auth_providers.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            blobs: self.blobs.clone(),
+            client_ip: None,
         }
     }
 }
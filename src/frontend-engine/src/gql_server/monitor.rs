@@ -1,58 +1,362 @@
 use super::prelude::*;
-use std::{collections::HashMap, convert::TryInto, num::NonZeroU32};
+use slog_scope::warn;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
 
-fn lower_run(r: &db::schema::Run) -> ranker::Run {
+/// Revision is taken from sequence before transaction is committed, so change
+/// with smaller revision can become visible later than change with bigger one.
+/// That's why runs with revisions slightly smaller than last seen are re-fetched.
+/// This makes late changes visible quickly in most cases; changes, which are
+/// committed even later, are picked up by periodic full rebuild.
+const REVISION_RESCAN_WINDOW: db::schema::RunRevision = 100;
+
+/// Monitor is rebuilt from scratch this often
+const FULL_REBUILD_INTERVAL: Duration = Duration::from_secs(60);
+
+fn make_id(idx: usize) -> NonZeroU32 {
+    NonZeroU32::new((idx + 1).try_into().unwrap()).unwrap()
+}
+
+fn lower_run(
+    r: &db::schema::Run,
+    party: ranker::PartyId,
+    problem: ranker::ProblemId,
+) -> ranker::Run {
     let mut subtasks = HashMap::new();
-    subtasks.insert(ranker::SubtaskId(NonZeroU32::new(1).unwrap()), r.score);
-    // TODO: properly support subtasks
-    // TODO: keep party info for runs
-    // TODO: keep problem_id for runs
+    if r.subtask_scores.is_empty() {
+        // valuer did not report subtasks, so whole problem is one subtask
+        subtasks.insert(ranker::SubtaskId(make_id(0)), r.score);
+    } else {
+        for (i, &score) in r.subtask_scores.iter().enumerate() {
+            subtasks.insert(ranker::SubtaskId(make_id(i)), score);
+        }
+    }
     ranker::Run {
         subtasks,
-        party: ranker::PartyId(NonZeroU32::new(1).unwrap()),
-        problem: ranker::ProblemId(NonZeroU32::new(1).unwrap()),
+        party,
+        problem,
+        time: lower_time(r.submit_time),
     }
 }
 
+fn lower_time(time: chrono::DateTime<chrono::Utc>) -> ranker::Time {
+    // times before unix epoch are not expected
+    std::cmp::max(time.timestamp(), 0) as ranker::Time
+}
+
+fn lower_run_id(id: db::schema::RunId) -> ranker::RunId {
+    ranker::RunId(make_id(id as usize))
+}
+
 fn lower_problem(prob: &cfg::Problem) -> ranker::ProblemConfig {
-    // TODO: get all this stuff from problem config
     ranker::ProblemConfig {
         name: prob.title.to_string(),
-        accepted_score: 100,
-        score_runs: ranker::RunScoreAggregationTarget::Best,
-        aggregation: ranker::RunScoreAggregation::Max,
+        accepted_score: prob
+            .accepted_score
+            .try_into()
+            .unwrap_or(ranker::Score::max_value()),
+        score_runs: match prob.score_runs {
+            cfg::ScoreRuns::Latest(cnt) => ranker::RunScoreAggregationTarget::Latest(cnt),
+            cfg::ScoreRuns::Best => ranker::RunScoreAggregationTarget::Best,
+            cfg::ScoreRuns::All => ranker::RunScoreAggregationTarget::All,
+        },
+        aggregation: match prob.score_aggregation {
+            cfg::ScoreAggregation::Max => ranker::RunScoreAggregation::Max,
+            cfg::ScoreAggregation::MergeSubtasks => ranker::RunScoreAggregation::MergeSubtasks,
+        },
     }
 }
 
-pub(super) fn get_standings(ctx: &Context) -> ApiResult<String> {
-    let runs = ctx.db.run_select(None, None).internal(ctx)?;
-    let ranker_runs = runs.iter().map(lower_run).collect::<Vec<_>>();
-    let mut ranker_problems = ctx
-        .cfg
-        .problems
-        .iter()
-        .map(|(prob_name, prob_cfg)| (prob_name.clone(), lower_problem(prob_cfg)))
-        .collect::<Vec<_>>();
+/// Monitors of contests by contest id, shared between requests.
+/// Monitor is created when it is requested first time.
+pub(crate) type MonitorCaches = HashMap<String, MonitorCache>;
+
+/// Monitor of one contest.
+///
+/// Only runs changed since last request are fetched from DB and applied.
+pub(crate) struct MonitorCache {
+    created_at: Instant,
+    builder: ranker::MonitorBuilder,
+    problem_ids: HashMap<String, ranker::ProblemId>,
+    // TODO: take parties from contest registration instead of runs
+    party_ids: HashMap<uuid::Uuid, ranker::PartyId>,
+    /// Reverse of `party_ids`
+    party_users: HashMap<ranker::PartyId, uuid::Uuid>,
+    labels: ranker::Labels,
+    last_revision: db::schema::RunRevision,
+    /// Revisions of applied runs, used to skip re-fetched runs which did not change
+    applied_revisions: HashMap<db::schema::RunId, db::schema::RunRevision>,
+}
+
+impl MonitorCache {
+    pub(crate) fn new(contest: &cfg::Contest) -> MonitorCache {
+        let mut problems = contest.problems.iter().collect::<Vec<_>>();
+        problems.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut problem_ids = HashMap::new();
+        let mut ranker_problems = Vec::new();
+        let mut labels = ranker::Labels::default();
+        for (i, prob_cfg) in problems.into_iter().enumerate() {
+            let id = ranker::ProblemId(make_id(i));
+            problem_ids.insert(prob_cfg.name.clone(), id);
+            ranker_problems.push((id, lower_problem(prob_cfg)));
+            labels.problems.push((id, prob_cfg.code.clone()));
+        }
+        labels.problems.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let mut builder = ranker::MonitorBuilder::new(&ranker_problems);
+        builder.set_start_time(contest.start_time.map(lower_time));
+        builder.set_freeze_time(contest.freeze_time.map(lower_time));
+
+        MonitorCache {
+            created_at: Instant::now(),
+            builder,
+            problem_ids,
+            party_ids: HashMap::new(),
+            party_users: HashMap::new(),
+            labels,
+            last_revision: 0,
+            applied_revisions: HashMap::new(),
+        }
+    }
+
+    /// Applies all runs, changed or deleted since previous refresh
+    pub(crate) fn refresh(&mut self, db: &dyn db::DbConn) -> Result<(), db::Error> {
+        // everything is fetched before cache is touched, so that failed
+        // refresh does not leave cache in inconsistent state
+        let since = std::cmp::max(self.last_revision - REVISION_RESCAN_WINDOW, 0);
+        let changed = db.run_select_changed(since)?;
+        let deleted = db.run_select_deleted(since)?;
+        let mut new_users = HashMap::new();
+        for run in &changed {
+            if self.party_ids.contains_key(&run.user_id) || new_users.contains_key(&run.user_id) {
+                continue;
+            }
+            let username = db
+                .user_try_load_by_id(run.user_id)?
+                .map(|user| user.username);
+            new_users.insert(run.user_id, username);
+        }
+
+        for run in &changed {
+            if self.applied_revisions.get(&run.id) != Some(&run.revision) {
+                if let Err(err) = self.apply_run(run, &mut new_users) {
+                    // run is not retried until it is changed, e.g. rejudged
+                    warn!("Run is excluded from standings"; "run" => run.id, "message" => %err);
+                    self.builder.remove_run(lower_run_id(run.id));
+                    self.applied_revisions.insert(run.id, run.revision);
+                }
+            }
+            self.last_revision = std::cmp::max(self.last_revision, run.revision);
+        }
+        for deletion in &deleted {
+            self.builder.remove_run(lower_run_id(deletion.run_id));
+            self.applied_revisions.remove(&deletion.run_id);
+            self.last_revision = std::cmp::max(self.last_revision, deletion.revision);
+        }
+        Ok(())
+    }
+
+    fn apply_run(
+        &mut self,
+        run: &db::schema::Run,
+        new_users: &mut HashMap<uuid::Uuid, Option<String>>,
+    ) -> Result<(), ranker::InvalidRunError> {
+        let problem = match self.problem_ids.get(&run.problem_id) {
+            Some(&id) => id,
+            // run for problem which is not in contest anymore
+            None => return Ok(()),
+        };
+        let party = match self.party_ids.get(&run.user_id) {
+            Some(&id) => id,
+            None => ranker::PartyId(make_id(self.party_ids.len())),
+        };
+        self.builder
+            .upsert_run(lower_run_id(run.id), lower_run(run, party, problem))?;
+        if !self.party_ids.contains_key(&run.user_id) {
+            self.party_ids.insert(run.user_id, party);
+            self.party_users.insert(party, run.user_id);
+            if let Some(Some(username)) = new_users.remove(&run.user_id) {
+                self.labels.parties.insert(party, username);
+            }
+        }
+        self.applied_revisions.insert(run.id, run.revision);
+        Ok(())
+    }
+
+    pub(crate) fn monitor(&self) -> ranker::Monitor {
+        self.builder.monitor()
+    }
+
+    /// Monitor as seen by user: frozen, except for user's own results
+    pub(crate) fn monitor_for_user(&self, user_id: uuid::Uuid) -> ranker::Monitor {
+        match self.party_ids.get(&user_id) {
+            Some(&party) => self.builder.monitor_for_party(party),
+            None => self.builder.monitor(),
+        }
+    }
+
+    pub(crate) fn unfrozen_monitor(&self) -> ranker::Monitor {
+        self.builder.unfrozen_monitor()
+    }
 
-    ranker_problems.sort_by(|k1, k2| k1.0.cmp(&k2.0));
+    pub(crate) fn labels(&self) -> &ranker::Labels {
+        &self.labels
+    }
+
+    fn party_user(&self, party: ranker::PartyId) -> Option<uuid::Uuid> {
+        self.party_users.get(&party).copied()
+    }
+}
 
-    let mut ranker_problems_with_id = Vec::new();
-    for (i, prob_cfg) in ranker_problems.into_iter().map(|x| x.1).enumerate() {
-        let id = NonZeroU32::new((i + 1).try_into().unwrap()).unwrap();
-        ranker_problems_with_id.push((ranker::ProblemId(id), prob_cfg));
+/// Returns contest, used when request does not specify one
+pub(super) fn default_contest(ctx: &Context) -> ApiResult<&'static str> {
+    match ctx.cfg.default_contest_id() {
+        Some(contest_id) => Ok(contest_id),
+        None => Err(ApiError::new(ctx, "NoDefaultContest")),
     }
+}
 
-    let ranker_config = ranker::Config {
-        penalty_aggregation: ranker::PenaltyAggregation::Sum,
-        score_problems: ranker::ProblemScoreAggregationTarget::All,
+/// Runs `f` with up-to-date monitor of contest `contest_id`
+fn with_monitor<T>(
+    ctx: &Context,
+    contest_id: &str,
+    f: impl FnOnce(&cfg::Contest, &MonitorCache) -> ApiResult<T>,
+) -> ApiResult<T> {
+    let contest = match ctx.cfg.find_contest(contest_id) {
+        Some(contest) => contest,
+        None => return Err(ApiError::new(ctx, "ContestNotFound")),
     };
+    let mut caches = ctx.monitor_cache.lock().unwrap();
+    let cache = caches
+        .entry(contest_id.to_string())
+        .or_insert_with(|| MonitorCache::new(contest));
+    if cache.created_at.elapsed() >= FULL_REBUILD_INTERVAL {
+        *cache = MonitorCache::new(contest);
+    }
+    cache.refresh(&*ctx.db).internal(ctx)?;
+    f(contest, cache)
+}
+
+fn check_can_view_unfrozen(ctx: &Context, contest_id: &str) -> ApiResult<()> {
+    if ctx.cfg.find_contest(contest_id).is_none() {
+        return Err(ApiError::new(ctx, "ContestNotFound"));
+    }
+    if !ctx
+        .access()
+        .user_can_view_unfrozen_standings(contest_id)
+        .internal(ctx)?
+    {
+        return Err(ApiError::access_denied(ctx));
+    }
+    Ok(())
+}
+
+pub(super) fn get_standings(ctx: &Context, contest_id: &str) -> ApiResult<String> {
+    with_monitor(ctx, contest_id, |_, cache| {
+        let monitor = cache.monitor_for_user(ctx.token.user_id());
+        Ok(serde_json::to_string(&monitor).unwrap())
+    })
+}
+
+pub(super) fn get_unfrozen_standings(ctx: &Context, contest_id: &str) -> ApiResult<String> {
+    check_can_view_unfrozen(ctx, contest_id)?;
+    with_monitor(ctx, contest_id, |_, cache| {
+        Ok(serde_json::to_string(&cache.unfrozen_monitor()).unwrap())
+    })
+}
 
-    let monitor = ranker::build_monitor(
-        &ranker_runs,
-        &ranker_problems_with_id,
-        &[ranker::PartyId(NonZeroU32::new(1).unwrap())],
-        &ranker_config,
-    );
+pub(super) fn get_reveal_sequence(ctx: &Context, contest_id: &str) -> ApiResult<String> {
+    check_can_view_unfrozen(ctx, contest_id)?;
+    with_monitor(ctx, contest_id, |_, cache| {
+        let steps = ranker::reveal_sequence(&cache.monitor(), &cache.unfrozen_monitor());
+        Ok(serde_json::to_string(&steps).unwrap())
+    })
+}
+
+pub(super) fn get_typed_standings(
+    ctx: &Context,
+    contest_id: &str,
+    unfrozen: bool,
+) -> ApiResult<schema::Standings> {
+    if unfrozen {
+        check_can_view_unfrozen(ctx, contest_id)?;
+    }
+    with_monitor(ctx, contest_id, |contest, cache| {
+        let monitor = if unfrozen {
+            cache.unfrozen_monitor()
+        } else {
+            cache.monitor_for_user(ctx.token.user_id())
+        };
+        let standings = ranker::Standings::new(&monitor, cache.labels());
+
+        let problems = cache
+            .labels()
+            .problems
+            .iter()
+            .map(|(id, code)| {
+                let title = contest
+                    .problems
+                    .iter()
+                    .find(|prob| &prob.code == code)
+                    .map(|prob| prob.title.clone())
+                    .unwrap_or_default();
+                schema::StandingsProblem {
+                    id: code.clone(),
+                    title,
+                    stats: (&monitor.stats.problems[id]).into(),
+                }
+            })
+            .collect();
+        let rows = standings
+            .rows
+            .into_iter()
+            .map(|row| schema::StandingsRow {
+                place: row.place as i32,
+                party: schema::Party {
+                    user_id: cache.party_user(row.party_id),
+                    name: row.party,
+                },
+                solved: row.solved as i32,
+                score: row.score,
+                penalty: row.penalty as i32,
+                cells: row
+                    .cells
+                    .into_iter()
+                    .zip(standings.problems.iter())
+                    .map(|(cell, code)| schema::StandingsCell {
+                        problem: code.clone(),
+                        empty: cell.empty,
+                        ok: cell.ok,
+                        score: cell.score,
+                        attempts: cell.attempts as i32,
+                        pending: cell.pending as i32,
+                        penalty: cell.penalty as i32,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(schema::Standings {
+            frozen: !unfrozen && contest.freeze_time.is_some(),
+            problems,
+            rows,
+        })
+    })
+}
 
-    Ok(serde_json::to_string(&monitor).unwrap())
+/// Renders public (i.e. frozen) standings for publishing
+pub(super) fn export_standings(
+    ctx: &Context,
+    contest_id: &str,
+    format: ranker::ExportFormat,
+) -> ApiResult<String> {
+    with_monitor(ctx, contest_id, |_, cache| {
+        let standings = ranker::Standings::new(&cache.monitor(), cache.labels());
+        Ok(standings.render(format))
+    })
 }
@@ -0,0 +1,86 @@
+//! Installing problem packages
+use super::{audit, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{fs, process::Command, sync::Arc};
+
+/// Output of `jjs-ppc install --json`
+#[derive(Deserialize, Serialize)]
+struct PackInfo {
+    name: String,
+    version: String,
+    checksum: String,
+}
+
+/// Installs archive with `jjs-ppc install`, so that installation is same as
+/// when it is performed from command line
+pub(super) fn install(ctx: &Context, archive: String) -> ApiResult<schema::ProblemPackage> {
+    if !ctx.access().user_can_install_problems().internal(ctx)? {
+        return Err(ApiError::access_denied(ctx));
+    }
+    let archive = match base64::decode(&archive) {
+        Ok(archive) => archive,
+        Err(_) => return Err(ApiError::new(ctx, "ArchiveNotBase64")),
+    };
+    let archive_path = std::env::temp_dir().join(format!(
+        "jjs-problem-archive-{}.tar.gz",
+        uuid::Uuid::new_v4()
+    ));
+    fs::write(&archive_path, archive).internal(ctx)?;
+    let output = Command::new(ctx.cfg.install_dir.join("bin/jjs-ppc"))
+        .arg("install")
+        .arg("--json")
+        .arg("--archive")
+        .arg(&archive_path)
+        .arg("--sysroot")
+        .arg(&ctx.cfg.sysroot)
+        .output();
+    fs::remove_file(&archive_path).ok();
+    let output = output.internal(ctx)?;
+    if !output.status.success() {
+        // ppc describes problem with archive in stderr
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let mut ext = ErrorExtension::new();
+        ext.set_error_code("InvalidProblemArchive");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, message)).report_ext(ctx, ext);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let info: PackInfo =
+        serde_json::from_str(stdout.lines().last().unwrap_or_default()).internal(ctx)?;
+    // package is already installed, so failure is reported explicitly
+    let audit_res = ctx.db.transaction(&mut |db| {
+        audit::record(
+            ctx,
+            db,
+            "problem.install",
+            format!("problem/{}", info.name),
+            None,
+            Some(serde_json::to_value(&info).unwrap()),
+        )
+    });
+    if let Err(err) = audit_res {
+        let message = format!(
+            "problem {} was installed, but audit record was not written: {}",
+            info.name, err
+        );
+        let mut ext = ErrorExtension::new();
+        ext.set_error_code("AuditRecordNotWritten");
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, message)).report_ext(ctx, ext);
+    }
+    reload_problem(ctx, &info.name)?;
+    Ok(schema::ProblemPackage {
+        name: info.name,
+        version: info.version,
+        checksum: info.checksum,
+    })
+}
+
+/// Makes installed version visible to subsequent requests
+fn reload_problem(ctx: &Context, name: &str) -> ApiResult<()> {
+    let mut cfg = ctx.cfg_handle.write().unwrap();
+    let mut new_cfg = cfg::Config::clone(&cfg);
+    new_cfg.reload_problem(name).internal(ctx)?;
+    *cfg = Arc::new(new_cfg);
+    // monitors contain problem titles
+    ctx.monitor_cache.lock().unwrap().clear();
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use super::{auth, misc, monitor, prelude::*, runs, schema, users, Context, Mutation, Query};
+use super::{
+    audit, auth, misc, monitor, prelude::*, problems, runs, schema, users, Context, Mutation, Query,
+};
+use crate::security::Credentials;
 
 #[juniper::object(Context = Context)]
 impl Query {
@@ -26,12 +29,87 @@ impl Query {
     }
 
     /// List runs
+    ///
+    /// Users without `view-all-runs` capability only see their own runs.
+    /// Capability is checked in contest from `filter`; if contest is not set,
+    /// only global role grants are taken into account.
+    #[graphql(arguments(
+        id(description = "If set, only run with this id is returned"),
+        filter(description = "Conditions runs must satisfy"),
+        order(description = "Order of runs (default is oldest first)"),
+        after(description = "Id of last run on previous page; only runs after it are returned"),
+        limit(description = "Max count of runs to return, at most 100 (all runs if not set)")
+    ))]
     fn runs(
         ctx: &Context,
         id: Option<schema::RunId>,
+        filter: Option<schema::RunsFilter>,
+        order: Option<schema::RunsOrder>,
+        after: Option<schema::RunId>,
         limit: Option<i32>,
     ) -> ApiResult<Vec<schema::Run>> {
-        runs::list(ctx, id, limit)
+        runs::list(ctx, id, filter, order, after, limit)
+    }
+
+    /// List sessions of user
+    ///
+    /// If `login` is not set, sessions of current user are returned.
+    /// Only users with `manage-users` capability can list sessions of other users.
+    fn sessions(ctx: &Context, login: Option<String>) -> ApiResult<Vec<schema::Session>> {
+        auth::list_sessions(ctx, login)
+    }
+
+    /// List users, ordered by login
+    ///
+    /// Requires `manage-users` capability.
+    #[graphql(arguments(
+        search(description = "If set, only users whose login contains this string are returned"),
+        offset(description = "Count of users to skip"),
+        limit(description = "Max count of users to return (default and max value is 100)")
+    ))]
+    fn users(
+        ctx: &Context,
+        search: Option<String>,
+        offset: Option<i32>,
+        limit: Option<i32>,
+    ) -> ApiResult<Vec<schema::User>> {
+        users::list(ctx, search, offset, limit)
+    }
+
+    /// Browse log of privileged actions, newest records first
+    ///
+    /// Requires `view-audit-log` capability. Capability, granted in contest, only
+    /// allows to view records about runs in this contest.
+    #[graphql(arguments(
+        action(description = "If set, only records of this action are returned"),
+        before(description = "If set, only records with smaller id are returned"),
+        limit(description = "Max count of records to return (default and max value is 100)")
+    ))]
+    fn audit_log(
+        ctx: &Context,
+        action: Option<String>,
+        before: Option<i32>,
+        limit: Option<i32>,
+    ) -> ApiResult<Vec<schema::AuditRecord>> {
+        audit::list(ctx, action, before, limit)
+    }
+
+    /// List external authentication providers
+    fn auth_providers(ctx: &Context) -> Vec<schema::AuthProvider> {
+        auth::list_providers(ctx)
+    }
+
+    /// URL, user should be redirected to in order to log in with redirect-based provider
+    ///
+    /// After authorization, provider redirects user back with `code` and `state`
+    /// query parameters. Client must check that `state` is unchanged and then call
+    /// `authExternalCode`.
+    #[graphql(arguments(
+        provider(description = "Provider name"),
+        state(description = "Random string, protecting against CSRF")
+    ))]
+    fn auth_provider_url(ctx: &Context, provider: String, state: String) -> ApiResult<String> {
+        auth::provider_url(ctx, &provider, &state)
     }
 
     /// List toolchains
@@ -44,9 +122,76 @@ impl Query {
         misc::get_contests(ctx)
     }
 
+    /// Get standings
+    ///
+    /// If `unfrozen` is true, results of runs submitted after freeze are shown.
+    /// This is only available for judges.
+    #[graphql(arguments(
+        contest(description = "contest ID (currently only contest=\"TODO\" is supported)"),
+        unfrozen(description = "Ignore standings freeze (default: false)")
+    ))]
+    fn standings(
+        ctx: &Context,
+        contest: schema::ContestId,
+        unfrozen: Option<bool>,
+    ) -> ApiResult<schema::Standings> {
+        monitor::get_typed_standings(ctx, &contest, unfrozen.unwrap_or(false))
+    }
+
     /// Get standings as JSON-encoded string
-    fn standings_simple(ctx: &Context) -> ApiResult<String> {
-        monitor::get_standings(ctx)
+    ///
+    /// Prefer `standings`, which is properly typed.
+    /// Own results of user are shown even if standings are frozen.
+    #[graphql(arguments(contest(description = "contest ID (default: \"TODO\")")))]
+    fn standings_simple(ctx: &Context, contest: Option<schema::ContestId>) -> ApiResult<String> {
+        monitor::get_standings(ctx, contest_or_default(ctx, &contest)?)
+    }
+
+    /// Get standings, rendered for publishing
+    ///
+    /// Supported formats are "html", "csv" and "json".
+    /// Layout of JSON export is stable, unlike `standingsSimple`.
+    #[graphql(arguments(
+        format(description = "One of: html, csv, json"),
+        contest(description = "contest ID (default: \"TODO\")")
+    ))]
+    fn standings_export(
+        ctx: &Context,
+        format: String,
+        contest: Option<schema::ContestId>,
+    ) -> ApiResult<String> {
+        let format = format.parse::<ranker::ExportFormat>().report(ctx)?;
+        monitor::export_standings(ctx, contest_or_default(ctx, &contest)?, format)
+    }
+
+    /// Get standings ignoring freeze as JSON-encoded string
+    ///
+    /// Only available for judges.
+    #[graphql(arguments(contest(description = "contest ID (default: \"TODO\")")))]
+    fn standings_unfrozen(ctx: &Context, contest: Option<schema::ContestId>) -> ApiResult<String> {
+        monitor::get_unfrozen_standings(ctx, contest_or_default(ctx, &contest)?)
+    }
+
+    /// Get order, in which frozen standings cells should be revealed, as JSON-encoded string
+    ///
+    /// Each step contains party, problem and cell after reveal.
+    /// Only available for judges.
+    #[graphql(arguments(contest(description = "contest ID (default: \"TODO\")")))]
+    fn standings_reveal_sequence(
+        ctx: &Context,
+        contest: Option<schema::ContestId>,
+    ) -> ApiResult<String> {
+        monitor::get_reveal_sequence(ctx, contest_or_default(ctx, &contest)?)
+    }
+}
+
+fn contest_or_default<'a>(
+    ctx: &'a Context,
+    contest: &'a Option<schema::ContestId>,
+) -> ApiResult<&'a str> {
+    match contest {
+        Some(contest) => Ok(contest),
+        None => monitor::default_contest(ctx),
     }
 }
 
@@ -84,6 +229,66 @@ impl Mutation {
         users::create(ctx, login, password, groups)
     }
 
+    /// Change password of current user
+    ///
+    /// All other sessions of user are revoked.
+    /// On success, 0 is returned.
+    fn change_password(
+        ctx: &Context,
+        old_password: String,
+        new_password: String,
+    ) -> ApiResult<i32> {
+        users::change_password(ctx, old_password, new_password).map(|_| 0)
+    }
+
+    /// Set new password for user and revoke all user's sessions
+    ///
+    /// Requires `manage-users` capability.
+    /// On success, 0 is returned.
+    fn reset_password(ctx: &Context, login: String, password: String) -> ApiResult<i32> {
+        users::reset_password(ctx, login, password).map(|_| 0)
+    }
+
+    /// Add user to groups and remove user from groups
+    ///
+    /// If user is removed from some group, all sessions of user are revoked.
+    /// Requires `manage-users` capability.
+    #[graphql(arguments(
+        login(description = "login"),
+        add(description = "Groups user should be added to"),
+        remove(description = "Groups user should be removed from")
+    ))]
+    fn modify_user_groups(
+        ctx: &Context,
+        login: String,
+        add: Option<Vec<String>>,
+        remove: Option<Vec<String>>,
+    ) -> ApiResult<schema::User> {
+        users::modify_groups(
+            ctx,
+            login,
+            add.unwrap_or_default(),
+            remove.unwrap_or_default(),
+        )
+    }
+
+    /// Disable or enable user
+    ///
+    /// Disabled user can not log in, and all user's sessions are revoked.
+    /// Requires `manage-users` capability.
+    fn set_user_disabled(ctx: &Context, login: String, disabled: bool) -> ApiResult<schema::User> {
+        users::set_disabled(ctx, login, disabled)
+    }
+
+    /// Delete user
+    ///
+    /// Users who have runs can not be deleted (disable them instead).
+    /// Requires `manage-users` capability.
+    /// On success, 0 is returned.
+    fn delete_user(ctx: &Context, login: String) -> ApiResult<i32> {
+        users::delete(ctx, login).map(|_| 0)
+    }
+
     /// Modify run
     ///
     /// Depending on arguments provided, two behaviors are possible
@@ -94,6 +299,7 @@ impl Mutation {
     ///
     /// 2) Update run according to given arguments
     ///
+    /// Requires `rejudge` capability in contest of run's problem.
     /// On success, 0 is returned.
     #[graphql(arguments(
         id(description = "Id of run to operate on"),
@@ -112,6 +318,15 @@ impl Mutation {
         runs::modify(ctx, id, status, rejudge, delete).map(|_| 0)
     }
 
+    /// Install problem package, created by `jjs-ppc pack`
+    ///
+    /// Previous versions are kept, so runs being judged are not affected.
+    /// Requires `install-problems` capability.
+    #[graphql(arguments(archive(description = "archive, base64-encoded")))]
+    fn install_problem(ctx: &Context, archive: String) -> ApiResult<schema::ProblemPackage> {
+        problems::install(ctx, archive)
+    }
+
     /// Login using login and password
     ///
     /// See `SessionToken` documentation for more details.
@@ -122,4 +337,52 @@ impl Mutation {
     ) -> ApiResult<schema::SessionToken> {
         auth::simple(ctx, login, password)
     }
+
+    /// Login using login and password, checked by external provider (e.g. LDAP)
+    ///
+    /// On first login, user is created with login `<provider>/<login>`.
+    fn auth_external_password(
+        ctx: &Context,
+        provider: String,
+        login: String,
+        password: String,
+    ) -> ApiResult<schema::SessionToken> {
+        let credentials = Credentials::Password {
+            login: &login,
+            password: &password,
+        };
+        auth::external(ctx, &provider, credentials)
+    }
+
+    /// Login using authorization code, issued by external provider (e.g. OpenID Connect)
+    ///
+    /// See `authProviderUrl` for details.
+    /// On first login, user is created with login `<provider>/<login>`.
+    fn auth_external_code(
+        ctx: &Context,
+        provider: String,
+        code: String,
+    ) -> ApiResult<schema::SessionToken> {
+        auth::external(
+            ctx,
+            &provider,
+            Credentials::AuthorizationCode { code: &code },
+        )
+    }
+
+    /// Revoke session of current token
+    ///
+    /// Returns false if token is not bound to session (e.g. guest token)
+    fn logout(ctx: &Context) -> ApiResult<bool> {
+        auth::logout(ctx)
+    }
+
+    /// Revoke session
+    ///
+    /// Users can revoke own sessions; users with `manage-users` capability
+    /// can revoke any session.
+    /// On success, 0 is returned.
+    fn revoke_session(ctx: &Context, id: schema::SessionId) -> ApiResult<i32> {
+        auth::revoke_session(ctx, id).map(|_| 0)
+    }
 }
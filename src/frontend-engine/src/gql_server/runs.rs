@@ -1,7 +1,9 @@
 use super::{
+    audit,
     prelude::*,
     schema::{Run, RunId},
 };
+use crate::security::AccessCheckError;
 
 fn describe_submission(submission: &db::schema::Run) -> Run {
     Run {
@@ -16,16 +18,131 @@ fn describe_submission(submission: &db::schema::Run) -> Run {
     }
 }
 
-pub(super) fn list(ctx: &Context, id: Option<RunId>, limit: Option<i32>) -> ApiResult<Vec<Run>> {
-    let user_submissions = ctx
+const MAX_LIST_LIMIT: i32 = 100;
+
+/// Converts API filter into repo filter. Returns `None` if no run can match it.
+fn make_filter(
+    ctx: &Context,
+    filter: schema::RunsFilter,
+) -> ApiResult<Option<db::schema::RunFilter>> {
+    let contests: Vec<&cfg::Contest> = match &filter.contest {
+        Some(contest) => match ctx.cfg.find_contest(contest) {
+            Some(contest_cfg) => vec![contest_cfg],
+            None => return Err(ApiError::new(ctx, "ContestUnknown")),
+        },
+        None => ctx.cfg.contests.iter().collect(),
+    };
+    let view_all = ctx
+        .access()
+        .user_can_view_all_runs(filter.contest.as_ref().map(String::as_str))
+        .internal(ctx)?;
+    let user_id = match filter.user {
+        Some(login) => {
+            // callers that can only see their own runs must not learn
+            // whether some other login exists
+            let user = match ctx.db.user_try_load_by_login(&login).internal(ctx)? {
+                Some(user) if view_all || user.id == ctx.token.user_id() => user,
+                Some(_) => return Err(ApiError::access_denied(ctx)),
+                None if view_all => return Err(ApiError::new(ctx, "UnknownUser")),
+                None => return Err(ApiError::access_denied(ctx)),
+            };
+            Some(user.id)
+        }
+        None if view_all => None,
+        None => Some(ctx.token.user_id()),
+    };
+    // runs reference problems by name, so problem and contest are resolved to names
+    let problem_ids = if filter.contest.is_some() || filter.problem.is_some() {
+        let names: Vec<_> = contests
+            .iter()
+            .flat_map(|contest| contest.problems.iter())
+            .filter(|problem| {
+                filter
+                    .problem
+                    .as_ref()
+                    .map_or(true, |code| &problem.code == code)
+            })
+            .map(|problem| problem.name.clone())
+            .collect();
+        if names.is_empty() {
+            return Ok(None);
+        }
+        Some(names)
+    } else {
+        None
+    };
+    Ok(Some(db::schema::RunFilter {
+        id: None,
+        user_id,
+        problem_ids,
+        status_kind: filter.status_kind,
+        submitted_since: filter.submitted_since,
+        submitted_before: filter.submitted_before,
+    }))
+}
+
+pub(super) fn list(
+    ctx: &Context,
+    id: Option<RunId>,
+    filter: Option<schema::RunsFilter>,
+    order: Option<schema::RunsOrder>,
+    after: Option<RunId>,
+    limit: Option<i32>,
+) -> ApiResult<Vec<Run>> {
+    let limit = match limit {
+        Some(limit) if limit >= 0 => Some(std::cmp::min(limit, MAX_LIST_LIMIT) as u32),
+        Some(_) => return Err(ApiError::new(ctx, "InvalidLimit")),
+        None => None,
+    };
+    let mut filter = match make_filter(ctx, filter.unwrap_or_default())? {
+        Some(filter) => filter,
+        None => return Ok(Vec::new()),
+    };
+    filter.id = id;
+    let order = order.map_or(db::schema::RunOrder::OldestFirst, Into::into);
+    let runs = ctx
         .db
-        .run_select(id, limit.map(|x| x as u32))
+        .run_select(&filter, order, after, limit)
         .internal(ctx)?;
-    let user_submissions = user_submissions
+    Ok(runs.iter().map(describe_submission).collect())
+}
+
+/// Decodes source and checks it against contest and toolchain restrictions,
+/// so that invalid submissions are rejected before run is created
+fn check_source(
+    ctx: &Context,
+    contest: &cfg::Contest,
+    toolchain: &cfg::Toolchain,
+    code: &str,
+) -> ApiResult<Vec<u8>> {
+    let max_size = match toolchain.max_source_size {
+        Some(size) => std::cmp::min(size, contest.max_source_size),
+        None => contest.max_source_size,
+    };
+    // base64 encodes each 3 bytes as 4 chars, so we can reject huge sources without decoding
+    if code.len() as u64 > (max_size + 2) / 3 * 4 {
+        return Err(ApiError::new(ctx, "SourceTooLarge"));
+    }
+    let decoded_code = match base64::decode(code) {
+        Ok(decoded_code) => decoded_code,
+        Err(_) => return Err(ApiError::new(ctx, "SourceNotBase64")),
+    };
+    if decoded_code.len() as u64 > max_size {
+        return Err(ApiError::new(ctx, "SourceTooLarge"));
+    }
+    if decoded_code.iter().all(u8::is_ascii_whitespace) {
+        return Err(ApiError::new(ctx, "SourceEmpty"));
+    }
+    for pattern in contest
+        .forbidden_patterns
         .iter()
-        .map(|s| describe_submission(s))
-        .collect();
-    Ok(user_submissions)
+        .chain(toolchain.forbidden_patterns.iter())
+    {
+        if pattern.is_match(&decoded_code) {
+            return Err(ApiError::new(ctx, "SourceForbiddenPattern"));
+        }
+    }
+    Ok(decoded_code)
 }
 
 pub(super) fn submit_simple(
@@ -41,13 +158,14 @@ pub(super) fn submit_simple(
         Some(tc) => tc.clone(),
         None => return Err(ApiError::new(ctx, "ToolchainUnknown")),
     };
-    if contest != "TODO" {
-        return Err(ApiError::new(ctx, "ContestUnknown"));
-    }
+    let contest_cfg = match ctx.cfg.find_contest(&contest) {
+        Some(contest_cfg) => contest_cfg,
+        None => return Err(ApiError::new(ctx, "ContestUnknown")),
+    };
     if !ctx.access().user_can_submit(&contest).internal(ctx)? {
         return Err(ApiError::access_denied(ctx));
     }
-    let problem = ctx.cfg.contests[0]
+    let problem = contest_cfg
         .problems
         .iter()
         .find(|pr| pr.code == problem)
@@ -56,38 +174,52 @@ pub(super) fn submit_simple(
         Some(p) => p,
         None => return Err(ApiError::new(ctx, "ProblemUnknown")),
     };
-    let prob_name = problem.name.clone();
-
-    let new_run = db::schema::NewRun {
-        toolchain_id: toolchain.name,
-        status_code: "QUEUE_JUDGE".to_string(),
-        status_kind: "QUEUE".to_string(),
-        problem_id: prob_name,
-        score: 0,
-        rejudge_id: 1,
-        user_id: ctx.token.user_id(),
-    };
-
-    let run = ctx.db.run_new(new_run).internal(ctx)?;
-
-    // Put run in sysroot
-    let run_dir = ctx
-        .cfg
-        .sysroot
-        .join("var/submissions")
-        .join(&format!("s-{}", run.id));
-    std::fs::create_dir(&run_dir).internal(ctx)?;
-    let submission_src_path = run_dir.join("source");
-    let decoded_code = base64::decode(&code).report(ctx)?;
-    std::fs::write(submission_src_path, &decoded_code).internal(ctx)?;
-
-    // create invocation request
-    let new_inv_req = NewInvocationRequest {
-        invoke_revision: 0,
-        run_id: run.id,
+    let decoded_code = check_source(ctx, contest_cfg, &toolchain, &code)?;
+    // quota is given back if run is not created
+    let reservation = match ctx
+        .rate_limiter
+        .submission(ctx.token.user_id(), &problem.name)
+    {
+        Ok(reservation) => reservation,
+        Err(retry_after) => {
+            return Err(ApiError::rate_limited(
+                ctx,
+                "SubmissionRateLimited",
+                retry_after,
+            ))
+        }
     };
-
-    ctx.db.inv_req_new(new_inv_req).internal(ctx)?;
+    // store source first, so that run never exists without it
+    let source_hash = ctx.blobs.put(&decoded_code).internal(ctx)?;
+    let mut run = None;
+    ctx.db
+        .transaction(&mut |db| {
+            let new_run = db::schema::NewRun {
+                toolchain_id: toolchain.name.clone(),
+                status_code: "QUEUE_JUDGE".to_string(),
+                status_kind: "QUEUE".to_string(),
+                problem_id: problem.name.clone(),
+                score: 0,
+                rejudge_id: 1,
+                user_id: ctx.token.user_id(),
+            };
+            let new_run = db.run_new(new_run)?;
+            db.run_blob_set(db::schema::RunBlob {
+                run_id: new_run.id,
+                name: db::blob::RUN_SOURCE.to_string(),
+                hash: source_hash.clone(),
+            })?;
+            // create invocation request
+            db.inv_req_new(NewInvocationRequest {
+                invoke_revision: 0,
+                run_id: new_run.id,
+            })?;
+            run = Some(new_run);
+            Ok(())
+        })
+        .internal(ctx)?;
+    let run = run.unwrap();
+    reservation.commit();
 
     Ok(describe_submission(&run))
 }
@@ -99,23 +231,53 @@ pub(super) fn modify(
     rejudge: Option<bool>,
     delete: Option<bool>,
 ) -> ApiResult<()> {
-    if !ctx.access().user_can_modify_run(id).internal(ctx)? {
-        return Err(ApiError::access_denied(ctx));
+    match ctx.access().user_can_modify_run(id) {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::access_denied(ctx)),
+        Err(AccessCheckError::NotFound) => return Err(ApiError::new(ctx, "RunNotFound")),
+        Err(err) => return Err(err).internal(ctx),
     }
     let should_delete = delete.unwrap_or(false);
     if should_delete {
         if status.is_some() || rejudge.is_some() {
             return "both modification and delete were requested".report(ctx);
         }
-        ctx.db.run_delete(id).internal(ctx)?;
+        ctx.db
+            .transaction(&mut |db| {
+                let before = db.run_load(id)?;
+                db.run_delete(id)?;
+                audit::record(
+                    ctx,
+                    db,
+                    "run.delete",
+                    format!("run/{}", id),
+                    Some(audit::run_snapshot(&before)),
+                    None,
+                )
+            })
+            .internal(ctx)?;
     } else {
-        let mut patch = db::schema::RunPatch::default();
-        if let Some(new_status) = status {
-            patch.status_kind = Some(new_status.kind);
-            patch.status_code = Some(new_status.code);
-        }
-        // TODO: handle rejudge
-        ctx.db.run_update(id, patch).internal(ctx)?;
+        ctx.db
+            .transaction(&mut |db| {
+                let before = db.run_load(id)?;
+                let mut patch = db::schema::RunPatch::default();
+                if let Some(new_status) = &status {
+                    patch.status_kind = Some(new_status.kind.clone());
+                    patch.status_code = Some(new_status.code.clone());
+                }
+                // TODO: handle rejudge
+                db.run_update(id, patch)?;
+                let after = db.run_load(id)?;
+                audit::record(
+                    ctx,
+                    db,
+                    "run.update",
+                    format!("run/{}", id),
+                    Some(audit::run_snapshot(&before)),
+                    Some(audit::run_snapshot(&after)),
+                )
+            })
+            .internal(ctx)?;
     }
 
     Ok(())
@@ -1,17 +1,22 @@
 mod contest;
 mod run;
+mod standings;
 
 use juniper::{GraphQLInputObject, GraphQLObject};
 use uuid::Uuid;
 
 pub(crate) use contest::{Contest, Problem};
-pub(crate) use run::Run;
+pub(crate) use run::{Run, RunsFilter, RunsOrder};
+pub(crate) use standings::{
+    Party, ProblemStats, Standings, StandingsCell, StandingsProblem, StandingsRow,
+};
 
 pub type ToolchainId = String;
 pub type RunId = i32;
 pub type ProblemId = String;
 pub type ContestId = String;
 pub type UserId = Uuid;
+pub type SessionId = Uuid;
 
 #[derive(GraphQLInputObject)]
 pub(crate) struct InvokeStatusIn {
@@ -29,6 +34,9 @@ pub(crate) struct InvokeStatusOut {
 pub(crate) struct User {
     pub id: UserId,
     pub login: String,
+    pub groups: Vec<String>,
+    /// Disabled user can not log in
+    pub disabled: bool,
 }
 
 impl<'a> From<&'a db::schema::User> for User {
@@ -36,10 +44,87 @@ impl<'a> From<&'a db::schema::User> for User {
         User {
             id: user.id,
             login: user.username.clone(),
+            groups: user.groups.clone(),
+            disabled: user.disabled,
         }
     }
 }
 
+/// Session, i.e. token issued on login
+#[derive(GraphQLObject)]
+pub(crate) struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// True if session was revoked, e.g. by logout
+    pub revoked: bool,
+}
+
+impl<'a> From<&'a db::schema::Session> for Session {
+    fn from(session: &'a db::schema::Session) -> Session {
+        Session {
+            id: session.id,
+            user_id: session.user_id,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            revoked: session.revoked,
+        }
+    }
+}
+
+/// Record of audit log
+#[derive(GraphQLObject)]
+pub(crate) struct AuditRecord {
+    pub id: i32,
+    pub time: chrono::DateTime<chrono::Utc>,
+    /// Not set if action was not performed by user (e.g. token was issued
+    /// to local root)
+    pub actor_id: Option<UserId>,
+    pub actor_name: String,
+    /// Action, e.g. `run.delete`
+    pub action: String,
+    /// Affected entity, e.g. `run/42`
+    pub target: String,
+    /// JSON-encoded state of target before action
+    pub before: Option<String>,
+    /// JSON-encoded state of target after action
+    pub after: Option<String>,
+}
+
+impl<'a> From<&'a db::schema::AuditRecord> for AuditRecord {
+    fn from(record: &'a db::schema::AuditRecord) -> AuditRecord {
+        AuditRecord {
+            id: record.id,
+            time: record.time,
+            actor_id: record.actor_id,
+            actor_name: record.actor_name.clone(),
+            action: record.action.clone(),
+            target: record.target.clone(),
+            before: record.before.clone(),
+            after: record.after.clone(),
+        }
+    }
+}
+
+/// External authentication provider
+#[derive(GraphQLObject)]
+pub(crate) struct AuthProvider {
+    pub name: String,
+    /// `ldap` (login with password via `authExternalPassword`) or
+    /// `oidc` (redirect flow, finished by `authExternalCode`)
+    pub kind: String,
+}
+
+/// Installed version of problem package
+#[derive(GraphQLObject)]
+pub(crate) struct ProblemPackage {
+    pub name: ProblemId,
+    pub version: String,
+    /// SHA3-256 of package contents
+    pub checksum: String,
+}
+
 #[derive(GraphQLObject)]
 pub(crate) struct Toolchain {
     /// Human readable name, e.g. "GCC C++ v9.1 with sanitizers enables"
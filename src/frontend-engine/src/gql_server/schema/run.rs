@@ -1,8 +1,7 @@
 use crate::gql_server::{
     prelude::*,
-    schema::{InvokeStatusOut, Problem, RunId, Toolchain},
+    schema::{ContestId, InvokeStatusOut, Problem, ProblemId, RunId, Toolchain},
 };
-use std::path::PathBuf;
 
 pub(crate) struct Run {
     pub id: RunId,
@@ -13,17 +12,10 @@ pub(crate) struct Run {
 }
 
 impl Run {
-    fn data_dir(&self, ctx: &Context) -> PathBuf {
-        ctx.cfg
-            .sysroot
-            .join("var/submissions")
-            .join(format!("s-{}", self.id))
-    }
-
-    fn last_invoke_dir(&self, ctx: &Context) -> ApiResult<PathBuf> {
-        let rejudge_id = self.lookup(ctx)?.rejudge_id;
-        let f = format!("i-{}", rejudge_id);
-        Ok(self.data_dir(ctx).join(f))
+    /// Loads named file of run (see `db::blob`)
+    fn load_blob(&self, ctx: &Context, name: &str) -> ApiResult<Option<Vec<u8>>> {
+        db::blob::load_run_file(&*ctx.db, &*ctx.blobs, &ctx.cfg.sysroot, self.id, name)
+            .internal(ctx)
     }
 
     fn lookup(&self, ctx: &Context) -> ApiResult<db::schema::Run> {
@@ -31,6 +23,39 @@ impl Run {
     }
 }
 
+/// Restricts which runs are returned by `runs` query.
+/// All set conditions must hold.
+#[derive(GraphQLInputObject, Default)]
+pub(crate) struct RunsFilter {
+    /// Login of run author
+    pub user: Option<String>,
+    /// Only runs for problems of this contest
+    pub contest: Option<ContestId>,
+    /// Problem code. If `contest` is not set, problems with this code from all contests match
+    pub problem: Option<ProblemId>,
+    /// Status kind, e.g. `Accepted`
+    pub status_kind: Option<String>,
+    /// Only runs submitted at this moment or later
+    pub submitted_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only runs submitted before this moment
+    pub submitted_before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(juniper::GraphQLEnum, Copy, Clone)]
+pub(crate) enum RunsOrder {
+    OldestFirst,
+    NewestFirst,
+}
+
+impl From<RunsOrder> for db::schema::RunOrder {
+    fn from(order: RunsOrder) -> db::schema::RunOrder {
+        match order {
+            RunsOrder::OldestFirst => db::schema::RunOrder::OldestFirst,
+            RunsOrder::NewestFirst => db::schema::RunOrder::NewestFirst,
+        }
+    }
+}
+
 #[derive(GraphQLInputObject, Copy, Clone)]
 pub(crate) struct RunProtocolFilterParams {
     /// If false, compilation logs will be excluded
@@ -102,16 +127,14 @@ impl Run {
 
     /// Returns run source as base64-encoded string
     fn source(&self, ctx: &Context) -> ApiResult<Option<String>> {
-        let source_path = self.data_dir(ctx).join("source");
-        let source = std::fs::read(source_path).ok();
+        let source = self.load_blob(ctx, db::blob::RUN_SOURCE)?;
         let source = source.as_ref().map(base64::encode);
         Ok(source)
     }
 
     /// Returns run build artifact as base64-encoded string
     fn binary(&self, ctx: &Context) -> ApiResult<Option<String>> {
-        let binary_path = self.data_dir(ctx).join("build");
-        let binary = std::fs::read(binary_path).ok();
+        let binary = self.load_blob(ctx, db::blob::RUN_BUILD)?;
         let binary = binary.as_ref().map(base64::encode);
         Ok(binary)
     }
@@ -122,8 +145,8 @@ impl Run {
         ctx: &Context,
         filter: RunProtocolFilterParams,
     ) -> ApiResult<Option<String>> {
-        let path = self.last_invoke_dir(ctx)?.join("log.json");
-        let protocol = std::fs::read(path).ok();
+        let rejudge_id = self.lookup(ctx)?.rejudge_id;
+        let protocol = self.load_blob(ctx, &db::blob::run_judge_log(rejudge_id))?;
         match protocol {
             Some(protocol) => {
                 let protocol = String::from_utf8(protocol).internal(ctx)?;
@@ -0,0 +1,76 @@
+use super::{super::prelude::*, ProblemId, UserId};
+
+#[derive(GraphQLObject)]
+pub(crate) struct ProblemStats {
+    /// Count of runs, submitted for this problem
+    pub total_runs: i32,
+    /// Count of runs with full score
+    pub accepted_runs: i32,
+    /// Maximal score among all runs
+    pub max_score: i32,
+}
+
+impl<'a> From<&'a ranker::ProblemStats> for ProblemStats {
+    fn from(stats: &'a ranker::ProblemStats) -> Self {
+        Self {
+            total_runs: stats.total_runs as i32,
+            accepted_runs: stats.accepted_runs as i32,
+            max_score: stats.max_score,
+        }
+    }
+}
+
+#[derive(GraphQLObject)]
+pub(crate) struct StandingsProblem {
+    /// Problem code, e.g. 'A'
+    pub id: ProblemId,
+    pub title: String,
+    pub stats: ProblemStats,
+}
+
+/// Participant (or team) of contest
+#[derive(GraphQLObject)]
+pub(crate) struct Party {
+    /// None if party is not backed by user
+    pub user_id: Option<UserId>,
+    pub name: String,
+}
+
+#[derive(GraphQLObject)]
+pub(crate) struct StandingsCell {
+    /// Problem code, e.g. 'A'
+    pub problem: ProblemId,
+    /// True if party haven't attempted to solve problem
+    pub empty: bool,
+    /// True if problem is solved
+    pub ok: bool,
+    pub score: i32,
+    /// Count of rejected attempts
+    pub attempts: i32,
+    /// Count of runs, results of which are hidden because standings are frozen
+    pub pending: i32,
+    pub penalty: i32,
+}
+
+#[derive(GraphQLObject)]
+pub(crate) struct StandingsRow {
+    /// 1-based place. Parties with equal results share place
+    pub place: i32,
+    pub party: Party,
+    /// Count of solved problems
+    pub solved: i32,
+    pub score: i32,
+    pub penalty: i32,
+    /// Cells, in the same order as `Standings.problems`
+    pub cells: Vec<StandingsCell>,
+}
+
+#[derive(GraphQLObject)]
+pub(crate) struct Standings {
+    /// True if results of runs submitted after freeze are hidden
+    pub frozen: bool,
+    /// Problems, in order of columns
+    pub problems: Vec<StandingsProblem>,
+    /// Rows, ordered by place
+    pub rows: Vec<StandingsRow>,
+}
@@ -1,4 +1,4 @@
-use super::prelude::*;
+use super::{audit, prelude::*};
 use crate::password;
 
 // TODO allow creation without password
@@ -8,6 +8,9 @@ pub(super) fn create(
     password: String,
     groups: Vec<String>,
 ) -> ApiResult<schema::User> {
+    if !ctx.access().user_can_manage_users().internal(ctx)? {
+        return Err(ApiError::access_denied(ctx));
+    }
     // TODO transaction
     let cur_user = ctx.db.user_try_load_by_login(&login).internal(ctx)?;
     if let Some(..) = cur_user {
@@ -16,13 +19,241 @@ pub(super) fn create(
 
     let provided_password_hash = password::get_password_hash(&password);
 
-    let new_user = db::schema::NewUser {
-        username: login.clone(),
-        password_hash: Some(provided_password_hash),
-        groups: groups.clone(),
+    let mut user = None;
+    ctx.db
+        .transaction(&mut |db| {
+            let new_user = db::schema::NewUser {
+                username: login.clone(),
+                password_hash: Some(provided_password_hash.clone()),
+                groups: groups.clone(),
+            };
+            let created = db.user_new(new_user)?;
+            audit::record(
+                ctx,
+                db,
+                "user.create",
+                user_target(&created),
+                None,
+                Some(audit::user_snapshot(&created)),
+            )?;
+            user = Some(created);
+            Ok(())
+        })
+        .internal(ctx)?;
+
+    Ok(user.as_ref().unwrap().into())
+}
+
+const MAX_LIST_LIMIT: i32 = 100;
+
+pub(super) fn list(
+    ctx: &Context,
+    search: Option<String>,
+    offset: Option<i32>,
+    limit: Option<i32>,
+) -> ApiResult<Vec<schema::User>> {
+    if !ctx.access().user_can_manage_users().internal(ctx)? {
+        return Err(ApiError::access_denied(ctx));
+    }
+    let offset = match offset {
+        Some(offset) if offset >= 0 => offset,
+        Some(_) => return Err(ApiError::new(ctx, "InvalidLimit")),
+        None => 0,
+    };
+    let limit = match limit {
+        Some(limit) if limit >= 0 => std::cmp::min(limit, MAX_LIST_LIMIT),
+        Some(_) => return Err(ApiError::new(ctx, "InvalidLimit")),
+        None => MAX_LIST_LIMIT,
+    };
+    let users = ctx
+        .db
+        .user_list(
+            search.as_ref().map(String::as_str),
+            offset as u32,
+            limit as u32,
+        )
+        .internal(ctx)?;
+    Ok(users.iter().map(Into::into).collect())
+}
+
+fn user_target(user: &db::schema::User) -> String {
+    format!("user/{}", user.username)
+}
+
+/// Loads user, checking that current user can manage it
+fn load_managed_user(ctx: &Context, login: &str) -> ApiResult<db::schema::User> {
+    if !ctx.access().user_can_manage_users().internal(ctx)? {
+        return Err(ApiError::access_denied(ctx));
+    }
+    match ctx.db.user_try_load_by_login(login).internal(ctx)? {
+        Some(user) => Ok(user),
+        None => Err(ApiError::new(ctx, "UnknownUser")),
+    }
+}
+
+/// Revokes all sessions of user, except session `keep` (if set)
+fn revoke_sessions(
+    db: &dyn db::DbConn,
+    user_id: db::schema::UserId,
+    keep: Option<db::schema::SessionId>,
+) -> Result<(), db::Error> {
+    for session in db.session_list(user_id)? {
+        if !session.revoked && Some(session.id) != keep {
+            db.session_revoke(session.id)?;
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn change_password(
+    ctx: &Context,
+    old_password: String,
+    new_password: String,
+) -> ApiResult<()> {
+    let user = match ctx
+        .db
+        .user_try_load_by_id(ctx.token.user_id())
+        .internal(ctx)?
+    {
+        Some(user) => user,
+        None => return Err(ApiError::new(ctx, "UnknownUser")),
+    };
+    let password_hash = match &user.password_hash {
+        Some(hash) => hash,
+        None => return Err(ApiError::new(ctx, "PasswordAuthNotAvailable")),
     };
+    if !password::check_password_hash(&old_password, password_hash) {
+        return Err(ApiError::new(ctx, "IncorrectPassword"));
+    }
+    let new_password_hash = password::get_password_hash(&new_password);
+    ctx.db
+        .transaction(&mut |db| {
+            let patch = db::schema::UserPatch {
+                password_hash: Some(new_password_hash.clone()),
+                ..Default::default()
+            };
+            db.user_update(user.id, patch)?;
+            // password could be compromised, so other sessions are closed
+            revoke_sessions(db, user.id, ctx.token.session_id())
+        })
+        .internal(ctx)
+}
 
-    let user = ctx.db.user_new(new_user).internal(ctx)?;
+pub(super) fn reset_password(ctx: &Context, login: String, password: String) -> ApiResult<()> {
+    let user = load_managed_user(ctx, &login)?;
+    let password_hash = password::get_password_hash(&password);
+    ctx.db
+        .transaction(&mut |db| {
+            let patch = db::schema::UserPatch {
+                password_hash: Some(password_hash.clone()),
+                ..Default::default()
+            };
+            db.user_update(user.id, patch)?;
+            revoke_sessions(db, user.id, None)?;
+            audit::record(
+                ctx,
+                db,
+                "user.reset-password",
+                user_target(&user),
+                None,
+                None,
+            )
+        })
+        .internal(ctx)
+}
 
+pub(super) fn modify_groups(
+    ctx: &Context,
+    login: String,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> ApiResult<schema::User> {
+    let mut user = load_managed_user(ctx, &login)?;
+    let before = audit::user_snapshot(&user);
+    let groups_count = user.groups.len();
+    user.groups.retain(|group| !remove.contains(group));
+    // groups are stored in session tokens, so tokens must be reissued
+    let groups_removed = user.groups.len() != groups_count;
+    for group in add {
+        if !user.groups.contains(&group) {
+            user.groups.push(group);
+        }
+    }
+    ctx.db
+        .transaction(&mut |db| {
+            let patch = db::schema::UserPatch {
+                groups: Some(user.groups.clone()),
+                ..Default::default()
+            };
+            db.user_update(user.id, patch)?;
+            if groups_removed {
+                revoke_sessions(db, user.id, None)?;
+            }
+            audit::record(
+                ctx,
+                db,
+                "user.modify-groups",
+                user_target(&user),
+                Some(before.clone()),
+                Some(audit::user_snapshot(&user)),
+            )
+        })
+        .internal(ctx)?;
     Ok((&user).into())
 }
+
+pub(super) fn set_disabled(
+    ctx: &Context,
+    login: String,
+    disabled: bool,
+) -> ApiResult<schema::User> {
+    let mut user = load_managed_user(ctx, &login)?;
+    let before = audit::user_snapshot(&user);
+    user.disabled = disabled;
+    ctx.db
+        .transaction(&mut |db| {
+            let patch = db::schema::UserPatch {
+                disabled: Some(disabled),
+                ..Default::default()
+            };
+            db.user_update(user.id, patch)?;
+            if disabled {
+                revoke_sessions(db, user.id, None)?;
+            }
+            audit::record(
+                ctx,
+                db,
+                "user.set-disabled",
+                user_target(&user),
+                Some(before.clone()),
+                Some(audit::user_snapshot(&user)),
+            )
+        })
+        .internal(ctx)?;
+    Ok((&user).into())
+}
+
+pub(super) fn delete(ctx: &Context, login: String) -> ApiResult<()> {
+    let user = load_managed_user(ctx, &login)?;
+    let mut deleted = false;
+    ctx.db
+        .transaction(&mut |db| {
+            deleted = db.user_delete(user.id)?;
+            if !deleted {
+                return Ok(());
+            }
+            audit::record(
+                ctx,
+                db,
+                "user.delete",
+                user_target(&user),
+                Some(audit::user_snapshot(&user)),
+                None,
+            )
+        })
+        .internal(ctx)?;
+    if !deleted {
+        return Err(ApiError::new(ctx, "UserHasRuns"));
+    }
+    Ok(())
+}
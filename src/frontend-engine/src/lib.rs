@@ -1,7 +1,7 @@
 #![feature(proc_macro_hygiene, decl_macro)]
 
 use rocket::{catch, catchers, get, post, routes, Rocket};
-use slog_scope::debug;
+use slog_scope::{debug, error};
 
 pub mod config;
 mod gql_server;
@@ -13,6 +13,7 @@ pub mod test_util;
 pub use config::FrontendConfig;
 pub use root_auth::LocalAuthServer;
 use security::TokenMgrError;
+use snafu::Snafu;
 
 use gql_server::Context;
 use rocket::{fairing::AdHoc, State};
@@ -101,6 +102,28 @@ fn route_post_graphql(
     execute_request(request, &*schema, &ctx)
 }
 
+/// Standings, rendered for publishing
+///
+/// `format` is one of `html`, `csv` or `json`
+#[get("/standings/<format>")]
+fn route_standings_export(
+    ctx: Result<gql_server::Context, TokenMgrError>,
+    format: String,
+) -> Result<rocket::response::Content<String>, rocket::http::Status> {
+    let ctx = ctx.map_err(|_| rocket::http::Status::BadRequest)?;
+    let format: ranker::ExportFormat =
+        format.parse().map_err(|_| rocket::http::Status::NotFound)?;
+    let content_type = rocket::http::ContentType::parse_flexible(format.content_type())
+        .expect("invalid content type");
+    match gql_server::export_standings(&ctx, format) {
+        Ok(standings) => Ok(rocket::response::Content(content_type, standings)),
+        Err(err) => {
+            error!("Failed to export standings: {error}", error = &err);
+            Err(rocket::http::Status::InternalServerError)
+        }
+    }
+}
+
 #[derive(Clone)]
 struct GqlApiSchema(String);
 
@@ -109,6 +132,12 @@ fn route_get_graphql_schema(schema: State<GqlApiSchema>) -> String {
     schema.clone().0
 }
 
+#[derive(Debug, Snafu)]
+pub enum ApiServerError {
+    #[snafu(display("failed to initialize auth providers: {}", description))]
+    AuthProviders { description: String },
+}
+
 pub struct ApiServer {}
 
 impl ApiServer {
@@ -125,6 +154,11 @@ impl ApiServer {
             env_blacklist: vec![],
             contests: vec![],
             problems: Default::default(),
+            roles: vec![],
+            grants: vec![],
+            auth_providers: vec![],
+            rate_limits: Default::default(),
+            blob_storage: Default::default(),
         };
         let secret: Arc<[u8]> = config::derive_key_512("EMBEDDED_FRONTEND_INSTANCE")
             .into_boxed_slice()
@@ -139,7 +173,7 @@ impl ApiServer {
             db_conn: db_conn.clone(),
         };
 
-        Self::create(frontend_config, &config, db_conn)
+        Self::create(frontend_config, &config, db_conn).expect("embedded config is valid")
     }
 
     pub fn get_schema() -> String {
@@ -151,7 +185,7 @@ impl ApiServer {
         frontend_config: config::FrontendConfig,
         config: &cfg::Config,
         pool: DbPool,
-    ) -> Rocket {
+    ) -> Result<Rocket, ApiServerError> {
         let rocket_cfg_env = match frontend_config.env {
             config::Env::Prod => rocket::config::Environment::Production,
             config::Env::Dev => rocket::config::Environment::Development,
@@ -170,10 +204,12 @@ impl ApiServer {
             .set_secret_key(base64::encode(frontend_config.token_mgr.secret_key()))
             .unwrap();
 
-        let graphql_context_factory = gql_server::ContextFactory {
-            pool: Arc::clone(&pool),
-            cfg: std::sync::Arc::new(config.clone()),
-        };
+        let graphql_context_factory =
+            gql_server::ContextFactory::new(Arc::clone(&pool), Arc::new(config.clone())).map_err(
+                |err| ApiServerError::AuthProviders {
+                    description: err.to_string(),
+                },
+            )?;
 
         let graphql_schema = gql_server::Schema::new(gql_server::Query, gql_server::Mutation);
 
@@ -192,7 +228,7 @@ impl ApiServer {
         let cfg1 = frontend_config.clone();
         let cfg2 = frontend_config.clone();
 
-        rocket::custom(rocket_config)
+        let rocket = rocket::custom(rocket_config)
             .manage(graphql_context_factory)
             .manage(graphql_schema)
             .manage(GqlApiSchema(introspection_json))
@@ -210,8 +246,10 @@ impl ApiServer {
                     route_get_graphql,
                     route_post_graphql,
                     route_ping,
+                    route_standings_export,
                 ],
             )
-            .register(catchers![catch_bad_request])
+            .register(catchers![catch_bad_request]);
+        Ok(rocket)
     }
 }
@@ -1,11 +1,77 @@
+//! Password hashing
+//!
+//! Passwords are hashed with argon2id and stored in PHC string format,
+//! e.g. `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`.
+//! Legacy hashes (unsalted SHA3-512 in hex encoding) are still accepted, but
+//! should be replaced on next successful login (see `needs_rehash`).
+
+const PHC_PREFIX: &str = "$argon2id$";
+const SALT_LEN: usize = 16;
+
+fn argon2_config() -> argon2::Config<'static> {
+    argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+        // in KiB
+        mem_cost: 19 * 1024,
+        time_cost: 2,
+        lanes: 1,
+        thread_mode: argon2::ThreadMode::Sequential,
+        secret: &[],
+        ad: &[],
+        hash_length: 32,
+    }
+}
+
 pub fn get_password_hash(password: &str) -> String {
+    use rand::Rng;
+    let mut salt = [0; SALT_LEN];
+    rand::thread_rng().fill(&mut salt);
+    argon2::hash_encoded(password.as_bytes(), &salt, &argon2_config())
+        .expect("argon2 config is invalid")
+}
+
+fn get_legacy_password_hash(password: &str) -> String {
     use digest::Digest;
     let digest = sha3::Sha3_512::digest(password.as_bytes());
-    let digest = format!("{:x}", digest);
-    digest
+    format!("{:x}", digest)
 }
 
 pub fn check_password_hash(password: &str, expected_hash: &str) -> bool {
-    let actual_hash = get_password_hash(password);
+    if expected_hash.starts_with('$') {
+        // malformed hash is treated as mismatch
+        return argon2::verify_encoded(expected_hash, password.as_bytes()).unwrap_or(false);
+    }
+    let actual_hash = get_legacy_password_hash(password);
     constant_time_eq::constant_time_eq(expected_hash.as_bytes(), actual_hash.as_bytes())
 }
+
+/// Returns true if hash was produced by outdated scheme and should be replaced
+pub fn needs_rehash(hash: &str) -> bool {
+    !hash.starts_with(PHC_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argon2() {
+        let hash = get_password_hash("VerySecretPass");
+        assert!(hash.starts_with(PHC_PREFIX));
+        assert!(!needs_rehash(&hash));
+        assert!(check_password_hash("VerySecretPass", &hash));
+        assert!(!check_password_hash("VerySecretPas", &hash));
+        // salt is random
+        assert_ne!(hash, get_password_hash("VerySecretPass"));
+    }
+
+    #[test]
+    fn test_legacy() {
+        let hash = get_legacy_password_hash("VerySecretPass");
+        assert_eq!(hash.len(), 128);
+        assert!(needs_rehash(&hash));
+        assert!(check_password_hash("VerySecretPass", &hash));
+        assert!(!check_password_hash("VerySecretPas", &hash));
+    }
+}
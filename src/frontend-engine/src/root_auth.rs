@@ -40,7 +40,7 @@ fn handle_conn(fcfg: &FrontendConfig, mut conn: UnixStream) {
         return;
     }
     info!("issuing root credentials");
-    let token = match fcfg.token_mgr.create_root_token() {
+    let token = match fcfg.token_mgr.create_session_token("Global/Root") {
         Ok(tok) => fcfg.token_mgr.serialize(&tok),
         Err(err) => {
             eprintln!("Error when issuing root credentials: {}", err);
@@ -48,6 +48,19 @@ fn handle_conn(fcfg: &FrontendConfig, mut conn: UnixStream) {
             return;
         }
     }; //crate::security::Token::new_root().serialize(&fcfg.secret);
+    let record = db::schema::NewAuditRecord {
+        actor_id: None,
+        actor_name: format!("unix-uid/{}", peer_cred.uid),
+        action: "token.issue-root".to_string(),
+        target: "user/Global/Root".to_string(),
+        before: None,
+        after: None,
+    };
+    if let Err(err) = fcfg.db_conn.audit_append(record) {
+        error!("failed to write audit record: {}", err);
+        conn.write_all(b"internal error").ok();
+        return;
+    }
     let message = format!("===Branca {}===\n", token);
     conn.write_all(message.as_bytes()).ok();
 }
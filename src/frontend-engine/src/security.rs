@@ -1,11 +1,19 @@
 mod access_ck;
+mod auth_provider;
+mod ldap;
+mod oidc;
+mod rate_limit;
 mod token;
 mod token_mgr;
 
-pub(crate) use access_ck::AccessChecker;
+pub(crate) use access_ck::{AccessCheckError, AccessChecker};
+pub(crate) use auth_provider::{
+    AuthProviders, Credentials, ExternalIdentity, ProviderError, RegisteredProvider,
+};
+pub(crate) use rate_limit::{LoginAttempt, RateLimiter};
 use std::sync::Arc;
 pub(crate) use token::Token;
-pub use token_mgr::{TokenMgr, TokenMgrError};
+pub use token_mgr::{TokenMgr, TokenMgrError, DEFAULT_TOKEN_TTL_SECS};
 
 #[derive(Clone)]
 pub struct SecretKey(pub Arc<[u8]>);
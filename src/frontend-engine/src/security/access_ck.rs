@@ -23,52 +23,363 @@ impl From<db::Error> for AccessCheckError {
 pub(crate) type AccessResult = Result<bool, AccessCheckError>;
 
 impl AccessChecker<'_> {
-    pub(crate) fn user_can_submit(&self, contest_id: &str) -> AccessResult {
-        let contest = self
-            .cfg
-            .find_contest(contest_id)
-            .ok_or(AccessCheckError::NotFound)?;
-        if self.user_is_contest_sudo(contest_id)? {
-            return Ok(true);
+    /// Checks that user was granted role with given capability, either
+    /// globally or in contest `contest_id` (if set)
+    pub(crate) fn has_capability(
+        &self,
+        contest_id: Option<&str>,
+        capability: cfg::Capability,
+    ) -> AccessResult {
+        let contest = match contest_id {
+            Some(contest_id) => Some(
+                self.cfg
+                    .find_contest(contest_id)
+                    .ok_or(AccessCheckError::NotFound)?,
+            ),
+            None => None,
+        };
+        self.has_capability_in(contest, capability)
+    }
+
+    /// Same as `has_capability`, but takes contest config instead of id
+    fn has_capability_in(
+        &self,
+        contest: Option<&cfg::Contest>,
+        capability: cfg::Capability,
+    ) -> AccessResult {
+        let mut grants = self.cfg.global_role_grants();
+        if let Some(contest) = contest {
+            grants.extend(contest.role_grants());
         }
-        for registered_group in &contest.group {
-            if self.token.user_info.groups.contains(registered_group) {
-                return Ok(true);
+        let user = &self.token.user_info;
+        for grant in grants {
+            if !grant.applies_to(&user.name, &user.groups) {
+                continue;
+            }
+            // grants of unknown roles are ignored
+            if let Some(role) = self.cfg.find_role(&grant.role) {
+                if role.capabilities.contains(&capability) {
+                    return Ok(true);
+                }
             }
         }
         Ok(false)
     }
 
-    fn is_sudo(&self) -> AccessResult {
-        // When namespaces are introduced, this function will account for that
-        Ok(self.token.user_info.name == "Global/Root")
+    pub(crate) fn user_can_submit(&self, contest_id: &str) -> AccessResult {
+        self.has_capability(Some(contest_id), cfg::Capability::Submit)
+    }
+
+    /// If `contest_id` is not set, only global grants are taken into account
+    pub(crate) fn user_can_view_all_runs(&self, contest_id: Option<&str>) -> AccessResult {
+        self.has_capability(contest_id, cfg::Capability::ViewAllRuns)
     }
 
-    fn user_is_contest_sudo(&self, contest_id: &str) -> AccessResult {
-        if self.is_sudo()? {
+    pub(crate) fn user_can_view_unfrozen_standings(&self, contest_id: &str) -> AccessResult {
+        self.has_capability(Some(contest_id), cfg::Capability::ViewFrozenStandings)
+    }
+
+    /// Checks `ViewAuditLog` capability, granted either globally or in some contest.
+    /// Which records are visible is checked by `user_can_view_audit_record`.
+    pub(crate) fn user_can_view_audit_log(&self) -> AccessResult {
+        if self.has_capability_in(None, cfg::Capability::ViewAuditLog)? {
             return Ok(true);
         }
-        let contest = self
-            .cfg
-            .find_contest(contest_id)
-            .ok_or(AccessCheckError::NotFound)?;
-        for judges_group in &contest.judges {
-            if self.token.user_info.groups.contains(judges_group) {
+        for contest in &self.cfg.contests {
+            if self.has_capability_in(Some(contest), cfg::Capability::ViewAuditLog)? {
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
-    pub(crate) fn user_can_modify_run(&self, run_id: i32) -> AccessResult {
-        if self.user_is_contest_sudo(
-            // TODO: correctly determine contest_id here
-            "TODO",
-        )? {
+    /// Records, related to problem `problem_id`, can be viewed with grant in contest,
+    /// containing this problem; other records require global grant
+    pub(crate) fn user_can_view_audit_record(&self, problem_id: Option<&str>) -> AccessResult {
+        let contest = problem_id.and_then(|problem_id| self.find_problem_contest(problem_id));
+        self.has_capability_in(contest, cfg::Capability::ViewAuditLog)
+    }
+
+    pub(crate) fn user_can_manage_users(&self) -> AccessResult {
+        self.has_capability(None, cfg::Capability::ManageUsers)
+    }
+
+    /// Problems are shared between contests, so only global grants are considered
+    pub(crate) fn user_can_install_problems(&self) -> AccessResult {
+        self.has_capability(None, cfg::Capability::InstallProblems)
+    }
+
+    /// Users can view and revoke own sessions; user managers can manage all sessions
+    pub(crate) fn user_can_manage_sessions(&self, user_id: uuid::Uuid) -> AccessResult {
+        if self.token.user_id() == user_id {
             return Ok(true);
         }
-        let run = self.db.run_load(run_id)?;
+        self.user_can_manage_users()
+    }
+
+    /// Checks `Rejudge` capability in contest, containing run's problem
+    pub(crate) fn user_can_modify_run(&self, run_id: i32) -> AccessResult {
+        let run = self
+            .db
+            .run_try_load(run_id)?
+            .ok_or(AccessCheckError::NotFound)?;
+        let contest = self.find_problem_contest(&run.problem_id);
+        self.has_capability_in(contest, cfg::Capability::Rejudge)
+    }
+
+    fn find_problem_contest(&self, problem_id: &str) -> Option<&cfg::Contest> {
+        self.cfg.contests.iter().find(|contest| {
+            contest
+                .problems
+                .iter()
+                .any(|problem| problem.name == problem_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cfg::Capability;
+
+    fn make_config() -> cfg::Config {
+        let contest = cfg::Contest {
+            title: "Test contest".to_string(),
+            problems: Vec::new(),
+            group: vec!["Participants".to_string()],
+            unregistered_visible: false,
+            anon_visible: false,
+            judges: vec!["Judges".to_string()],
+            start_time: None,
+            freeze_time: None,
+            grants: vec![cfg::RoleGrant {
+                role: "helpdesk".to_string(),
+                groups: Vec::new(),
+                users: vec!["Volunteer".to_string()],
+            }],
+            max_source_size: cfg::Contest::DEFAULT_MAX_SOURCE_SIZE,
+            forbidden_patterns: Vec::new(),
+        };
+        cfg::Config {
+            toolchains: Vec::new(),
+            sysroot: Default::default(),
+            install_dir: Default::default(),
+            toolchain_root: String::new(),
+            global_env: Default::default(),
+            env_passing: false,
+            env_blacklist: Vec::new(),
+            contests: vec![contest],
+            problems: Default::default(),
+            roles: vec![cfg::Role {
+                name: "helpdesk".to_string(),
+                capabilities: vec![Capability::AnswerClarifications],
+            }],
+            grants: vec![cfg::RoleGrant {
+                role: cfg::ROLE_ADMIN.to_string(),
+                groups: vec!["Admins".to_string()],
+                users: Vec::new(),
+            }],
+            auth_providers: Vec::new(),
+            rate_limits: Default::default(),
+            blob_storage: Default::default(),
+        }
+    }
+
+    fn make_token(name: &str, groups: &[&str]) -> Token {
+        Token::issue_for_virtual_user(
+            uuid::Uuid::new_v4(),
+            name.to_string(),
+            groups.iter().map(|g| g.to_string()).collect(),
+        )
+    }
+
+    /// Returns names of users from fixed set, who have capability in contest
+    fn users_with(capability: Capability, contest_id: Option<&str>) -> Vec<&'static str> {
+        users_where(make_config(), |access| {
+            access.has_capability(contest_id, capability).unwrap()
+        })
+    }
+
+    /// Returns names of users from fixed set, for whom `check` holds
+    fn users_where(cfg: cfg::Config, check: impl Fn(&AccessChecker) -> bool) -> Vec<&'static str> {
+        let db = db::connect::connect_memory().unwrap();
+        let users: &[(&str, &[&str])] = &[
+            ("Global/Root", &[]),
+            ("Admin", &["Admins"]),
+            ("Judge", &["Judges"]),
+            ("Contestant", &["Participants"]),
+            ("Volunteer", &[]),
+            ("Guest", &[]),
+        ];
+        users
+            .iter()
+            .filter(|(name, groups)| {
+                let token = make_token(name, groups);
+                let access = AccessChecker {
+                    token: &token,
+                    cfg: &cfg,
+                    db: &*db,
+                };
+                check(&access)
+            })
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    #[test]
+    fn test_submit() {
+        assert_eq!(
+            users_with(Capability::Submit, Some("TODO")),
+            ["Global/Root", "Admin", "Judge", "Contestant"]
+        );
+        assert_eq!(
+            users_with(Capability::Submit, None),
+            ["Global/Root", "Admin"]
+        );
+    }
+
+    #[test]
+    fn test_view_all_runs() {
+        assert_eq!(
+            users_with(Capability::ViewAllRuns, Some("TODO")),
+            ["Global/Root", "Admin", "Judge"]
+        );
+    }
+
+    #[test]
+    fn test_rejudge() {
+        assert_eq!(
+            users_with(Capability::Rejudge, Some("TODO")),
+            ["Global/Root", "Admin", "Judge"]
+        );
+    }
+
+    #[test]
+    fn test_manage_users() {
+        assert_eq!(
+            users_with(Capability::ManageUsers, Some("TODO")),
+            ["Global/Root", "Admin"]
+        );
+        assert_eq!(
+            users_with(Capability::ManageUsers, None),
+            ["Global/Root", "Admin"]
+        );
+    }
+
+    #[test]
+    fn test_answer_clarifications() {
+        assert_eq!(
+            users_with(Capability::AnswerClarifications, Some("TODO")),
+            ["Global/Root", "Admin", "Judge", "Volunteer"]
+        );
+        // helpdesk role is granted only in contest
+        assert_eq!(
+            users_with(Capability::AnswerClarifications, None),
+            ["Global/Root", "Admin"]
+        );
+    }
+
+    #[test]
+    fn test_view_frozen_standings() {
+        assert_eq!(
+            users_with(Capability::ViewFrozenStandings, Some("TODO")),
+            ["Global/Root", "Admin", "Judge"]
+        );
+    }
+
+    #[test]
+    fn test_view_audit_log() {
+        let mut cfg = make_config();
+        cfg.contests[0].problems.push(cfg::Problem {
+            name: "a-plus-b".to_string(),
+            code: "A".to_string(),
+            limits: Default::default(),
+            time_multiplier: 1.0,
+            score_runs: Default::default(),
+            score_aggregation: Default::default(),
+            accepted_score: 100,
+            title: String::new(),
+            loaded: false,
+        });
+        assert_eq!(
+            users_where(cfg.clone(), |access| access
+                .user_can_view_audit_log()
+                .unwrap()),
+            ["Global/Root", "Admin", "Judge"]
+        );
+        assert_eq!(
+            users_where(cfg.clone(), |access| {
+                access.user_can_view_audit_record(Some("a-plus-b")).unwrap()
+            }),
+            ["Global/Root", "Admin", "Judge"]
+        );
+        // judges only see records about their contests
+        assert_eq!(
+            users_where(cfg.clone(), |access| {
+                access.user_can_view_audit_record(Some("sqrt")).unwrap()
+            }),
+            ["Global/Root", "Admin"]
+        );
+        assert_eq!(
+            users_where(cfg, |access| access
+                .user_can_view_audit_record(None)
+                .unwrap()),
+            ["Global/Root", "Admin"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_contest() {
+        let cfg = make_config();
+        let db = db::connect::connect_memory().unwrap();
+        let token = make_token("Global/Root", &[]);
+        let access = AccessChecker {
+            token: &token,
+            cfg: &cfg,
+            db: &*db,
+        };
+        match access.has_capability(Some("nonexistent"), Capability::Submit) {
+            Err(AccessCheckError::NotFound) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 
-        Ok(run.user_id == self.token.user_id())
+    #[test]
+    fn test_modify_run() {
+        let cfg = make_config();
+        let db = db::connect::connect_memory().unwrap();
+        let owner = make_token("Contestant", &["Participants"]);
+        // problem is not included in any contest, so only global grants apply
+        let run = db
+            .run_new(db::schema::NewRun {
+                toolchain_id: "cpp".to_string(),
+                status_code: "QUEUE_JUDGE".to_string(),
+                status_kind: "QUEUE".to_string(),
+                problem_id: "a-plus-b".to_string(),
+                score: 0,
+                rejudge_id: 1,
+                user_id: owner.user_id(),
+            })
+            .unwrap();
+        let can_modify = |token: &Token| {
+            let access = AccessChecker {
+                token,
+                cfg: &cfg,
+                db: &*db,
+            };
+            access.user_can_modify_run(run.id).unwrap()
+        };
+        assert!(can_modify(&make_token("Admin", &["Admins"])));
+        assert!(!can_modify(&make_token("Judge", &["Judges"])));
+        assert!(!can_modify(&owner));
+        let access = AccessChecker {
+            token: &owner,
+            cfg: &cfg,
+            db: &*db,
+        };
+        match access.user_can_modify_run(run.id + 1) {
+            Err(AccessCheckError::NotFound) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 }
@@ -0,0 +1,127 @@
+//! External authentication providers
+use super::{ldap::LdapProvider, oidc::OidcProvider};
+use snafu::Snafu;
+
+#[derive(Debug, Snafu)]
+pub(crate) enum ProviderError {
+    #[snafu(display("invalid provider config: {}", description))]
+    Config { description: String },
+    #[snafu(display("io error: {}", source))]
+    Io { source: std::io::Error },
+    #[snafu(display("http error: {}", source))]
+    Http { source: reqwest::Error },
+    #[snafu(display("unexpected response from provider: {}", description))]
+    Protocol { description: String },
+    #[snafu(display("provider does not support this kind of credentials"))]
+    UnsupportedCredentials,
+}
+
+impl From<std::io::Error> for ProviderError {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io { source }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(source: reqwest::Error) -> Self {
+        Self::Http { source }
+    }
+}
+
+pub(crate) enum Credentials<'a> {
+    Password {
+        login: &'a str,
+        password: &'a str,
+    },
+    /// Authorization code, obtained by redirect flow
+    AuthorizationCode {
+        code: &'a str,
+    },
+}
+
+/// User, confirmed by provider
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ExternalIdentity {
+    /// Stable identifier in provider's namespace
+    pub(crate) id: String,
+    /// Preferred login in provider's namespace, i.e. without provider prefix.
+    /// Only used when local user is created.
+    pub(crate) login: String,
+}
+
+pub(crate) trait AuthProvider: Send + Sync + std::fmt::Debug {
+    fn kind(&self) -> &'static str;
+
+    /// For redirect-based providers, returns URL user should be sent to.
+    /// `state` will be passed back unchanged together with authorization code.
+    fn authorization_url(&self, _state: &str) -> Option<String> {
+        None
+    }
+
+    /// Returns None if provider rejected credentials
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Result<Option<ExternalIdentity>, ProviderError>;
+}
+
+#[derive(Debug)]
+pub(crate) struct RegisteredProvider {
+    pub(crate) name: String,
+    /// Groups, new users are added to
+    pub(crate) groups: Vec<String>,
+    pub(crate) provider: Box<dyn AuthProvider>,
+}
+
+impl RegisteredProvider {
+    /// Login of local user, corresponding to external identity
+    pub(crate) fn local_login(&self, identity: &ExternalIdentity) -> String {
+        format!("{}/{}", self.name, identity.login)
+    }
+}
+
+/// All configured providers
+#[derive(Debug, Default)]
+pub(crate) struct AuthProviders {
+    providers: Vec<RegisteredProvider>,
+}
+
+impl AuthProviders {
+    pub(crate) fn new(configs: &[cfg::AuthProviderConfig]) -> Result<Self, ProviderError> {
+        let mut providers = Vec::new();
+        for config in configs {
+            // prefix must not clash with builtin users (e.g. Global/Root)
+            if config.name.is_empty() || config.name.contains('/') || config.name == "Global" {
+                return Err(ProviderError::Config {
+                    description: format!("provider name '{}' is not allowed", config.name),
+                });
+            }
+            if providers
+                .iter()
+                .any(|p: &RegisteredProvider| p.name == config.name)
+            {
+                return Err(ProviderError::Config {
+                    description: format!("provider '{}' is defined twice", config.name),
+                });
+            }
+            let provider: Box<dyn AuthProvider> = match &config.backend {
+                cfg::AuthBackendConfig::Ldap(ldap_cfg) => Box::new(LdapProvider::new(ldap_cfg)?),
+                cfg::AuthBackendConfig::Oidc(oidc_cfg) => Box::new(OidcProvider::new(oidc_cfg)?),
+            };
+            providers.push(RegisteredProvider {
+                name: config.name.clone(),
+                groups: config.groups.clone(),
+                provider,
+            });
+        }
+        Ok(AuthProviders { providers })
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<&RegisteredProvider> {
+        self.providers.iter().find(|p| p.name == name)
+    }
+
+    pub(crate) fn list(&self) -> &[RegisteredProvider] {
+        &self.providers
+    }
+}
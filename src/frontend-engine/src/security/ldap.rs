@@ -0,0 +1,226 @@
+//! LDAP simple bind authentication
+use super::auth_provider::{AuthProvider, Credentials, ExternalIdentity, ProviderError};
+use ldap3::{LdapConn, LdapConnSettings};
+use std::time::Duration;
+
+const RESULT_SUCCESS: u32 = 0;
+const RESULT_INVALID_CREDENTIALS: u32 = 49;
+
+#[derive(Debug)]
+pub(crate) struct LdapProvider {
+    url: String,
+    bind_dn: String,
+    timeout: Duration,
+}
+
+impl LdapProvider {
+    pub(crate) fn new(config: &cfg::LdapConfig) -> Result<Self, ProviderError> {
+        if !config.url.starts_with("ldap://") && !config.url.starts_with("ldaps://") {
+            return Err(ProviderError::Config {
+                description: format!("invalid LDAP url: {}", config.url),
+            });
+        }
+        if !config.bind_dn.contains("{login}") {
+            return Err(ProviderError::Config {
+                description: "bind-dn must contain {login}".to_string(),
+            });
+        }
+        Ok(LdapProvider {
+            url: config.url.clone(),
+            bind_dn: config.bind_dn.clone(),
+            timeout: Duration::from_secs(config.timeout),
+        })
+    }
+
+    fn check_password(&self, login: &str, password: &str) -> Result<bool, ProviderError> {
+        // empty password means unauthenticated bind, which always succeeds
+        if login.is_empty() || password.is_empty() {
+            return Ok(false);
+        }
+        let dn = self.bind_dn.replace("{login}", &ldap3::dn_escape(login));
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout);
+        let conn = LdapConn::with_settings(settings, &self.url)?;
+        let result = conn.with_timeout(self.timeout).simple_bind(&dn, password)?;
+        // connection is closed anyway, so error here is not important
+        conn.unbind().ok();
+        match result.rc {
+            RESULT_SUCCESS => Ok(true),
+            RESULT_INVALID_CREDENTIALS => Ok(false),
+            code => Err(ProviderError::Protocol {
+                description: format!("bind failed with code {}: {}", code, result.text),
+            }),
+        }
+    }
+}
+
+impl AuthProvider for LdapProvider {
+    fn kind(&self) -> &'static str {
+        "ldap"
+    }
+
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Result<Option<ExternalIdentity>, ProviderError> {
+        match credentials {
+            Credentials::Password { login, password } => {
+                // LDAP compares names case-insensitively, so one user could
+                // otherwise get several local accounts
+                let login = login.to_lowercase();
+                if self.check_password(&login, password)? {
+                    Ok(Some(ExternalIdentity {
+                        id: login.clone(),
+                        login,
+                    }))
+                } else {
+                    Ok(None)
+                }
+            }
+            Credentials::AuthorizationCode { .. } => Err(ProviderError::UnsupportedCredentials),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ldap3::asn1::{parse_tag, IResult, StructureTag};
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    const TAG_BIND_REQUEST: u64 = 0;
+
+    /// Reads one LDAP message; returns message id and protocol operation
+    fn read_request(stream: &mut impl Read) -> Option<(u8, StructureTag)> {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+        loop {
+            let cnt = stream.read(&mut chunk).ok()?;
+            if cnt == 0 {
+                return None;
+            }
+            buf.extend_from_slice(&chunk[..cnt]);
+            if let IResult::Done(_, message) = parse_tag(&buf) {
+                let mut parts = message.expect_constructed()?.into_iter();
+                let message_id = parts.next()?.expect_primitive()?;
+                return Some((message_id[0], parts.next()?));
+            }
+        }
+    }
+
+    /// Minimal LDAP server, accepting single bind
+    fn spawn_server(dn: &'static str, password: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (message_id, op) = match read_request(&mut stream) {
+                    Some(req) => req,
+                    None => continue,
+                };
+                assert_eq!(op.id, TAG_BIND_REQUEST);
+                let mut fields = op.expect_constructed().unwrap().into_iter();
+                assert_eq!(fields.next().unwrap().expect_primitive().unwrap(), [3]);
+                let req_dn = fields.next().unwrap().expect_primitive().unwrap();
+                let req_password = fields.next().unwrap().expect_primitive().unwrap();
+                let code = if req_dn == dn.as_bytes() && req_password == password.as_bytes() {
+                    RESULT_SUCCESS
+                } else {
+                    RESULT_INVALID_CREDENTIALS
+                };
+                // BindResponse with empty matchedDN and diagnosticMessage
+                let response = [
+                    0x30, 0x0c, 0x02, 0x01, message_id, 0x61, 0x07, 0x0a, 0x01, code as u8, 0x04,
+                    0x00, 0x04, 0x00,
+                ];
+                stream.write_all(&response).unwrap();
+                // wait for unbind
+                read_request(&mut stream);
+            }
+        });
+        port
+    }
+
+    fn make_provider(port: u16) -> LdapProvider {
+        LdapProvider::new(&cfg::LdapConfig {
+            url: format!("ldap://127.0.0.1:{}", port),
+            bind_dn: "uid={login},ou=people,dc=example,dc=org".to_string(),
+            timeout: 5,
+        })
+        .unwrap()
+    }
+
+    fn password_creds<'a>(login: &'a str, password: &'a str) -> Credentials<'a> {
+        Credentials::Password { login, password }
+    }
+
+    fn identity(login: &str) -> ExternalIdentity {
+        ExternalIdentity {
+            id: login.to_string(),
+            login: login.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bind() {
+        let port = spawn_server("uid=jsnow,ou=people,dc=example,dc=org", "winter");
+        let provider = make_provider(port);
+        assert_eq!(
+            provider
+                .authenticate(password_creds("jsnow", "winter"))
+                .unwrap(),
+            Some(identity("jsnow"))
+        );
+        // login is case-insensitive
+        assert_eq!(
+            provider
+                .authenticate(password_creds("JSnow", "winter"))
+                .unwrap(),
+            Some(identity("jsnow"))
+        );
+        assert_eq!(
+            provider
+                .authenticate(password_creds("jsnow", "summer"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            provider
+                .authenticate(password_creds("astark", "winter"))
+                .unwrap(),
+            None
+        );
+        // must be rejected without asking server
+        assert_eq!(
+            provider.authenticate(password_creds("jsnow", "")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_escape_dn() {
+        let port = spawn_server("uid=x\\,ou\\=admins,ou=people,dc=example,dc=org", "pass");
+        let provider = make_provider(port);
+        assert_eq!(
+            provider
+                .authenticate(password_creds("x,ou=admins", "pass"))
+                .unwrap(),
+            Some(identity("x,ou=admins"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = |url: &str, bind_dn: &str| cfg::LdapConfig {
+            url: url.to_string(),
+            bind_dn: bind_dn.to_string(),
+            timeout: 5,
+        };
+        assert!(LdapProvider::new(&config("http://localhost", "uid={login}")).is_err());
+        assert!(LdapProvider::new(&config("ldap://localhost", "uid=admin")).is_err());
+        assert!(LdapProvider::new(&config("ldaps://localhost:1636", "uid={login}")).is_ok());
+    }
+}
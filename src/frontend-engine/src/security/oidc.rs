@@ -0,0 +1,263 @@
+//! OpenID Connect authentication (authorization code flow)
+//!
+//! ID token is not inspected: tokens are received directly from token endpoint,
+//! and user identity is taken from UserInfo endpoint. Users are identified by
+//! `sub` claim; `login_claim` only provides login for newly created users.
+use super::auth_provider::{AuthProvider, Credentials, ExternalIdentity, ProviderError};
+use reqwest::Url;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub(crate) struct OidcProvider {
+    authorization_endpoint: Url,
+    token_endpoint: Url,
+    userinfo_endpoint: Url,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    scope: String,
+    login_claim: String,
+    timeout: Duration,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+fn parse_endpoint(url: &str) -> Result<Url, ProviderError> {
+    Url::parse(url).map_err(|err| ProviderError::Config {
+        description: format!("invalid endpoint url {}: {}", url, err),
+    })
+}
+
+impl OidcProvider {
+    pub(crate) fn new(config: &cfg::OidcConfig) -> Result<Self, ProviderError> {
+        Ok(OidcProvider {
+            authorization_endpoint: parse_endpoint(&config.authorization_endpoint)?,
+            token_endpoint: parse_endpoint(&config.token_endpoint)?,
+            userinfo_endpoint: parse_endpoint(&config.userinfo_endpoint)?,
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            redirect_uri: config.redirect_uri.clone(),
+            scope: config.scopes.join(" "),
+            login_claim: config.login_claim.clone(),
+            timeout: Duration::from_secs(config.timeout),
+        })
+    }
+
+    fn exchange_code(&self, code: &str) -> Result<Option<ExternalIdentity>, ProviderError> {
+        let client = reqwest::Client::builder().timeout(self.timeout).build()?;
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        let mut token_response = client
+            .post(self.token_endpoint.clone())
+            .form(&params)
+            .send()?;
+        // e.g. invalid_grant for expired or reused code
+        if token_response.status() == reqwest::StatusCode::BAD_REQUEST {
+            return Ok(None);
+        }
+        let token: TokenResponse = token_response.error_for_status()?.json()?;
+
+        let userinfo: serde_json::Value = client
+            .get(self.userinfo_endpoint.clone())
+            .bearer_auth(&token.access_token)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let claim = |name: &str| {
+            userinfo
+                .get(name)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+        };
+        // `sub` is the only claim which is stable and unique within issuer
+        let id = claim("sub").ok_or_else(|| ProviderError::Protocol {
+            description: "UserInfo does not contain 'sub'".to_string(),
+        })?;
+        let login = claim(&self.login_claim).unwrap_or(id);
+        Ok(Some(ExternalIdentity {
+            id: id.to_string(),
+            login: login.to_string(),
+        }))
+    }
+}
+
+impl AuthProvider for OidcProvider {
+    fn kind(&self) -> &'static str {
+        "oidc"
+    }
+
+    fn authorization_url(&self, state: &str) -> Option<String> {
+        let mut url = self.authorization_endpoint.clone();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.scope)
+            .append_pair("state", state);
+        Some(url.into_string())
+    }
+
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Result<Option<ExternalIdentity>, ProviderError> {
+        match credentials {
+            Credentials::AuthorizationCode { code } => self.exchange_code(code),
+            Credentials::Password { .. } => Err(ProviderError::UnsupportedCredentials),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    const VALID_CODE: &str = "code-1";
+    const ACCESS_TOKEN: &str = "token-1";
+
+    /// Minimal HTTP server, implementing token and UserInfo endpoints
+    fn spawn_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                let mut authorization = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let mut parts = line.splitn(2, ':');
+                    let name = parts.next().unwrap().to_lowercase();
+                    let value = parts.next().unwrap_or_default().trim().to_string();
+                    match name.as_str() {
+                        "content-length" => content_length = value.parse().unwrap(),
+                        "authorization" => authorization = value,
+                        _ => (),
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let (status, response) = if request_line.starts_with("POST /token ") {
+                    let params = body.split('&').collect::<Vec<_>>();
+                    assert!(params.contains(&"grant_type=authorization_code"));
+                    assert!(params.contains(&"client_secret=secret"));
+                    if params.contains(&format!("code={}", VALID_CODE).as_str()) {
+                        (
+                            "200 OK",
+                            format!(
+                                r#"{{"access_token": "{}", "token_type": "Bearer"}}"#,
+                                ACCESS_TOKEN
+                            ),
+                        )
+                    } else {
+                        (
+                            "400 Bad Request",
+                            r#"{"error": "invalid_grant"}"#.to_string(),
+                        )
+                    }
+                } else if request_line.starts_with("GET /userinfo ") {
+                    if authorization == format!("Bearer {}", ACCESS_TOKEN) {
+                        (
+                            "200 OK",
+                            r#"{"sub": "42", "preferred_username": "jsnow"}"#.to_string(),
+                        )
+                    } else {
+                        ("401 Unauthorized", "{}".to_string())
+                    }
+                } else {
+                    ("404 Not Found", "{}".to_string())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        port
+    }
+
+    fn make_provider(port: u16) -> OidcProvider {
+        let base = format!("http://127.0.0.1:{}", port);
+        OidcProvider::new(&cfg::OidcConfig {
+            authorization_endpoint: format!("{}/authorize", base),
+            token_endpoint: format!("{}/token", base),
+            userinfo_endpoint: format!("{}/userinfo", base),
+            client_id: "jjs".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: "https://jjs.example.org/login/callback".to_string(),
+            scopes: vec!["openid".to_string(), "profile".to_string()],
+            login_claim: "preferred_username".to_string(),
+            timeout: 5,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_authorization_url() {
+        let provider = make_provider(1);
+        let url = Url::parse(&provider.authorization_url("xyz").unwrap()).unwrap();
+        assert_eq!(url.path(), "/authorize");
+        let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+        assert!(pairs.contains(&("response_type".to_string(), "code".to_string())));
+        assert!(pairs.contains(&("scope".to_string(), "openid profile".to_string())));
+        assert!(pairs.contains(&("state".to_string(), "xyz".to_string())));
+        assert!(pairs.contains(&(
+            "redirect_uri".to_string(),
+            "https://jjs.example.org/login/callback".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_code_exchange() {
+        let provider = make_provider(spawn_server());
+        assert_eq!(
+            provider
+                .authenticate(Credentials::AuthorizationCode { code: VALID_CODE })
+                .unwrap(),
+            Some(ExternalIdentity {
+                id: "42".to_string(),
+                login: "jsnow".to_string()
+            })
+        );
+        assert_eq!(
+            provider
+                .authenticate(Credentials::AuthorizationCode { code: "forged" })
+                .unwrap(),
+            None
+        );
+        match provider.authenticate(Credentials::Password {
+            login: "jsnow",
+            password: "winter",
+        }) {
+            Err(ProviderError::UnsupportedCredentials) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
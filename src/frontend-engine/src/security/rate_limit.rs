@@ -0,0 +1,425 @@
+//! Rate limiting of submissions and login attempts
+//!
+//! State is kept in memory, so limits are per frontend instance.
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const SUBMISSION_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limiter state is compacted when it contains more entries
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum AttemptSource {
+    Login(String),
+    Ip(IpAddr),
+}
+
+#[derive(Debug)]
+struct Backoff {
+    failures: u32,
+    last_failure: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limits: cfg::RateLimits,
+    /// Submission times during last minute, for each user and problem
+    submissions: Mutex<HashMap<(uuid::Uuid, String), VecDeque<Instant>>>,
+    failed_logins: Mutex<HashMap<AttemptSource, Backoff>>,
+}
+
+/// Submission, counted against limit. If it is dropped without `commit`
+/// (e.g. because run could not be created), quota is given back.
+#[must_use]
+pub(crate) struct SubmissionReservation<'a> {
+    limiter: &'a RateLimiter,
+    key: (uuid::Uuid, String),
+    /// None if submissions are not limited or reservation is committed
+    time: Option<Instant>,
+}
+
+impl SubmissionReservation<'_> {
+    pub(crate) fn commit(mut self) {
+        self.time = None;
+    }
+}
+
+impl Drop for SubmissionReservation<'_> {
+    fn drop(&mut self) {
+        let time = match self.time {
+            Some(time) => time,
+            None => return,
+        };
+        let mut submissions = self.limiter.submissions.lock().unwrap();
+        if let Some(times) = submissions.get_mut(&self.key) {
+            if let Some(pos) = times.iter().rposition(|&t| t == time) {
+                times.remove(pos);
+            }
+        }
+    }
+}
+
+/// Login attempt in progress. It is counted as failed unless `succeeded`
+/// or `cancelled` is called.
+#[must_use]
+pub(crate) struct LoginAttempt<'a> {
+    limiter: &'a RateLimiter,
+    login: String,
+    ip: Option<IpAddr>,
+}
+
+impl LoginAttempt<'_> {
+    /// Forgets failed attempts for login. Attempts from IP are still remembered,
+    /// so that one known password does not allow guessing others.
+    pub(crate) fn succeeded(self) {
+        self.limiter.release_login_attempt(&self, true);
+    }
+
+    /// Attempt was not checked at all (e.g. provider is unavailable)
+    pub(crate) fn cancelled(self) {
+        self.limiter.release_login_attempt(&self, false);
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: cfg::RateLimits) -> RateLimiter {
+        RateLimiter {
+            limits,
+            submissions: Mutex::new(HashMap::new()),
+            failed_logins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers submission. If limit is exceeded, returns time to wait instead.
+    pub(crate) fn submission(
+        &self,
+        user: uuid::Uuid,
+        problem: &str,
+    ) -> Result<SubmissionReservation<'_>, Duration> {
+        self.submission_at(Instant::now(), user, problem)
+    }
+
+    fn submission_at(
+        &self,
+        now: Instant,
+        user: uuid::Uuid,
+        problem: &str,
+    ) -> Result<SubmissionReservation<'_>, Duration> {
+        let limit = match self.limits.submissions_per_minute {
+            Some(limit) => limit as usize,
+            None => {
+                return Ok(SubmissionReservation {
+                    limiter: self,
+                    key: (user, problem.to_string()),
+                    time: None,
+                })
+            }
+        };
+        let mut submissions = self.submissions.lock().unwrap();
+        if submissions.len() > MAX_ENTRIES {
+            submissions.retain(|_, times| {
+                times
+                    .back()
+                    .map_or(false, |&t| now.duration_since(t) < SUBMISSION_WINDOW)
+            });
+        }
+        let key = (user, problem.to_string());
+        let times = submissions.entry(key.clone()).or_default();
+        while let Some(&oldest) = times.front() {
+            if now.duration_since(oldest) < SUBMISSION_WINDOW {
+                break;
+            }
+            times.pop_front();
+        }
+        if times.len() >= limit {
+            let retry_after = match times.front() {
+                Some(&oldest) => SUBMISSION_WINDOW - now.duration_since(oldest),
+                // limit is zero
+                None => SUBMISSION_WINDOW,
+            };
+            return Err(retry_after);
+        }
+        times.push_back(now);
+        Ok(SubmissionReservation {
+            limiter: self,
+            key,
+            time: Some(now),
+        })
+    }
+
+    /// Checks that login attempt is allowed and counts it as failed until
+    /// it is reported successful. Otherwise returns time to wait.
+    ///
+    /// Check and counting are done at once, so concurrent attempts can not
+    /// exceed limit.
+    pub(crate) fn login_attempt(
+        &self,
+        login: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<LoginAttempt<'_>, Duration> {
+        self.login_attempt_at(Instant::now(), login, ip)
+    }
+
+    fn login_attempt_at(
+        &self,
+        now: Instant,
+        login: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<LoginAttempt<'_>, Duration> {
+        let reset = Duration::from_secs(self.limits.login_backoff_reset);
+        let mut failed_logins = self.failed_logins.lock().unwrap();
+        let mut wait = Duration::from_secs(0);
+        for (source, free_attempts) in self.sources(login, ip) {
+            if let Some(backoff) = failed_logins.get(&source) {
+                let locked_until = backoff.last_failure + self.delay(backoff, free_attempts);
+                if locked_until > now {
+                    wait = std::cmp::max(wait, locked_until - now);
+                }
+            }
+        }
+        if wait > Duration::from_secs(0) {
+            return Err(wait);
+        }
+        if failed_logins.len() > MAX_ENTRIES {
+            failed_logins.retain(|_, backoff| now.duration_since(backoff.last_failure) < reset);
+        }
+        if failed_logins.len() > MAX_ENTRIES {
+            // too many recent failures, so oldest entries are evicted
+            let mut times = failed_logins
+                .values()
+                .map(|backoff| backoff.last_failure)
+                .collect::<Vec<_>>();
+            times.sort();
+            let threshold = times[times.len() - MAX_ENTRIES / 2];
+            failed_logins.retain(|_, backoff| backoff.last_failure >= threshold);
+        }
+        for (source, _) in self.sources(login, ip) {
+            let backoff = failed_logins.entry(source).or_insert(Backoff {
+                failures: 0,
+                last_failure: now,
+            });
+            if now.duration_since(backoff.last_failure) >= reset {
+                backoff.failures = 0;
+            }
+            backoff.failures += 1;
+            backoff.last_failure = now;
+        }
+        Ok(LoginAttempt {
+            limiter: self,
+            login: login.to_string(),
+            ip,
+        })
+    }
+
+    /// Takes back failure, counted by `login_attempt`
+    fn release_login_attempt(&self, attempt: &LoginAttempt, forget_login: bool) {
+        let mut failed_logins = self.failed_logins.lock().unwrap();
+        for (source, _) in self.sources(&attempt.login, attempt.ip) {
+            let forget = match &source {
+                AttemptSource::Login(_) => forget_login,
+                AttemptSource::Ip(_) => false,
+            };
+            if forget {
+                failed_logins.remove(&source);
+            } else if let Some(backoff) = failed_logins.get_mut(&source) {
+                backoff.failures = backoff.failures.saturating_sub(1);
+            }
+        }
+    }
+
+    fn sources(&self, login: &str, ip: Option<IpAddr>) -> Vec<(AttemptSource, u32)> {
+        let mut sources = vec![(
+            AttemptSource::Login(login.to_string()),
+            self.limits.login_free_attempts,
+        )];
+        if let Some(ip) = ip {
+            sources.push((AttemptSource::Ip(ip), self.limits.ip_free_attempts));
+        }
+        sources
+    }
+
+    /// Time after last failure, during which new attempts are rejected
+    fn delay(&self, backoff: &Backoff, free_attempts: u32) -> Duration {
+        if backoff.failures <= free_attempts {
+            return Duration::from_secs(0);
+        }
+        let exponent = std::cmp::min(backoff.failures - free_attempts - 1, 32);
+        let delay = self.limits.login_backoff_base.saturating_mul(1 << exponent);
+        Duration::from_secs(std::cmp::min(delay, self.limits.login_backoff_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_limits() -> cfg::RateLimits {
+        cfg::RateLimits {
+            submissions_per_minute: Some(2),
+            login_free_attempts: 2,
+            ip_free_attempts: 3,
+            login_backoff_base: 10,
+            login_backoff_max: 60,
+            login_backoff_reset: 600,
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    /// Submits and commits submission
+    fn submit(
+        limiter: &RateLimiter,
+        now: Instant,
+        user: uuid::Uuid,
+        problem: &str,
+    ) -> Result<(), Duration> {
+        limiter
+            .submission_at(now, user, problem)
+            .map(SubmissionReservation::commit)
+    }
+
+    /// Makes attempt, which fails
+    fn fail_login(
+        limiter: &RateLimiter,
+        now: Instant,
+        login: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), Duration> {
+        limiter.login_attempt_at(now, login, ip).map(drop)
+    }
+
+    #[test]
+    fn test_submissions() {
+        let limiter = RateLimiter::new(make_limits());
+        let user = uuid::Uuid::new_v4();
+        let other_user = uuid::Uuid::new_v4();
+        let start = Instant::now();
+        assert_eq!(submit(&limiter, start, user, "A"), Ok(()));
+        assert_eq!(submit(&limiter, start + secs(20), user, "A"), Ok(()));
+        assert_eq!(submit(&limiter, start + secs(30), user, "A"), Err(secs(30)));
+        // other problems and other users are counted separately
+        assert_eq!(submit(&limiter, start + secs(30), user, "B"), Ok(()));
+        assert_eq!(submit(&limiter, start + secs(30), other_user, "A"), Ok(()));
+        // rejected submission is not counted
+        assert_eq!(submit(&limiter, start + secs(60), user, "A"), Ok(()));
+        assert_eq!(submit(&limiter, start + secs(70), user, "A"), Err(secs(10)));
+    }
+
+    #[test]
+    fn test_submissions_unlimited() {
+        let limiter = RateLimiter::new(Default::default());
+        let user = uuid::Uuid::new_v4();
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(submit(&limiter, now, user, "A"), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_submission_not_committed() {
+        let limiter = RateLimiter::new(make_limits());
+        let user = uuid::Uuid::new_v4();
+        let now = Instant::now();
+        let first = limiter.submission_at(now, user, "A").unwrap();
+        let second = limiter.submission_at(now, user, "A").unwrap();
+        // uncommitted reservations are counted
+        assert!(limiter.submission_at(now, user, "A").is_err());
+        first.commit();
+        drop(second);
+        assert_eq!(submit(&limiter, now, user, "A"), Ok(()));
+        assert!(limiter.submission_at(now, user, "A").is_err());
+    }
+
+    #[test]
+    fn test_login_backoff() {
+        let limiter = RateLimiter::new(make_limits());
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(fail_login(&limiter, start, "jsnow", None), Ok(()));
+        }
+        assert_eq!(
+            fail_login(&limiter, start + secs(4), "jsnow", None),
+            Err(secs(6))
+        );
+        assert_eq!(fail_login(&limiter, start, "astark", None), Ok(()));
+        // delay doubles with each failure, up to maximum
+        let mut now = start;
+        for &delay in &[10, 20, 40, 60, 60] {
+            now += secs(delay);
+            assert_eq!(fail_login(&limiter, now, "jsnow", None), Ok(()));
+        }
+        assert_eq!(fail_login(&limiter, now, "jsnow", None), Err(secs(60)));
+        // failures are eventually forgotten
+        now += secs(600);
+        assert_eq!(fail_login(&limiter, now, "jsnow", None), Ok(()));
+        assert_eq!(fail_login(&limiter, now, "jsnow", None), Ok(()));
+    }
+
+    #[test]
+    fn test_login_backoff_ip() {
+        let limiter = RateLimiter::new(make_limits());
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let other_ip: IpAddr = "192.0.2.2".parse().unwrap();
+        let now = Instant::now();
+        for login in &["a", "b", "c", "d"] {
+            assert_eq!(fail_login(&limiter, now, login, Some(ip)), Ok(()));
+        }
+        assert_eq!(fail_login(&limiter, now, "e", Some(ip)), Err(secs(10)));
+        assert_eq!(fail_login(&limiter, now, "e", Some(other_ip)), Ok(()));
+        // success resets login, but not IP
+        for _ in 0..2 {
+            assert_eq!(fail_login(&limiter, now, "f", Some(other_ip)), Ok(()));
+        }
+        limiter
+            .login_attempt_at(now, "f", Some(other_ip))
+            .unwrap()
+            .succeeded();
+        assert_eq!(fail_login(&limiter, now, "f", None), Ok(()));
+        assert!(fail_login(&limiter, now, "f", Some(ip)).is_err());
+    }
+
+    #[test]
+    fn test_login_entries_evicted() {
+        let limiter = RateLimiter::new(make_limits());
+        let start = Instant::now();
+        for i in 0..=MAX_ENTRIES {
+            let now = start + Duration::from_millis(i as u64);
+            let login = format!("user{}", i);
+            assert_eq!(fail_login(&limiter, now, &login, None), Ok(()));
+        }
+        assert_eq!(
+            fail_login(&limiter, start + secs(60), "jsnow", None),
+            Ok(())
+        );
+        let failed_logins = limiter.failed_logins.lock().unwrap();
+        assert!(failed_logins.len() <= MAX_ENTRIES);
+        let has_login =
+            |login: &str| failed_logins.contains_key(&AttemptSource::Login(login.to_string()));
+        assert!(!has_login("user0"));
+        assert!(has_login(&format!("user{}", MAX_ENTRIES)));
+        assert!(has_login("jsnow"));
+    }
+
+    #[test]
+    fn test_concurrent_login_attempts() {
+        let limiter = RateLimiter::new(make_limits());
+        let now = Instant::now();
+        // attempts are counted before they complete
+        let attempts = (0..3)
+            .map(|_| limiter.login_attempt_at(now, "jsnow", None).unwrap())
+            .collect::<Vec<_>>();
+        assert!(limiter.login_attempt_at(now, "jsnow", None).is_err());
+        for attempt in attempts {
+            attempt.cancelled();
+        }
+        assert!(limiter.login_attempt_at(now, "jsnow", None).is_ok());
+    }
+}
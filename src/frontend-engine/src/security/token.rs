@@ -12,16 +12,37 @@ pub struct UserInfo {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Token {
     pub(super) user_info: UserInfo,
+    /// Session this token belongs to.
+    /// None for tokens that can not be revoked (e.g. guest token)
+    #[serde(default)]
+    pub(super) session_id: Option<uuid::Uuid>,
+    /// Unix timestamp
+    #[serde(default)]
+    pub(super) issued_at: i64,
+    /// Unix timestamp, after which token is rejected
+    #[serde(default)]
+    pub(super) expires_at: i64,
 }
 
 impl Token {
     pub fn issue_for_virtual_user(id: uuid::Uuid, name: String, groups: Vec<String>) -> Token {
         Token {
             user_info: UserInfo { id, name, groups },
+            session_id: None,
+            issued_at: 0,
+            expires_at: i64::max_value(),
         }
     }
 
     pub fn user_id(&self) -> uuid::Uuid {
         self.user_info.id
     }
+
+    pub fn user_name(&self) -> &str {
+        &self.user_info.name
+    }
+
+    pub fn session_id(&self) -> Option<uuid::Uuid> {
+        self.session_id
+    }
 }
@@ -1,4 +1,5 @@
 use crate::security::{token::UserInfo, Token};
+use chrono::TimeZone;
 use snafu::Snafu;
 use std::sync::Arc;
 
@@ -7,8 +8,12 @@ use std::sync::Arc;
 pub struct TokenMgr {
     db: Arc<dyn db::DbConn>,
     secret_key: Arc<[u8]>,
+    ttl: chrono::Duration,
 }
 
+/// Lifetime of token, if not overriden with `TokenMgr::with_ttl`
+pub const DEFAULT_TOKEN_TTL_SECS: i64 = 24 * 60 * 60;
+
 static TOKEN_PREFIX_BRANCA: &str = "Branca ";
 static TOKEN_PREFIX_DEV: &str = "Dev ";
 static TOKEN_PREFIX_GUEST: &str = "Guest";
@@ -32,6 +37,10 @@ pub enum TokenMgrError {
     UnknownKind,
     #[snafu(display("using token is denien"))]
     Denied,
+    #[snafu(display("token is expired"))]
+    Expired,
+    #[snafu(display("token was revoked"))]
+    Revoked,
     #[snafu(display("branca error: {}", source))]
     Branca {
         source: branca::errors::Error,
@@ -46,7 +55,17 @@ impl From<db::Error> for TokenMgrError {
 
 impl TokenMgr {
     pub fn new(db: Arc<dyn db::DbConn>, secret_key: Arc<[u8]>) -> Self {
-        Self { db, secret_key }
+        Self {
+            db,
+            secret_key,
+            ttl: chrono::Duration::seconds(DEFAULT_TOKEN_TTL_SECS),
+        }
+    }
+
+    /// Sets lifetime of issued tokens
+    pub fn with_ttl(mut self, ttl: chrono::Duration) -> Self {
+        self.ttl = ttl;
+        self
     }
 
     pub fn secret_key(&self) -> &[u8] {
@@ -61,15 +80,56 @@ impl TokenMgr {
                 .ok_or(TokenMgrError::UserMissing {
                     user: username.to_string(),
                 })?;
+        let now = chrono::Utc::now();
         Ok(Token {
             user_info: UserInfo {
                 name: user_data.username,
                 groups: user_data.groups,
                 id: user_data.id,
             },
+            session_id: None,
+            issued_at: now.timestamp(),
+            expires_at: (now + self.ttl).timestamp(),
         })
     }
 
+    /// Like `create_token`, but also registers new session, so that token
+    /// can be listed and revoked later.
+    pub fn create_session_token(&self, username: &str) -> Result<Token, TokenMgrError> {
+        let mut token = self.create_token(username)?;
+        let session = self.db.session_new(db::schema::NewSession {
+            user_id: token.user_id(),
+            created_at: chrono::Utc.timestamp(token.issued_at, 0),
+            expires_at: chrono::Utc.timestamp(token.expires_at, 0),
+        })?;
+        token.session_id = Some(session.id);
+        Ok(token)
+    }
+
+    /// Revokes session token belongs to. Returns false if token is not bound to session.
+    pub fn revoke(&self, token: &Token) -> Result<bool, TokenMgrError> {
+        match token.session_id {
+            Some(session_id) => {
+                self.db.session_revoke(session_id)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn check_not_expired_or_revoked(&self, token: &Token) -> Result<(), TokenMgrError> {
+        if token.expires_at <= chrono::Utc::now().timestamp() {
+            return Err(TokenMgrError::Expired);
+        }
+        if let Some(session_id) = token.session_id {
+            match self.db.session_try_load(session_id)? {
+                Some(session) if !session.revoked => {}
+                _ => return Err(TokenMgrError::Revoked),
+            }
+        }
+        Ok(())
+    }
+
     pub fn create_guest_token(&self) -> Result<Token, TokenMgrError> {
         self.create_token("Global/Guest")
     }
@@ -84,7 +144,8 @@ impl TokenMgr {
         let mut rand_gen = rand::thread_rng();
         let mut nonce = [0 as u8; 24];
         rand_gen.fill(&mut nonce);
-        let branca_data = branca::encode(&ser, self.secret_key(), 0).expect("Token encoding error");
+        let branca_data = branca::encode(&ser, self.secret_key(), token.issued_at as u32)
+            .expect("Token encoding error");
         format!("Branca {}", branca_data)
     }
 
@@ -100,6 +161,7 @@ impl TokenMgr {
                 Err(err) => return Err(TokenMgrError::Branca { source: err }),
             };
             let res = serde_json::from_str(&token_data).expect("Token decoding error");
+            self.check_not_expired_or_revoked(&res)?;
             return Ok(res);
         }
         if data.starts_with(TOKEN_PREFIX_DEV) {
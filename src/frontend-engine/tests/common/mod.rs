@@ -6,6 +6,7 @@ use std::{env::temp_dir, path::PathBuf, sync::Arc};
 #[derive(Default)]
 pub struct EnvBuilder {
     toolchains: Vec<cfg::Toolchain>,
+    rate_limits: cfg::RateLimits,
 }
 
 impl EnvBuilder {
@@ -18,6 +19,11 @@ impl EnvBuilder {
         self
     }
 
+    pub fn rate_limits(&mut self, limits: cfg::RateLimits) -> &mut Self {
+        self.rate_limits = limits;
+        self
+    }
+
     pub fn build(&self, name: &str) -> Env {
         util::log::setup();
         // TODO partially duplicates ApiServer::create_embedded()
@@ -53,6 +59,10 @@ impl EnvBuilder {
                 name: "dev-problem".to_string(),
                 code: "A".to_string(),
                 limits: Default::default(),
+                time_multiplier: 1.0,
+                score_runs: Default::default(),
+                score_aggregation: Default::default(),
+                accepted_score: 100,
                 title: "DEV PROBLEM".to_string(),
                 loaded: true,
             }],
@@ -60,6 +70,11 @@ impl EnvBuilder {
             unregistered_visible: false,
             anon_visible: false,
             judges: Vec::new(),
+            start_time: None,
+            freeze_time: None,
+            grants: Vec::new(),
+            max_source_size: cfg::Contest::DEFAULT_MAX_SOURCE_SIZE,
+            forbidden_patterns: Vec::new(),
         };
 
         let config = cfg::Config {
@@ -72,6 +87,11 @@ impl EnvBuilder {
             env_blacklist: vec![],
             contests: vec![contest],
             problems: Default::default(),
+            roles: vec![],
+            grants: vec![],
+            auth_providers: vec![],
+            rate_limits: self.rate_limits.clone(),
+            blob_storage: Default::default(),
         };
         let secret = config::derive_key_512("EMBEDDED_FRONTEND_INSTANCE");
         let frontend_config = config::FrontendConfig {
@@ -83,7 +103,7 @@ impl EnvBuilder {
             token_mgr: frontend_engine::security::TokenMgr::new(db_conn.clone(), secret.into()),
         };
 
-        let rock = ApiServer::create(frontend_config, &config, db_conn);
+        let rock = ApiServer::create(frontend_config, &config, db_conn).unwrap();
         Env {
             client: rocket::local::Client::new(rock).unwrap(),
         }
@@ -111,6 +131,12 @@ impl RequestBuilder<'_> {
         self
     }
 
+    /// Use given token instead of dev root token
+    pub fn auth(&mut self, token: &str) -> &mut Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
     pub fn exec(&self) -> test_util::Response {
         let body = self.builder.to_query();
         let request = self
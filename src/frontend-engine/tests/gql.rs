@@ -66,6 +66,37 @@ mutation CreateSameUserAgain {
         .unwrap_errs();
     assert_eq!(res.len(), 1);
     common::check_error(&res[0], "UserAlreadyExists");
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogIn {
+    authSimple(login: "JonSnow", password: "VerySecretPass") {
+        data
+    }
+}
+        "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert!(res["authSimple"]["data"].is_string());
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogInWithWrongPassword {
+    authSimple(login: "JonSnow", password: "NotSoSecretPass") {
+        data
+    }
+}
+        "#,
+        )
+        .exec()
+        .unwrap_errs();
+    assert_eq!(res.len(), 1);
+    common::check_error(&res[0], "IncorrectPassword");
 }
 
 ///  tests operations with run
@@ -80,6 +111,8 @@ fn test_runs_ops() {
             build_commands: vec![],
             run_command: Default::default(),
             limits: Default::default(),
+            max_source_size: None,
+            forbidden_patterns: vec![],
         })
         .build("runs_ops");
 
@@ -162,3 +195,693 @@ query GetRun {
     let res = String::from_utf8(res).unwrap();
     assert_eq!(res, RUN_TEXT);
 }
+
+/// tests typed standings query
+#[test]
+fn test_standings_ops() {
+    let env = common::Env::new("StandingsOps");
+    let res = env
+        .req()
+        .operation(
+            r#"
+query GetStandings {
+    standings(contest: "TODO") {
+        frozen
+        problems {
+            id
+        }
+        rows {
+            place
+        }
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(
+        res,
+        json!({
+            "standings": {
+                "frozen": false,
+                "problems": [{"id": "A"}],
+                "rows": []
+            }
+        })
+    );
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query GetStandingsOfUnknownContest {
+    standings(contest: "unknown") {
+        frozen
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    assert_eq!(res.len(), 1);
+    common::check_error(&res[0], "ContestNotFound");
+}
+
+/// tests login, session listing and logout
+#[test]
+fn test_session_ops() {
+    let env = common::Env::new("SessionOps");
+    env.req()
+        .operation(
+            r#"
+mutation CreateAUser {
+    createUser(login: "JonSnow", password: "VerySecretPass", groups: []) {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogIn {
+    authSimple(login: "JonSnow", password: "VerySecretPass") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let token = res["authSimple"]["data"].as_str().unwrap().to_string();
+
+    let res = env
+        .req()
+        .auth(&token)
+        .operation(
+            r#"
+query ListOwnSessions {
+    sessions {
+        revoked
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"sessions": [{"revoked": false}]}));
+
+    let res = env
+        .req()
+        .auth(&token)
+        .operation(
+            r#"
+mutation LogOut {
+    logout
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"logout": true}));
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query ListSessionsAsRoot {
+    sessions(login: "JonSnow") {
+        revoked
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"sessions": [{"revoked": true}]}));
+}
+
+/// tests user listing, password changes, groups editing, disabling and deletion
+#[test]
+fn test_user_management_ops() {
+    let env = common::Env::new("UserManagementOps");
+    env.req()
+        .operation(
+            r#"
+mutation CreateUsers {
+    jon: createUser(login: "JonSnow", password: "VerySecretPass", groups: ["Stark"]) {
+        login
+    }
+    arya: createUser(login: "AryaStark", password: "NeedleIsSharp", groups: []) {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query SearchUsers {
+    users(search: "Snow") {
+        login
+        groups
+        disabled
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(
+        res,
+        json!({"users": [{"login": "JonSnow", "groups": ["Stark"], "disabled": false}]})
+    );
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation EditGroups {
+    modifyUserGroups(login: "JonSnow", add: ["NightsWatch"], remove: ["Stark"]) {
+        groups
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"modifyUserGroups": {"groups": ["NightsWatch"]}}));
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogIn {
+    authSimple(login: "AryaStark", password: "NeedleIsSharp") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let token = res["authSimple"]["data"].as_str().unwrap().to_string();
+
+    let res = env
+        .req()
+        .auth(&token)
+        .operation(
+            r#"
+query ListUsersAsContestant {
+    users {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    common::check_error(&res[0], "AccessDenied");
+
+    let res = env
+        .req()
+        .auth(&token)
+        .operation(
+            r#"
+mutation ChangeWithWrongPassword {
+    changePassword(oldPassword: "Needle", newPassword: "NoOne")
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    common::check_error(&res[0], "IncorrectPassword");
+
+    env.req()
+        .operation(
+            r#"
+mutation LogInAgain {
+    authSimple(login: "AryaStark", password: "NeedleIsSharp") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    env.req()
+        .auth(&token)
+        .operation(
+            r#"
+mutation ChangeOwnPassword {
+    changePassword(oldPassword: "NeedleIsSharp", newPassword: "NoOne")
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    // other session is revoked, current one is kept
+    let res = env
+        .req()
+        .operation(
+            r#"
+query ListSessionsAfterPasswordChange {
+    sessions(login: "AryaStark") {
+        revoked
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let mut revoked = res["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|session| session["revoked"].as_bool().unwrap())
+        .collect::<Vec<_>>();
+    revoked.sort();
+    assert_eq!(revoked, [false, true]);
+
+    env.req()
+        .operation(
+            r#"
+mutation ResetPassword {
+    resetPassword(login: "AryaStark", password: "ValarMorghulis")
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogInWithOldPassword {
+    authSimple(login: "AryaStark", password: "NoOne") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    common::check_error(&res[0], "IncorrectPassword");
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation Disable {
+    setUserDisabled(login: "AryaStark", disabled: true) {
+        disabled
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"setUserDisabled": {"disabled": true}}));
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogInDisabled {
+    authSimple(login: "AryaStark", password: "ValarMorghulis") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    common::check_error(&res[0], "UserDisabled");
+
+    env.req()
+        .operation(
+            r#"
+mutation Delete {
+    deleteUser(login: "AryaStark")
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query ListAll {
+    users(search: "Stark") {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"users": []}));
+}
+
+/// tests that privileged actions are recorded to audit log
+#[test]
+fn test_audit_ops() {
+    let env = common::Env::new("AuditOps");
+    env.req()
+        .operation(
+            r#"
+mutation CreateAndModifyUser {
+    createUser(login: "JonSnow", password: "VerySecretPass", groups: []) {
+        login
+    }
+    modifyUserGroups(login: "JonSnow", add: ["NightsWatch"]) {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query BrowseAuditLog {
+    auditLog(limit: 2) {
+        actorName
+        action
+        target
+        before
+        after
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(
+        res,
+        json!({"auditLog": [
+            {
+                "actorName": "Global/Root",
+                "action": "user.modify-groups",
+                "target": "user/JonSnow",
+                "before": r#"{"disabled":false,"groups":[],"login":"JonSnow"}"#,
+                "after": r#"{"disabled":false,"groups":["NightsWatch"],"login":"JonSnow"}"#,
+            },
+            {
+                "actorName": "Global/Root",
+                "action": "user.create",
+                "target": "user/JonSnow",
+                "before": null,
+                "after": r#"{"disabled":false,"groups":[],"login":"JonSnow"}"#,
+            }
+        ]})
+    );
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogIn {
+    authSimple(login: "JonSnow", password: "VerySecretPass") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let token = res["authSimple"]["data"].as_str().unwrap().to_string();
+    let res = env
+        .req()
+        .auth(&token)
+        .operation(
+            r#"
+query BrowseAuditLogAsContestant {
+    auditLog {
+        action
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_errs();
+    common::check_error(&res[0], "AccessDenied");
+}
+
+/// tests that repeated failed logins are rejected
+#[test]
+fn test_login_rate_limit() {
+    let env = common::EnvBuilder::new()
+        .rate_limits(cfg::RateLimits {
+            login_free_attempts: 2,
+            login_backoff_base: 600,
+            ..Default::default()
+        })
+        .build("LoginRateLimit");
+    env.req()
+        .operation(
+            r#"
+mutation CreateUser {
+    createUser(login: "JonSnow", password: "VerySecretPass", groups: []) {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+
+    let log_in = |password: &str| {
+        env.req()
+            .operation(
+                r#"
+mutation LogIn($password: String!) {
+    authSimple(login: "JonSnow", password: $password) {
+        data
+    }
+}
+    "#,
+            )
+            .var("password", &json!(password))
+            .exec()
+    };
+
+    for _ in 0..2 {
+        let res = log_in("IDontKnow").unwrap_errs();
+        common::check_error(&res[0], "IncorrectPassword");
+    }
+    // third attempt is still allowed, but starts backoff
+    let res = log_in("IDontKnow").unwrap_errs();
+    common::check_error(&res[0], "IncorrectPassword");
+    let res = log_in("VerySecretPass").unwrap_errs();
+    common::check_error(&res[0], "TooManyLoginAttempts");
+    assert!(res[0]["extensions"]["retryAfter"].as_i64().unwrap() > 0);
+}
+
+/// tests that invalid submissions are rejected before run is created
+#[test]
+fn test_submission_validation() {
+    let env = common::EnvBuilder::new()
+        .toolchain(cfg::Toolchain {
+            title: "C++".to_string(),
+            name: "cpp".to_string(),
+            filename: "source.cpp".to_string(),
+            build_commands: vec![],
+            run_command: Default::default(),
+            limits: Default::default(),
+            max_source_size: Some(100),
+            forbidden_patterns: vec![cfg::Pattern::new(r"#include\s*<windows\.h>").unwrap()],
+        })
+        .build("SubmissionValidation");
+
+    let submit = |code: &str| {
+        env.req()
+            .operation(
+                r#"
+mutation CreateRun($runCode: String!) {
+    submitSimple(toolchain: "cpp", runCode: $runCode, problem: "A", contest: "TODO") {
+        id
+    }
+}
+    "#,
+            )
+            .var("runCode", &json!(code))
+            .exec()
+    };
+
+    let cases = [
+        (base64::encode(&"a".repeat(101)), "SourceTooLarge"),
+        (base64::encode(&"a".repeat(10_000)), "SourceTooLarge"),
+        ("not base64".to_string(), "SourceNotBase64"),
+        (base64::encode(" \n"), "SourceEmpty"),
+        (
+            base64::encode("#include <windows.h>\nint main() {}"),
+            "SourceForbiddenPattern",
+        ),
+    ];
+    for (code, error_code) in cases.iter() {
+        let res = submit(code).unwrap_errs();
+        common::check_error(&res[0], error_code);
+    }
+
+    let res = env
+        .req()
+        .operation(
+            r#"
+query ListRuns {
+    runs {
+        id
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    assert_eq!(res, json!({"runs": []}));
+
+    submit(&base64::encode("int main() {}")).unwrap_ok();
+}
+
+/// tests filtering and pagination of runs
+#[test]
+fn test_runs_list() {
+    let env = common::EnvBuilder::new()
+        .toolchain(cfg::Toolchain {
+            title: "C++".to_string(),
+            name: "cpp".to_string(),
+            filename: "source.cpp".to_string(),
+            build_commands: vec![],
+            run_command: Default::default(),
+            limits: Default::default(),
+            max_source_size: None,
+            forbidden_patterns: vec![],
+        })
+        .build("RunsList");
+
+    for _ in 0..3 {
+        env.req()
+            .operation(
+                r#"
+mutation CreateRun($runCode: String!) {
+    submitSimple(toolchain: "cpp", runCode: $runCode, problem: "A", contest: "TODO") {
+        id
+    }
+}
+    "#,
+            )
+            .var("runCode", &json!(base64::encode("int main() {}")))
+            .exec()
+            .unwrap_ok();
+    }
+
+    let list_runs = |token: Option<&str>, args: &str| {
+        let mut req = env.req();
+        if let Some(token) = token {
+            req.auth(token);
+        }
+        req.operation(&format!("query ListRuns {{ runs{} {{ id }} }}", args))
+            .exec()
+    };
+    let ids = |res: frontend_engine::test_util::Response| {
+        res.unwrap_ok()["runs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|run| run["id"].as_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(list_runs(None, "")), [0, 1, 2]);
+    assert_eq!(ids(list_runs(None, "(limit: 2)")), [0, 1]);
+    assert_eq!(ids(list_runs(None, "(after: 1, limit: 2)")), [2]);
+    assert_eq!(
+        ids(list_runs(None, "(order: NEWEST_FIRST, after: 2)")),
+        [1, 0]
+    );
+    assert_eq!(
+        ids(list_runs(
+            None,
+            r#"(filter: {contest: "TODO", problem: "A"})"#
+        )),
+        [0, 1, 2]
+    );
+    assert_eq!(
+        ids(list_runs(None, r#"(filter: {problem: "Z"})"#)),
+        Vec::<i64>::new()
+    );
+    assert_eq!(
+        ids(list_runs(None, r#"(filter: {statusKind: "Accepted"})"#)),
+        Vec::<i64>::new()
+    );
+    common::check_error(
+        &list_runs(None, r#"(filter: {contest: "nope"})"#).unwrap_errs()[0],
+        "ContestUnknown",
+    );
+    common::check_error(
+        &list_runs(None, "(limit: -1)").unwrap_errs()[0],
+        "InvalidLimit",
+    );
+    common::check_error(
+        &list_runs(None, r#"(filter: {user: "NoOne"})"#).unwrap_errs()[0],
+        "UnknownUser",
+    );
+
+    env.req()
+        .operation(
+            r#"
+mutation CreateUsers {
+    jon: createUser(login: "JonSnow", password: "VerySecretPass", groups: []) {
+        login
+    }
+    arya: createUser(login: "AryaStark", password: "VerySecretPass", groups: []) {
+        login
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let res = env
+        .req()
+        .operation(
+            r#"
+mutation LogIn {
+    authSimple(login: "JonSnow", password: "VerySecretPass") {
+        data
+    }
+}
+    "#,
+        )
+        .exec()
+        .unwrap_ok();
+    let token = res["authSimple"]["data"].as_str().unwrap().to_string();
+    // contestant only sees own runs
+    assert_eq!(ids(list_runs(Some(&token), "")), Vec::<i64>::new());
+    assert_eq!(
+        ids(list_runs(Some(&token), r#"(filter: {user: "JonSnow"})"#)),
+        Vec::<i64>::new()
+    );
+    common::check_error(
+        &list_runs(Some(&token), r#"(filter: {user: "AryaStark"})"#).unwrap_errs()[0],
+        "AccessDenied",
+    );
+    common::check_error(
+        &list_runs(Some(&token), r#"(filter: {user: "NoOne"})"#).unwrap_errs()[0],
+        "AccessDenied",
+    );
+}
@@ -12,7 +12,14 @@ fn launch_api(frcfg: FrontendConfig, config: cfg::Config) {
         }
     };
 
-    let launch_err = frontend_engine::ApiServer::create(frcfg, &config, pool.into()).launch();
+    let rocket = match frontend_engine::ApiServer::create(frcfg, &config, pool.into()) {
+        Ok(rocket) => rocket,
+        Err(e) => {
+            eprintln!("invalid config: {}", e);
+            exit(1);
+        }
+    };
+    let launch_err = rocket.launch();
 
     error!("launch error: {}", launch_err);
     exit(1)
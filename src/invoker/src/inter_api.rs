@@ -75,6 +75,13 @@ pub(crate) struct ValuerNotification {
     pub(crate) test_status: Status,
 }
 
+/// Score, gained by solution on one subtask
+#[derive(Debug, Clone)]
+pub(crate) struct SubtaskScore {
+    pub(crate) subtask_id: u32,
+    pub(crate) score: u32,
+}
+
 pub(crate) enum ValuerResponse {
     Test {
         test_id: u32,
@@ -83,5 +90,7 @@ pub(crate) enum ValuerResponse {
         score: u32,
         treat_as_full: bool,
         judge_log: judge_log::JudgeLog,
+        /// Empty if valuer does not split problem into subtasks
+        subtasks: Vec<SubtaskScore>,
     },
 }
@@ -22,15 +22,14 @@ pub(crate) struct InvokeContext<'a> {
     pub(crate) toolchain_cfg: &'a cfg::Toolchain,
     pub(crate) problem_cfg: &'a cfg::Problem,
     pub(crate) problem_data: &'a pom::Problem,
+    /// Resolved problem package directory
+    pub(crate) problem_dir: &'a Path,
     pub(crate) submission_props: &'a crate::SubmissionProps,
 }
 
 impl<'a> InvokeContext<'a> {
     pub(crate) fn get_problem_root(&self) -> PathBuf {
-        self.cfg
-            .sysroot
-            .join("var/problems")
-            .join(&self.problem_cfg.name)
+        self.problem_dir.to_path_buf()
     }
 
     pub(crate) fn get_asset_path(&self, short_path: &pom::FileRef) -> PathBuf {
@@ -43,6 +42,22 @@ impl<'a> InvokeContext<'a> {
         root.join(&short_path.path)
     }
 
+    /// Returns limits for running solution on `test`.
+    /// Limits not specified in problem manifest are taken from contest config;
+    /// only time limit from manifest is multiplied by `time_multiplier`.
+    pub(crate) fn test_limits(&self, test: &pom::Test) -> cfg::Limits {
+        let fallback = &self.problem_cfg.limits;
+        let time = match test.limits.time {
+            Some(time) => (time as f64 * self.problem_cfg.time_multiplier).round() as u64,
+            None => fallback.time,
+        };
+        cfg::Limits {
+            time,
+            memory: test.limits.memory.unwrap_or(fallback.memory),
+            process_count: test.limits.process_count.unwrap_or(fallback.process_count),
+        }
+    }
+
     pub(crate) fn create_sandbox(
         &self,
         limits: &cfg::Limits,
@@ -3,8 +3,8 @@ use crate::{
     compiler::Compiler,
     err,
     inter_api::{
-        Artifact, BuildOutcome, BuildRequest, JudgeRequest, Paths, ValuerNotification,
-        ValuerResponse,
+        Artifact, BuildOutcome, BuildRequest, JudgeRequest, Paths, SubtaskScore,
+        ValuerNotification, ValuerResponse,
     },
     judge::Judge,
     judge_log::JudgeLog,
@@ -109,6 +109,7 @@ pub struct Invoker<'a> {
 pub struct InvokeOutcome {
     pub status: Status,
     pub score: u32,
+    pub(crate) subtasks: Vec<SubtaskScore>,
 }
 
 impl<'a> Invoker<'a> {
@@ -117,11 +118,7 @@ impl<'a> Invoker<'a> {
     }
 
     fn problem_path(&self) -> PathBuf {
-        self.ctx
-            .cfg
-            .sysroot
-            .join("var/problems")
-            .join(&self.ctx.problem_cfg.name)
+        self.ctx.get_problem_root()
     }
 
     fn run_tests(&self, artifact: &Artifact) -> Result<(InvokeOutcome, JudgeLog), Error> {
@@ -130,7 +127,7 @@ impl<'a> Invoker<'a> {
         let mut valuer = Valuer::new(self.ctx.clone())?;
         let mut resp = valuer.initial_test()?;
 
-        let (score, treat_as_full, judge_log) = loop {
+        let (score, treat_as_full, judge_log, subtasks) = loop {
             match resp {
                 ValuerResponse::Test { test_id: tid } => {
                     let test = &self.ctx.problem_data.tests[(tid - 1) as usize];
@@ -163,8 +160,9 @@ impl<'a> Invoker<'a> {
                     score,
                     treat_as_full,
                     judge_log,
+                    subtasks,
                 } => {
-                    break (score, treat_as_full, judge_log);
+                    break (score, treat_as_full, judge_log, subtasks);
                 }
             }
         };
@@ -180,7 +178,11 @@ impl<'a> Invoker<'a> {
                 code: status_codes::PARTIAL_SOLUTION.to_string(),
             }
         };
-        let outcome = InvokeOutcome { status, score };
+        let outcome = InvokeOutcome {
+            status,
+            score,
+            subtasks,
+        };
         Ok((outcome, judge_log))
     }
 
@@ -281,6 +283,7 @@ impl<'a> Invoker<'a> {
                 outcome = Some(InvokeOutcome {
                     status: st,
                     score: 0,
+                    subtasks: Vec::new(),
                 });
                 None
             }
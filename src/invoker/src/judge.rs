@@ -24,9 +24,9 @@ enum RunOutcome {
 
 impl<'a> Judge<'a> {
     fn run_solution(&self, test_data: &[u8]) -> Result<RunOutcome, Error> {
-        let limits = &self.ctx.problem_cfg.limits;
+        let limits = self.ctx.test_limits(self.req.test);
 
-        let sandbox = self.ctx.create_sandbox(limits, self.req.paths)?;
+        let sandbox = self.ctx.create_sandbox(&limits, self.req.paths)?;
 
         fs::copy(
             self.req.paths.submission.join("build"),
@@ -19,6 +19,7 @@ use std::{
     fs,
     path::PathBuf,
     sync::{self, atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
 };
 
 pub(crate) mod err {
@@ -101,7 +102,7 @@ pub(crate) mod err {
 }
 
 pub(crate) use err::{Error, StringError};
-use std::{path::Path, process::exit};
+use std::process::exit;
 
 /// Secondary information, used for various interpolations
 #[derive(Debug)]
@@ -116,7 +117,10 @@ pub(crate) struct SubmissionInfo {
     pub toolchain_cfg: cfg::Toolchain,
     pub problem_cfg: cfg::Problem,
     pub problem_data: pom::Problem,
-    /// Directory for general files (source, build, invlog)
+    /// Problem package directory. It is resolved once, when run is fetched,
+    /// so that reinstalling problem doesn't affect runs being judged.
+    pub problem_dir: PathBuf,
+    /// Directory for source and build artifact (inside `work_dir`)
     pub root_dir: PathBuf,
     pub props: SubmissionProps,
 }
@@ -152,20 +156,37 @@ fn submission_set_judge_outcome(
     outcome: invoker::InvokeOutcome,
     request: &InvocationRequest,
 ) -> Result<(), Error> {
+    // subtask ids were checked when valuer reported them
+    let subtasks_count = outcome
+        .subtasks
+        .iter()
+        .map(|st| st.subtask_id as usize)
+        .max()
+        .unwrap_or(0);
+    let mut subtask_scores = vec![0; subtasks_count];
+    for st in &outcome.subtasks {
+        subtask_scores[st.subtask_id as usize - 1] = st.score as i32;
+    }
     let run_patch = db::schema::RunPatch {
         status_code: Some(outcome.status.code.to_string()),
         status_kind: Some(outcome.status.kind.to_string()),
         score: Some(outcome.score as i32),
         rejudge_id: Some(request.invoke_revision as i32),
+        subtask_scores: Some(subtask_scores),
     };
 
     conn.run_update(submission_id, run_patch)?;
     Ok(())
 }
 
+/// How often unreferenced blobs are looked for. Blob is deleted when it is
+/// found unreferenced twice, i.e. after one to two intervals.
+const GC_INTERVAL: Duration = Duration::from_secs(3600);
+
 struct Server {
     config: cfg::Config,
-    db_conn: Box<dyn db::DbConn>,
+    db_conn: Arc<dyn db::DbConn>,
+    blobs: Box<dyn db::blob::BlobStore>,
     backend: Box<dyn minion::Backend>,
 }
 
@@ -193,17 +214,31 @@ impl Server {
         res
     }
 
+    /// Deletes blobs of deleted runs and replaced artifacts
+    fn collect_garbage(&self, gc: &mut db::blob::GarbageCollector) {
+        match gc.collect(&*self.db_conn, &*self.blobs) {
+            Ok(deleted) => debug!("Blob garbage collection finished"; "deleted" => deleted),
+            Err(err) => error!("Blob garbage collection failed"; "message" => %err),
+        }
+    }
+
     /// called by every thread
     fn thread_loop(&self, should_run: Arc<AtomicBool>) {
+        let mut gc = db::blob::GarbageCollector::new();
+        let mut last_gc = Instant::now();
         loop {
             if !should_run.load(sync::atomic::Ordering::SeqCst) {
                 break;
             }
+            if last_gc.elapsed() >= GC_INTERVAL {
+                self.collect_garbage(&mut gc);
+                last_gc = Instant::now();
+            }
 
             let inv_req = match self.try_get_task() {
                 Some(s) => s,
                 None => {
-                    std::thread::sleep(std::time::Duration::from_millis(2000));
+                    std::thread::sleep(Duration::from_millis(2000));
                     continue;
                 }
             };
@@ -221,29 +256,40 @@ impl Server {
         let req = self.fetch_submission_info(&inv_req)?;
         let submission_id = req.submission.props.id;
         let outcome = self.process_invoke_request(&req);
+        // store artifacts first, so that judge log is available when outcome is visible.
+        // Outcome is written anyway: otherwise run would stay queued forever.
+        if let Err(err) = self.store_invocation_artifacts(&req) {
+            error!("Failed to store invocation artifacts"; "submission" => submission_id, "message" => %err);
+        }
         submission_set_judge_outcome(&*self.db_conn, submission_id, outcome, &inv_req)?;
-        self.copy_invokation_data_dir_to_shared_fs(&req.work_dir.path(), submission_id, req.id)?;
         Ok(())
     }
 
-    fn copy_invokation_data_dir_to_shared_fs(
-        &self,
-        temp_path: &Path,
-        run_id: i32,
-        inv_id: i32,
-    ) -> Result<(), Error> {
-        let target_dir = self
-            .config
-            .sysroot
-            .join("var/submissions")
-            .join(format!("s-{}", run_id))
-            .join(format!("i-{}", inv_id));
-        std::fs::create_dir_all(&target_dir)?;
-        let from: Result<Vec<_>, _> = std::fs::read_dir(temp_path)?
-            .map(|x| x.map(|y| y.path()))
-            .collect();
-        fs_extra::copy_items(&from?, &target_dir, &fs_extra::dir::CopyOptions::new())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    /// Saves build artifact and judge log to blob storage
+    fn store_invocation_artifacts(&self, req: &InvokeRequest) -> Result<(), Error> {
+        let artifacts = [
+            (
+                req.submission.root_dir.join("build"),
+                db::blob::RUN_BUILD.to_string(),
+            ),
+            (
+                req.work_dir.path().join("log.json"),
+                db::blob::run_judge_log(req.id),
+            ),
+        ];
+        for (path, name) in artifacts.iter() {
+            // e.g. there is no build if compilation failed
+            if !path.exists() {
+                continue;
+            }
+            let data = fs::read(path)?;
+            let hash = self.blobs.put(&data)?;
+            self.db_conn.run_blob_set(db::schema::RunBlob {
+                run_id: req.submission.props.id,
+                name: name.clone(),
+                hash,
+            })?;
+        }
         Ok(())
     }
 
@@ -255,6 +301,7 @@ impl Server {
             problem_cfg: &request.submission.problem_cfg,
             toolchain_cfg: &request.submission.toolchain_cfg,
             problem_data: &request.submission.problem_data,
+            problem_dir: &request.submission.problem_dir,
             submission_props: &request.submission.props,
         };
         let invoker = Invoker::new(invoke_ctx, request);
@@ -275,6 +322,7 @@ impl Server {
             invoker::InvokeOutcome {
                 status: st,
                 score: 0,
+                subtasks: Vec::new(),
             }
         });
 
@@ -289,8 +337,24 @@ impl Server {
     ) -> Result<InvokeRequest, Error> {
         let db_submission = self.db_conn.run_load(db_inv_req.run_id)?;
 
-        let submission_root = self.config.sysroot.join("var/submissions");
-        let submission_root = submission_root.join(&format!("s-{}", db_submission.id));
+        let work_dir = tempfile::TempDir::new().context(err::Io {})?;
+        let submission_root = work_dir.path().join("submission");
+        fs::create_dir(&submission_root).context(err::Io {})?;
+        let source = db::blob::load_run_file(
+            &*self.db_conn,
+            &*self.blobs,
+            &self.config.sysroot,
+            db_submission.id,
+            db::blob::RUN_SOURCE,
+        )?;
+        let source = source.ok_or_else(|| Error::Other {
+            backtrace: Default::default(),
+            inner: Box::new(StringError(format!(
+                "source of run {} not found in blob storage",
+                db_submission.id
+            ))),
+        })?;
+        fs::write(submission_root.join("source"), source).context(err::Io {})?;
 
         let mut submission_metadata = HashMap::new();
         let judge_time = {
@@ -301,12 +365,11 @@ impl Server {
 
         let prob_name = &db_submission.problem_id;
 
-        let problem_manifest_path = self
-            .config
-            .sysroot
-            .join("var/problems")
-            .join(&prob_name)
-            .join("manifest.json");
+        // current version is symlink, which can be switched by reinstalling problem
+        let problem_dir = cfg::problem_dir(&self.config.sysroot, &prob_name)
+            .canonicalize()
+            .context(err::Io {})?;
+        let problem_manifest_path = problem_dir.join("manifest.json");
 
         let reader =
             std::io::BufReader::new(fs::File::open(problem_manifest_path).context(err::Io)?);
@@ -349,12 +412,13 @@ impl Server {
             props: submission_props,
             toolchain_cfg: toolchain_cfg.clone(),
             problem_data,
+            problem_dir,
             problem_cfg: problem_cfg.clone(),
         };
 
         let req = InvokeRequest {
             submission,
-            work_dir: tempfile::TempDir::new().context(err::Io {})?,
+            work_dir,
             id: db_inv_req.invoke_revision,
         };
         Ok(req)
@@ -383,8 +447,8 @@ fn main() {
     util::wait::wait();
 
     let config = cfg::get_config();
-    let db_conn = match db::connect_env() {
-        Ok(db_conn) => db_conn,
+    let db_conn: Arc<dyn db::DbConn> = match db::connect_env() {
+        Ok(db_conn) => db_conn.into(),
         Err(e) => {
             eprintln!("Startup error: failed connect to database: {}", e);
             exit(1);
@@ -398,9 +462,17 @@ fn main() {
     }
     let backend = minion::setup();
 
+    let blobs: Box<dyn db::blob::BlobStore> = match config.blob_storage {
+        cfg::BlobStorage::Filesystem => Box::new(db::blob::FsBlobStore::new(
+            &config.sysroot.join("var/blobs"),
+        )),
+        cfg::BlobStorage::Database => Box::new(db::blob::DbBlobStore::new(db_conn.clone())),
+    };
+
     let invoker = Server {
         config,
         db_conn,
+        blobs,
         backend,
     };
 
@@ -1,6 +1,6 @@
 use crate::{
     err::ErrorBox,
-    inter_api::{ValuerNotification, ValuerResponse},
+    inter_api::{SubtaskScore, ValuerNotification, ValuerResponse},
     invoke_context::InvokeContext,
 };
 use snafu::ResultExt;
@@ -25,6 +25,83 @@ enum ParseError {
     Other { message: String },
 }
 
+/// Parses subtask score line, which looks like `<subtask id> <score>`
+fn parse_subtask_score(line: &str) -> Result<SubtaskScore, ParseError> {
+    let items: Vec<_> = line.split_whitespace().collect();
+    if items.len() != 2 {
+        return Err(ParseError::WrongArgCount {
+            expected: 2,
+            got: items.len(),
+        });
+    }
+    let subtask_id: u32 = items[0].parse().context(NumParseFail)?;
+    let score: u32 = items[1].parse().context(NumParseFail)?;
+    if subtask_id == 0 {
+        return Err(ParseError::Range {
+            lhs: 1,
+            rhs: u32::max_value().into(),
+            got: 0,
+        });
+    }
+    Ok(SubtaskScore { subtask_id, score })
+}
+
+/// Returns maximal possible subtask id for problem.
+///
+/// If problem has groups, subtasks are groups; otherwise each subtask contains
+/// at least one test, so there can't be more subtasks than tests.
+fn max_subtask_id(problem: &pom::Problem) -> usize {
+    if problem.groups.is_empty() {
+        problem.tests.len()
+    } else {
+        problem.groups.len()
+    }
+}
+
+/// Checks that subtask ids are valid and distinct, and that subtask scores
+/// sum up to total score
+fn check_subtasks(
+    subtasks: &[SubtaskScore],
+    problem: &pom::Problem,
+    score: u32,
+) -> Result<(), ParseError> {
+    let max_subtask_id = max_subtask_id(problem);
+    let mut reported = vec![false; max_subtask_id];
+    let mut total: u32 = 0;
+    for st in subtasks {
+        let idx = st.subtask_id as usize - 1;
+        match reported.get_mut(idx) {
+            Some(true) => {
+                return Err(ParseError::Other {
+                    message: format!("subtask {} is reported twice", st.subtask_id),
+                });
+            }
+            Some(reported) => *reported = true,
+            None => {
+                return Err(ParseError::Range {
+                    lhs: 1,
+                    rhs: max_subtask_id as i64,
+                    got: st.subtask_id.into(),
+                });
+            }
+        }
+        total = total
+            .checked_add(st.score)
+            .ok_or_else(|| ParseError::Other {
+                message: "sum of subtask scores overflows".to_string(),
+            })?;
+    }
+    if !subtasks.is_empty() && total != score {
+        return Err(ParseError::Other {
+            message: format!(
+                "sum of subtask scores is {}, but total score is {}",
+                total, score
+            ),
+        });
+    }
+    Ok(())
+}
+
 impl<'a> Valuer<'a> {
     pub(crate) fn new(ctx: InvokeContext<'a>) -> Result<Valuer, ErrorBox> {
         let valuer_exe = ctx.get_asset_path(&ctx.problem_data.valuer_exe);
@@ -75,15 +152,21 @@ impl<'a> Valuer<'a> {
                 ValuerResponse::Test { test_id }
             }
             "DONE" => {
-                if items.len() != 4 {
+                // subtask count is optional, so that valuers which are not aware
+                // of subtasks continue to work
+                if items.len() != 4 && items.len() != 5 {
                     return Err(Box::new(ParseError::WrongArgCount {
-                        expected: 2,
+                        expected: 3,
                         got: items.len() - 1,
                     }));
                 }
                 let score: u16 = items[1].parse().context(NumParseFail)?;
                 let is_full: i8 = items[2].parse().context(NumParseFail)?;
                 let num_judge_log_rows: usize = items[3].parse().context(NumParseFail)?;
+                let num_subtasks: usize = match items.get(4) {
+                    Some(cnt) => cnt.parse().context(NumParseFail)?,
+                    None => 0,
+                };
 
                 if score > 100 {
                     return Err(Box::new(ParseError::Range {
@@ -106,9 +189,18 @@ impl<'a> Valuer<'a> {
                     self.stdout.read_line(&mut line)?;
                     tests.push(line.parse()?);
                 }
+                let mut subtasks = Vec::new();
+                for _ in 0..num_subtasks {
+                    line.clear();
+                    self.stdout.read_line(&mut line)?;
+                    subtasks.push(parse_subtask_score(&line)?);
+                }
+                // invalid subtasks are reported as judge fault
+                check_subtasks(&subtasks, self.ctx.problem_data, score.into())?;
                 ValuerResponse::Finish {
                     score: score.into(),
                     treat_as_full: is_full == 1,
+                    subtasks,
                     judge_log: crate::judge_log::JudgeLog {
                         tests,
                         compile_stdout: String::new(),
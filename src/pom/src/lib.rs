@@ -13,10 +13,74 @@ pub struct FileRef {
     pub path: String,
 }
 
+/// Limits for solution, running on test.
+/// Limits which are not set are taken from contest config
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Time limit in milliseconds
+    pub time: Option<u64>,
+    /// Memory limit in bytes
+    pub memory: Option<u64>,
+    pub process_count: Option<u64>,
+}
+
+impl Limits {
+    /// Returns limits, in which unset values are taken from `fallback`
+    pub fn or(self, fallback: Limits) -> Limits {
+        Limits {
+            time: self.time.or(fallback.time),
+            memory: self.memory.or(fallback.memory),
+            process_count: self.process_count.or(fallback.process_count),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Test {
     pub path: FileRef,
     pub correct: Option<FileRef>,
+    /// Id of group this test belongs to
+    #[serde(default)]
+    pub group: Option<GroupId>,
+    /// Already includes problem-wide and group limits
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+pub type GroupId = u32;
+
+/// Which test results of group are shown to contestant
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeedbackPolicy {
+    /// Results of all judged tests, including test data
+    Complete,
+    /// Only result of first failed test
+    Icpc,
+    /// Only group score
+    None,
+}
+
+impl Default for FeedbackPolicy {
+    fn default() -> Self {
+        FeedbackPolicy::Complete
+    }
+}
+
+/// Group of tests, scored as a whole (also known as subtask)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    /// Starts from 1; also used as subtask id
+    pub id: GroupId,
+    pub name: String,
+    /// Score for passing all tests of group
+    pub score: u32,
+    /// Groups which must be passed for this group to be judged
+    pub dependencies: Vec<GroupId>,
+    pub feedback: FeedbackPolicy,
+    /// Overrides problem-wide limits for tests of this group
+    #[serde(default)]
+    pub limits: Limits,
 }
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
@@ -51,4 +115,10 @@ pub struct Problem {
     pub checker_cmd: Vec<String>,
     pub valuer_exe: FileRef,
     pub valuer_cfg: FileRef,
+    /// Empty if problem has no groups
+    #[serde(default)]
+    pub groups: Vec<Group>,
+    /// Default limits for all tests
+    #[serde(default)]
+    pub limits: Limits,
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct CustomCheck {
@@ -22,59 +23,69 @@ pub struct RawTestsSpec {
     pub map: String,
     pub testgen: Option<Vec<String>>,
     pub files: Option<String>,
+    /// Pattern of correct answer files; only allowed together with `files`
+    pub answers: Option<String>,
 }
 
-impl RawTestsSpec {
-    fn parse_mapping_chunk(&self, ch: &str) -> Result<Vec<u32>, String> {
-        if ch.contains("..") {
-            let parts: Vec<_> = ch.split("..").collect();
-            if parts.len() != 2 {
-                return Err("range map chunk must look like x..y".to_string());
-            }
-            let parts: Result<Vec<_>, _> = parts.into_iter().map(|x| x.parse::<u32>()).collect();
-            match parts {
-                Ok(parts) => {
-                    let begin = parts[0];
-                    let end = parts[1];
-                    if begin > end {
-                        return Err(
-                            "range begin must be less than or equal to range end".to_string()
-                        );
-                    }
-                    let idxs: Vec<_> = std::ops::RangeInclusive::new(begin, end).collect();
-                    return Ok(idxs);
-                }
-                Err(e) => {
-                    return Err(format!("couldn't parse range bound: {}", e.to_string()));
+/// Interpolates test id into file name pattern
+fn format_file_pattern(pattern: &str, id: u32) -> Result<String, String> {
+    match formatf::format(pattern.as_bytes(), &[formatf::Value::Int(i128::from(id))]) {
+        Ok(file) => Ok(String::from_utf8(file).expect("interpolation provided non-utf8 data")),
+        // TODO: implement Display for formatf FormatError
+        Err(err) => Err(format!("formatting error: {:?}", err)),
+    }
+}
+
+fn parse_mapping_chunk(ch: &str) -> Result<Vec<u32>, String> {
+    if ch.contains("..") {
+        let parts: Vec<_> = ch.split("..").collect();
+        if parts.len() != 2 {
+            return Err("range map chunk must look like x..y".to_string());
+        }
+        let parts: Result<Vec<_>, _> = parts.into_iter().map(|x| x.parse::<u32>()).collect();
+        match parts {
+            Ok(parts) => {
+                let begin = parts[0];
+                let end = parts[1];
+                if begin > end {
+                    return Err("range begin must be less than or equal to range end".to_string());
                 }
+                let idxs: Vec<_> = std::ops::RangeInclusive::new(begin, end).collect();
+                return Ok(idxs);
+            }
+            Err(e) => {
+                return Err(format!("couldn't parse range bound: {}", e.to_string()));
             }
         }
+    }
 
-        match ch.parse() {
-            Ok(num) => Ok(vec![num]),
-            Err(err) => Err(format!("couldn't parse number: {}", err.to_string())),
-        }
+    match ch.parse() {
+        Ok(num) => Ok(vec![num]),
+        Err(err) => Err(format!("couldn't parse number: {}", err.to_string())),
     }
+}
 
-    fn parse_mapping(&self) -> Result<Vec<u32>, String> {
-        let chunks = self.map.split(',');
-        let mut out = vec![];
-        for ch in chunks {
-            match self.parse_mapping_chunk(ch) {
-                Ok(idxs) => {
-                    out.extend(idxs.into_iter());
-                }
-                err => {
-                    return err;
-                }
+/// Parses comma-separated list of test numbers and ranges, e.g. `1,3..5`
+fn parse_mapping(map: &str) -> Result<Vec<u32>, String> {
+    let chunks = map.split(',');
+    let mut out = vec![];
+    for ch in chunks {
+        match parse_mapping_chunk(ch) {
+            Ok(idxs) => {
+                out.extend(idxs.into_iter());
+            }
+            err => {
+                return err;
             }
         }
-        if !out.is_sorted() {
-            return Err("mapping is not sorted".to_string());
-        }
-        Ok(out)
     }
+    if !out.is_sorted() {
+        return Err("mapping is not sorted".to_string());
+    }
+    Ok(out)
+}
 
+impl RawTestsSpec {
     fn postprocess(&self) -> Result<Vec<(u32, TestSpec)>, String> {
         {
             let mut cnt = 0;
@@ -88,23 +99,19 @@ impl RawTestsSpec {
                 return Err("exactly one of 'files' and 'testgen' must be specified".to_string());
             }
         }
-        let idxs = self.parse_mapping()?;
+        if self.answers.is_some() && self.files.is_none() {
+            return Err("'answers' can only be specified together with 'files'".to_string());
+        }
+        let idxs = parse_mapping(&self.map)?;
         let mut out = Vec::new();
         if let Some(file_tpl) = &self.files {
             for &id in idxs.iter() {
-                let res =
-                    formatf::format(file_tpl.as_bytes(), &[formatf::Value::Int(i128::from(id))]);
-                match res {
-                    Ok(file) => {
-                        let file =
-                            String::from_utf8(file).expect("interpolation provided non-utf8 data");
-                        out.push((id, TestGenSpec::File { path: file }));
-                    }
-                    Err(err) => {
-                        return Err(format!("formatting error: {:?}", err));
-                        // TODO: implement Display for formatf FormatError
-                    }
-                }
+                let path = format_file_pattern(file_tpl, id)?;
+                let answer = match &self.answers {
+                    Some(answer_tpl) => Some(format_file_pattern(answer_tpl, id)?),
+                    None => None,
+                };
+                out.push((id, TestGenSpec::File { path, answer }));
             }
         }
         if let Some(testgen_cmd) = &self.testgen {
@@ -119,7 +126,14 @@ impl RawTestsSpec {
         }
         let out = out
             .into_iter()
-            .map(|(id, test_gen_spec)| (id, TestSpec { gen: test_gen_spec }))
+            .map(|(id, test_gen_spec)| {
+                let spec = TestSpec {
+                    gen: test_gen_spec,
+                    group: None,
+                    limits: pom::Limits::default(),
+                };
+                (id, spec)
+            })
             .collect();
 
         Ok(out)
@@ -128,13 +142,118 @@ impl RawTestsSpec {
 
 #[derive(Clone, Debug)]
 pub enum TestGenSpec {
-    Generate { testgen: String, args: Vec<String> },
-    File { path: String },
+    Generate {
+        testgen: String,
+        args: Vec<String>,
+    },
+    /// `answer`, if present, is file with correct answer
+    File {
+        path: String,
+        answer: Option<String>,
+    },
 }
 
 #[derive(Debug)]
 pub struct TestSpec {
     pub gen: TestGenSpec,
+    pub group: Option<pom::GroupId>,
+    pub limits: pom::Limits,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct RawLimits {
+    /// Time limit in milliseconds
+    pub time: Option<u64>,
+    /// Memory limit in megabytes
+    pub memory: Option<u64>,
+    #[serde(rename = "process-count")]
+    pub process_count: Option<u64>,
+}
+
+impl RawLimits {
+    fn postprocess(&self) -> Result<pom::Limits, String> {
+        if self.time == Some(0) || self.memory == Some(0) || self.process_count == Some(0) {
+            return Err("limits must be positive".to_string());
+        }
+        let memory = match self.memory {
+            Some(mem) => Some(
+                mem.checked_mul(1024 * 1024)
+                    .ok_or_else(|| "memory limit is too large".to_string())?,
+            ),
+            None => None,
+        };
+        Ok(pom::Limits {
+            time: self.time,
+            memory,
+            process_count: self.process_count,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawGroup {
+    pub name: String,
+    /// Tests of group, in same format as `map` of `[[tests]]`
+    pub tests: String,
+    #[serde(default)]
+    pub score: u32,
+    /// Names of groups, which must be passed before this group is judged
+    #[serde(rename = "depends-on", default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub feedback: pom::FeedbackPolicy,
+    /// Overrides problem limits for tests of this group
+    #[serde(default)]
+    pub limits: RawLimits,
+}
+
+/// Outcome, which solution is expected to get on the whole test set
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// All tests passed
+    Ok,
+    WrongAnswer,
+    PresentationError,
+    TimeLimitExceeded,
+    RuntimeError,
+    /// Solution gets exactly this score
+    Partial(u32),
+}
+
+impl std::str::FromStr for Verdict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Verdict, String> {
+        let verdict = match s {
+            "ok" => Verdict::Ok,
+            "wa" => Verdict::WrongAnswer,
+            "pe" => Verdict::PresentationError,
+            "tl" => Verdict::TimeLimitExceeded,
+            "re" => Verdict::RuntimeError,
+            _ if s.starts_with("partial:") => {
+                let score = &s["partial:".len()..];
+                match score.parse() {
+                    Ok(score) if score <= 100 => Verdict::Partial(score),
+                    _ => return Err(format!("invalid partial score: {}", score)),
+                }
+            }
+            _ => return Err(format!("unknown verdict: {}", s)),
+        };
+        Ok(verdict)
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Verdict::Ok => f.write_str("ok"),
+            Verdict::WrongAnswer => f.write_str("wa"),
+            Verdict::PresentationError => f.write_str("pe"),
+            Verdict::TimeLimitExceeded => f.write_str("tl"),
+            Verdict::RuntimeError => f.write_str("re"),
+            Verdict::Partial(score) => write!(f, "partial:{}", score),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -167,9 +286,128 @@ pub struct RawProblem {
 
     #[serde(rename = "valuer-cfg")]
     pub valuer_cfg: Option<String>,
+
+    /// Path to validator source, relative to problem root
+    pub validator: Option<String>,
+
+    /// Path to interactor source, relative to problem root
+    pub interactor: Option<String>,
+
+    #[serde(default)]
+    pub groups: Vec<RawGroup>,
+
+    /// Default limits for all tests
+    #[serde(default)]
+    pub limits: RawLimits,
+
+    /// Maps solution name to expected verdict (see `Verdict`)
+    #[serde(default)]
+    pub solutions: BTreeMap<String, String>,
 }
 
 impl RawProblem {
+    /// Validates groups and assigns them to tests
+    fn process_groups(&self, tests: &mut [TestSpec]) -> Result<Vec<pom::Group>, String> {
+        let mut groups: Vec<pom::Group> = Vec::new();
+        for raw_group in &self.groups {
+            if groups.iter().any(|g| g.name == raw_group.name) {
+                return Err(format!(
+                    "group {} is defined more than once",
+                    raw_group.name
+                ));
+            }
+            let id = groups.len() as pom::GroupId + 1;
+            let mut dependencies = Vec::new();
+            for dep_name in &raw_group.depends_on {
+                // groups are judged in order of definition, so only previous groups can be dependencies
+                match groups.iter().find(|g| &g.name == dep_name) {
+                    Some(dep) => dependencies.push(dep.id),
+                    None => {
+                        return Err(format!(
+                            "group {} depends on {}, which is not defined before it",
+                            raw_group.name, dep_name
+                        ));
+                    }
+                }
+            }
+            let limits = raw_group
+                .limits
+                .postprocess()
+                .map_err(|err| format!("group {}: {}", raw_group.name, err))?;
+            let test_ids = parse_mapping(&raw_group.tests)
+                .map_err(|err| format!("group {}: {}", raw_group.name, err))?;
+            if test_ids.is_empty() {
+                return Err(format!("group {} contains no tests", raw_group.name));
+            }
+            for tid in test_ids {
+                let test = match tests.get_mut((tid as usize).wrapping_sub(1)) {
+                    Some(test) => test,
+                    None => {
+                        return Err(format!(
+                            "group {} contains test {}, which does not exist",
+                            raw_group.name, tid
+                        ));
+                    }
+                };
+                if test.group.replace(id).is_some() {
+                    return Err(format!("test {} belongs to several groups", tid));
+                }
+                test.limits = limits;
+            }
+            groups.push(pom::Group {
+                id,
+                name: raw_group.name.clone(),
+                score: raw_group.score,
+                dependencies,
+                feedback: raw_group.feedback,
+                limits,
+            });
+        }
+        if groups.is_empty() {
+            return Ok(groups);
+        }
+        if let Some(pos) = tests.iter().position(|test| test.group.is_none()) {
+            return Err(format!("test {} does not belong to any group", pos + 1));
+        }
+        let total_score = groups
+            .iter()
+            .try_fold(0u32, |sum, g| sum.checked_add(g.score))
+            .ok_or_else(|| "sum of group scores is too large".to_string())?;
+        if total_score != 100 {
+            return Err(format!(
+                "sum of group scores must be 100, but it is {}",
+                total_score
+            ));
+        }
+        Ok(groups)
+    }
+
+    fn process_solutions(&self) -> Result<BTreeMap<String, Verdict>, String> {
+        let mut solutions = BTreeMap::new();
+        for (name, verdict) in &self.solutions {
+            let verdict = verdict
+                .parse::<Verdict>()
+                .map_err(|err| format!("solution {}: {}", name, err))?;
+            solutions.insert(name.clone(), verdict);
+        }
+        // primary solution produces answers, so it must pass all tests
+        if let Some(primary_solution) = &self.primary_solution {
+            match solutions.get(primary_solution) {
+                Some(Verdict::Ok) => {}
+                Some(verdict) => {
+                    return Err(format!(
+                        "primary solution {} is expected to get {}, but it must be ok",
+                        primary_solution, verdict
+                    ));
+                }
+                None => {
+                    solutions.insert(primary_solution.clone(), Verdict::Ok);
+                }
+            }
+        }
+        Ok(solutions)
+    }
+
     fn process_tests(&self) -> Result<Vec<TestSpec>, String> {
         let mut tests = Vec::new();
         for test_spec in &self.tests {
@@ -207,7 +445,19 @@ impl RawProblem {
 
     pub fn postprocess(mut self) -> Result<(Problem, /* warnings */ Vec<String>), String> {
         let mut warnings = Vec::new();
-        let tests = self.process_tests()?;
+        let mut tests = self.process_tests()?;
+        let groups = self.process_groups(&mut tests)?;
+        let limits = self.limits.postprocess()?;
+        for test in &mut tests {
+            test.limits = test.limits.or(limits);
+        }
+        let solutions = self.process_solutions()?;
+        if self.interactor.is_some() {
+            warnings.push(
+                "interactor is built, but interactive problems are not supported by invoker yet"
+                    .to_string(),
+            );
+        }
 
         let random_seed = match self.random_seed.take() {
             Some(s) => {
@@ -266,6 +516,11 @@ impl RawProblem {
             }),
             valuer: self.valuer,
             valuer_cfg: self.valuer_cfg,
+            validator: self.validator,
+            interactor: self.interactor,
+            groups,
+            limits,
+            solutions,
         };
 
         Ok((out, warnings))
@@ -289,4 +544,165 @@ pub struct Problem {
     pub check_options: CheckOptions,
     pub valuer: String,
     pub valuer_cfg: Option<String>,
+    pub validator: Option<String>,
+    pub interactor: Option<String>,
+    pub groups: Vec<pom::Group>,
+    pub limits: pom::Limits,
+    /// Expected verdicts of solutions, checked by `jjs-ppc verify`
+    pub solutions: BTreeMap<String, Verdict>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdict_parse() {
+        for &verdict in &[
+            Verdict::Ok,
+            Verdict::WrongAnswer,
+            Verdict::PresentationError,
+            Verdict::TimeLimitExceeded,
+            Verdict::RuntimeError,
+            Verdict::Partial(0),
+            Verdict::Partial(100),
+        ] {
+            assert_eq!(verdict.to_string().parse::<Verdict>(), Ok(verdict));
+        }
+        assert_eq!("partial:42".parse::<Verdict>(), Ok(Verdict::Partial(42)));
+        assert!("partial:101".parse::<Verdict>().is_err());
+        assert!("partial:".parse::<Verdict>().is_err());
+        assert!("partial:-1".parse::<Verdict>().is_err());
+        assert!("OK".parse::<Verdict>().is_err());
+        assert!("ml".parse::<Verdict>().is_err());
+    }
+
+    #[test]
+    fn test_tests_spec_answers() {
+        let spec = RawTestsSpec {
+            map: "1..2".to_string(),
+            testgen: None,
+            files: Some("%02d.in".to_string()),
+            answers: Some("%02d.ans".to_string()),
+        };
+        let tests = spec.postprocess().unwrap();
+        assert_eq!(tests.len(), 2);
+        match &tests[1].1.gen {
+            TestGenSpec::File { path, answer } => {
+                assert_eq!(path, "02.in");
+                assert_eq!(answer.as_deref(), Some("02.ans"));
+            }
+            other => panic!("unexpected test spec {:?}", other),
+        }
+
+        let spec = RawTestsSpec {
+            map: "1".to_string(),
+            testgen: Some(vec!["gen".to_string()]),
+            files: None,
+            answers: Some("%02d.ans".to_string()),
+        };
+        assert!(spec.postprocess().is_err());
+    }
+
+    fn group(name: &str, tests: &str, score: u32, depends_on: &[&str]) -> RawGroup {
+        RawGroup {
+            name: name.to_string(),
+            tests: tests.to_string(),
+            score,
+            depends_on: depends_on.iter().map(ToString::to_string).collect(),
+            feedback: Default::default(),
+            limits: Default::default(),
+        }
+    }
+
+    fn process_groups(groups: Vec<RawGroup>) -> Result<(Vec<pom::Group>, Vec<TestSpec>), String> {
+        let raw = RawProblem {
+            groups,
+            ..Default::default()
+        };
+        let mut tests: Vec<_> = (0..4)
+            .map(|_| TestSpec {
+                gen: TestGenSpec::File {
+                    path: "test".to_string(),
+                    answer: None,
+                },
+                group: None,
+                limits: Default::default(),
+            })
+            .collect();
+        raw.process_groups(&mut tests).map(|groups| (groups, tests))
+    }
+
+    #[test]
+    fn test_process_groups() {
+        let (groups, tests) = process_groups(vec![]).unwrap();
+        assert!(groups.is_empty());
+        assert!(tests.iter().all(|test| test.group.is_none()));
+
+        let mut large = group("large", "3..4", 70, &["samples"]);
+        large.limits.time = Some(5000);
+        let (groups, tests) =
+            process_groups(vec![group("samples", "1..2", 30, &[]), large]).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].id, 2);
+        assert_eq!(groups[1].dependencies, [1]);
+        let test_groups: Vec<_> = tests.iter().map(|test| test.group).collect();
+        assert_eq!(test_groups, [Some(1), Some(1), Some(2), Some(2)]);
+        assert_eq!(tests[0].limits.time, None);
+        assert_eq!(tests[3].limits.time, Some(5000));
+    }
+
+    #[test]
+    fn test_process_groups_errors() {
+        let cases = vec![
+            vec![group("a", "1..4", 50, &[]), group("a", "1..4", 50, &[])],
+            vec![group("a", "1..2", 50, &["b"]), group("b", "3..4", 50, &[])],
+            vec![group("a", "1..3", 100, &[])],
+            vec![group("a", "1..5", 100, &[])],
+            vec![group("a", "1..3", 50, &[]), group("b", "3..4", 50, &[])],
+            vec![group("a", "1..4", 90, &[])],
+            // sum would wrap around to 100
+            vec![
+                group("a", "1..2", u32::max_value(), &[]),
+                group("b", "3..4", 101, &[]),
+            ],
+        ];
+        for groups in cases {
+            assert!(process_groups(groups).is_err());
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = RawLimits {
+            memory: Some(256),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits.postprocess().unwrap().memory,
+            Some(256 * 1024 * 1024)
+        );
+        let limits = RawLimits {
+            memory: Some(u64::max_value() / 1024),
+            ..Default::default()
+        };
+        assert!(limits.postprocess().is_err());
+    }
+
+    #[test]
+    fn test_process_solutions() {
+        let mut raw = RawProblem::default();
+        raw.solutions.insert("slow".to_string(), "tl".to_string());
+        raw.primary_solution = Some("main".to_string());
+        let solutions = raw.process_solutions().unwrap();
+        assert_eq!(solutions["slow"], Verdict::TimeLimitExceeded);
+        assert_eq!(solutions["main"], Verdict::Ok);
+
+        raw.solutions.insert("main".to_string(), "wa".to_string());
+        assert!(raw.process_solutions().is_err());
+
+        raw.solutions.insert("main".to_string(), "ok".to_string());
+        raw.solutions.insert("bad".to_string(), "maybe".to_string());
+        assert!(raw.process_solutions().is_err());
+    }
 }
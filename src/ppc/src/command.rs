@@ -21,6 +21,22 @@ impl Command {
         cmd
     }
 
+    pub fn exe(&self) -> &OsStr {
+        &self.exe
+    }
+
+    pub fn argv(&self) -> &[OsString] {
+        &self.argv
+    }
+
+    pub fn envs(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.env.iter().map(|(k, v)| (k.as_os_str(), v.as_os_str()))
+    }
+
+    pub fn cwd(&self) -> Option<&OsStr> {
+        self.cwd.as_deref()
+    }
+
     pub fn to_string_pretty(&self) -> String {
         use std::fmt::Write;
         let mut out = String::new();
@@ -36,32 +52,6 @@ impl Command {
         }
         out
     }
-
-    pub fn run_quiet(&mut self) {
-        use std::{os::unix::process::ExitStatusExt, process::exit};
-        let mut s = self.to_std_command();
-        let out = s.output().expect("couldn't spawn");
-        let status = out.status;
-        if status.success() {
-            return;
-        }
-        eprintln!("error: child returned error");
-
-        let exit_code = if status.code().is_some() {
-            format!("normal: {}", status.code().unwrap())
-        } else {
-            format!("signaled: {}", status.signal().unwrap())
-        };
-        eprintln!(
-            "testgen did not finished successfully (exit code {})",
-            exit_code
-        );
-
-        eprintln!("command: `{}`", self);
-        eprintln!("child stdout:\n{}", String::from_utf8_lossy(&out.stdout));
-        eprintln!("child stderr:\n{}", String::from_utf8_lossy(&out.stderr));
-        exit(1);
-    }
 }
 
 impl std::fmt::Display for Command {
@@ -1,5 +1,7 @@
 //! This module implements compiling source package into invoker package
 pub(crate) mod build;
+pub(crate) mod sandbox;
+mod testgen;
 
 use crate::command::Command;
 use pom::{FileRef, FileRefRoot};
@@ -15,28 +17,14 @@ pub(crate) struct ProblemBuilder<'a> {
     pub(crate) problem_dir: &'a Path,
     pub(crate) out_dir: &'a Path,
     pub(crate) build_backend: &'a dyn build::BuildBackend,
-}
-
-fn get_entropy_hex(s: &mut [u8]) {
-    let n = s.len();
-    assert_eq!(n % 2, 0);
-    let m = n / 2;
-    let rnd_buf = &mut s[m..];
-    getrandom::getrandom(rnd_buf).expect("get entropy failed");
-    for i in 0..m {
-        let c = s[m + i];
-        let lo = c % 16;
-        let hi = c / 16;
-        s[i] = hi;
-        s[i + m] = lo;
-    }
-    for x in s.iter_mut() {
-        if *x < 10 {
-            *x += b'0';
-        } else {
-            *x += b'a' - 10;
-        }
-    }
+    /// Count of tests generated simultaneously
+    pub(crate) jobs: usize,
+    /// Generated tests are cached here; `None` disables caching
+    pub(crate) cache_dir: Option<&'a Path>,
+    /// Limits for testgens, primary solution and validator
+    pub(crate) limits: sandbox::Limits,
+    /// If false, programs are launched directly, and only time and memory limits are enforced
+    pub(crate) sandbox: bool,
 }
 
 // TODO: remove duplicated code
@@ -121,127 +109,153 @@ impl<'a> ProblemBuilder<'a> {
         out
     }
 
-    fn configure_command(&self, cmd: &mut Command) {
-        cmd.current_dir(self.problem_dir);
-        cmd.env("JJS_PROBLEM_SRC", &self.problem_dir);
-        cmd.env("JJS_PROBLEM_DEST", &self.out_dir);
+    fn build_validator(&self) -> Option<Command> {
+        let validator_path = self.cfg.validator.as_ref()?;
+        let out_path = self.out_dir.join("assets/validator");
+        Some(self.do_build(&self.problem_dir.join(validator_path), &out_path))
+    }
+
+    fn build_interactor(&self) {
+        if let Some(interactor_path) = &self.cfg.interactor {
+            let out_path = self.out_dir.join("assets/interactor");
+            self.do_build(&self.problem_dir.join(interactor_path), &out_path);
+        }
     }
 
     fn build_tests(
         &self,
-        testgens: &HashMap<String, Command>,
+        testgens: HashMap<String, Command>,
         gen_answers: Option<&Command>,
+        validator: Option<Command>,
     ) -> Vec<pom::Test> {
-        let tests_path = format!("{}/assets/tests", self.out_dir.display());
-        std::fs::create_dir_all(&tests_path).expect("couldn't create tests output dir");
+        let cache = match self.cache_dir {
+            Some(dir) => match testgen::Cache::new(dir.to_path_buf()) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    eprintln!(
+                        "warning: couldn't open test cache {}: {}",
+                        dir.display(),
+                        err
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+        let work_dir = std::env::temp_dir().join(format!("jjs-ppc-compile-{}", std::process::id()));
+        let sandbox = if self.sandbox {
+            sandbox::Sandbox::Minion {
+                backend: minion::setup(),
+                limits: self.limits,
+                exposed_dirs: vec![self.problem_dir.to_path_buf()],
+                // programs may write to `JJS_PROBLEM_DEST`
+                writable_dirs: vec![self.out_dir.to_path_buf()],
+                work_dir: work_dir.clone(),
+                launch_count: Default::default(),
+            }
+        } else {
+            sandbox::Sandbox::Disabled {
+                limits: self.limits,
+            }
+        };
+        let generator = testgen::TestGenerator {
+            testgens,
+            gen_answers: gen_answers.cloned(),
+            validator,
+            problem_dir: self.problem_dir.to_path_buf(),
+            out_dir: self.out_dir.to_path_buf(),
+            random_seed: self.cfg.random_seed.clone(),
+            problem_cfg: fs::read(self.problem_dir.join("problem.toml"))
+                .expect("couldn't read problem.toml"),
+            cache,
+            sandbox,
+        };
+        let specs = self.cfg.tests.iter().map(|spec| spec.gen.clone()).collect();
+        let outcomes = generator.run(specs, self.jobs);
+        std::fs::remove_dir_all(&work_dir).ok();
+
         let mut out = vec![];
-        for (i, test_spec) in self.cfg.tests.iter().enumerate() {
+        let mut invalid_tests = vec![];
+        let mut failed_tests = vec![];
+        let mut cached_count = 0;
+        for (i, (test_spec, outcome)) in self.cfg.tests.iter().zip(outcomes).enumerate() {
             let tid = i + 1;
-            let out_file_path = format!("{}/{}-in.txt", &tests_path, tid);
-            match &test_spec.gen {
-                crate::cfg::TestGenSpec::Generate { testgen, args } => {
-                    let testgen_cmd = testgens.get(testgen).unwrap_or_else(|| {
-                        eprintln!("error: unknown testgen {}", testgen);
-                        exit(1);
-                    });
-
-                    let mut entropy_buf = [0; 64];
-                    get_entropy_hex(&mut entropy_buf);
-                    let entropy = String::from_utf8(entropy_buf.to_vec()).unwrap(); // only ASCII can be here
-
-                    let mut cmd = testgen_cmd.clone();
-                    for a in args {
-                        cmd.arg(a);
+            match outcome {
+                testgen::TestOutcome::Ok { cached } => {
+                    if cached {
+                        cached_count += 1;
                     }
-                    cmd.env("JJS_TEST_ID", &tid.to_string());
-                    let out_file_handle = crate::open_as_handle(&out_file_path)
-                        .expect("couldn't create test output file");
-                    cmd.env("JJS_TEST", &out_file_handle.to_string());
-                    cmd.env("JJS_RANDOM_SEED", &entropy);
-                    self.configure_command(&mut cmd);
-                    cmd.run_quiet();
                 }
-                crate::cfg::TestGenSpec::File { path } => {
-                    let src_path = self.problem_dir.join("tests").join(path);
-                    if let Err(e) = std::fs::copy(&src_path, &out_file_path) {
-                        eprintln!(
-                            "Couldn't copy test data from {} to {}: {}",
-                            src_path.display(),
-                            out_file_path,
-                            e,
-                        );
-                    }
+                testgen::TestOutcome::Invalid(message) => {
+                    invalid_tests.push((tid, message));
+                    continue;
+                }
+                testgen::TestOutcome::Failed(message) => {
+                    failed_tests.push((tid, message));
+                    continue;
                 }
             }
-            let mut test_info = pom::Test {
+            let has_answer_file = match &test_spec.gen {
+                crate::cfg::TestGenSpec::File { answer, .. } => answer.is_some(),
+                crate::cfg::TestGenSpec::Generate { .. } => false,
+            };
+            let correct = if gen_answers.is_some() || has_answer_file {
+                Some(FileRef {
+                    path: format!("tests/{}-out.txt", tid),
+                    root: FileRefRoot::Problem,
+                })
+            } else {
+                None
+            };
+            out.push(pom::Test {
                 path: FileRef {
                     path: format!("tests/{}-in.txt", tid),
                     root: FileRefRoot::Problem,
                 },
-                correct: None,
-            };
-            if let Some(cmd) = gen_answers {
-                let test_data = fs::File::open(&out_file_path).unwrap();
-
-                let correct_file_path = format!("{}/{}-out.txt", &tests_path, tid);
-
-                let answer_data = fs::File::create(&correct_file_path).unwrap();
-
-                let mut cmd = cmd.clone();
-                self.configure_command(&mut cmd);
-                let mut cmd = cmd.to_std_command();
-                unsafe {
-                    use std::os::unix::{io::IntoRawFd, process::CommandExt};
-                    let test_data_fd = test_data.into_raw_fd();
-                    let test_data_fd = libc::dup(test_data_fd);
-
-                    let ans_data_fd = answer_data.into_raw_fd();
-                    let ans_data_fd = libc::dup(ans_data_fd);
-                    cmd.pre_exec(move || {
-                        if libc::dup2(test_data_fd, 0) == -1 {
-                            return Err(std::io::Error::last_os_error());
-                        }
-                        if libc::dup2(ans_data_fd, 1) == -1 {
-                            return Err(std::io::Error::last_os_error());
-                        }
-                        Ok(())
-                    });
-                }
-                let status = cmd
-                    .stdin(crate::Stdio::piped())
-                    .stdout(crate::Stdio::piped())
-                    .status()
-                    .unwrap_or_else(|err| panic!("launching gen_answers cmd failed: {}", err));
-                if !status.success() {
-                    eprintln!(
-                        "Error when generating correct answer for test {}: primary solution failed",
-                        tid
-                    );
-                    exit(1);
-                }
-                let short_file_path = format!("tests/{}-out.txt", tid);
-                test_info.correct.replace(FileRef {
-                    path: short_file_path,
-                    root: FileRefRoot::Problem,
-                });
+                correct,
+                group: test_spec.group,
+                limits: test_spec.limits,
+            });
+        }
+        if !failed_tests.is_empty() {
+            eprintln!("error: couldn't generate {} tests", failed_tests.len());
+            for (tid, message) in &failed_tests {
+                eprintln!("- test {}: {}", tid, message);
             }
-            out.push(test_info);
         }
+        if !invalid_tests.is_empty() {
+            eprintln!("error: {} tests are invalid", invalid_tests.len());
+            for (tid, message) in &invalid_tests {
+                eprintln!("- test {}: {}", tid, message);
+            }
+        }
+        if !failed_tests.is_empty() || !invalid_tests.is_empty() {
+            exit(1);
+        }
+        println!(
+            "Tests: {} total, {} reused from cache",
+            self.cfg.tests.len(),
+            cached_count
+        );
         out
     }
 
     fn build_checkers(&self) -> FileRef {
-        // TODO: support multi-file checkers
-        let checker_path = format!("{}/checkers/main.cpp", self.problem_dir.display());
+        // checker is either `checkers/main` directory or single file `checkers/main.<ext>`
+        let checker_path = self
+            .glob("checkers/main*")
+            .into_iter()
+            .find(|path| path.file_stem().map_or(false, |stem| stem == "main"))
+            .unwrap_or_else(|| self.problem_dir.join("checkers/main.cpp"));
         self.build_checker(&checker_path)
     }
 
-    fn build_checker(&self, checker_path: &str) -> FileRef {
+    fn build_checker(&self, checker_path: &Path) -> FileRef {
         let out_path = format!("{}/assets/checker", self.out_dir.display());
 
         match self.cfg.check {
             crate::cfg::Check::Custom(_) => {
-                self.do_build(Path::new(checker_path), Path::new(&out_path));
+                self.do_build(checker_path, Path::new(&out_path));
                 FileRef {
                     path: "checker/bin".to_string(),
                     root: FileRefRoot::Problem,
@@ -282,12 +296,55 @@ impl<'a> ProblemBuilder<'a> {
         Ok(())
     }
 
+    /// Writes groups description for builtin groups valuer, one line per group:
+    /// `<id> <score> <feedback> <test count> <tests...> <dependency count> <dependencies...>`
+    fn emit_groups(&self) -> std::io::Result<()> {
+        use std::fmt::Write;
+        if self.cfg.groups.is_empty() {
+            return Ok(());
+        }
+        let mut data = format!("{}\n", self.cfg.groups.len());
+        for group in &self.cfg.groups {
+            let tests: Vec<_> = self
+                .cfg
+                .tests
+                .iter()
+                .enumerate()
+                .filter(|(_, test)| test.group == Some(group.id))
+                .map(|(i, _)| (i + 1).to_string())
+                .collect();
+            let dependencies: Vec<_> = group.dependencies.iter().map(ToString::to_string).collect();
+            let feedback = match group.feedback {
+                pom::FeedbackPolicy::Complete => "complete",
+                pom::FeedbackPolicy::Icpc => "icpc",
+                pom::FeedbackPolicy::None => "none",
+            };
+            writeln!(
+                data,
+                "{} {} {} {} {} {} {}",
+                group.id,
+                group.score,
+                feedback,
+                tests.len(),
+                tests.join(" "),
+                dependencies.len(),
+                dependencies.join(" ")
+            )
+            .unwrap();
+        }
+        let valuer_cfg_dir = self.out_dir.join("assets/valuer-cfg");
+        std::fs::create_dir_all(&valuer_cfg_dir)?;
+        std::fs::write(valuer_cfg_dir.join("groups.txt"), data)
+    }
+
     pub fn build(&self) {
         self.build_modules();
         let solutions = self.build_solutions();
         let testgen_launch_info = self.build_testgens();
 
         let checker_ref = self.build_checkers();
+        let validator = self.build_validator();
+        self.build_interactor();
 
         let checker_cmd = self.cfg.check_options.args.clone();
 
@@ -298,10 +355,16 @@ impl<'a> ProblemBuilder<'a> {
         }
 
         let tests = {
-            let gen_answers = match &self.cfg.check {
+            let need_answers = match &self.cfg.check {
                 crate::cfg::Check::Custom(cs) => cs.pass_correct,
                 crate::cfg::Check::Builtin(_) => true,
             };
+            // answers are generated only for tests without answer file
+            let gen_answers = need_answers
+                && self.cfg.tests.iter().any(|test| match &test.gen {
+                    crate::cfg::TestGenSpec::File { answer, .. } => answer.is_none(),
+                    crate::cfg::TestGenSpec::Generate { .. } => true,
+                });
             let gen_answers = if gen_answers {
                 let primary_solution_name = self.cfg.primary_solution.as_ref().unwrap_or_else(|| {
                     eprintln!("primary-solution must be specified in order to generate tests correct answers");
@@ -323,11 +386,15 @@ impl<'a> ProblemBuilder<'a> {
             } else {
                 None
             };
-            self.build_tests(&testgen_launch_info, gen_answers)
+            self.build_tests(testgen_launch_info, gen_answers, validator)
         };
         if let Err(e) = self.copy_raw() {
             eprintln!("Error: {}", e);
         }
+        if let Err(e) = self.emit_groups() {
+            eprintln!("Error: couldn't write groups config: {}", e);
+            exit(1);
+        }
 
         let valuer_exe = FileRef {
             root: FileRefRoot::System,
@@ -347,6 +414,8 @@ impl<'a> ProblemBuilder<'a> {
             valuer_exe,
             tests,
             valuer_cfg,
+            groups: self.cfg.groups.clone(),
+            limits: self.cfg.limits,
         };
         let manifest_path = format!("{}/manifest.json", self.out_dir.display());
         let manifest_data = serde_json::to_string(&problem).expect("couldn't serialize manifest");
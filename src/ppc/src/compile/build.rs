@@ -1,5 +1,12 @@
-use snafu::{ResultExt, Snafu};
-use std::path::Path;
+mod cmake;
+mod lang;
+
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Copy, Clone)]
 pub(crate) struct Task<'a> {
@@ -22,8 +29,14 @@ pub(crate) enum TaskError {
     ExitCodeNonZero {},
     #[snafu(display("child execution failed: {}", source))]
     ChildExecError { source: std::io::Error },
-    #[snafu(display("feature not supported: {}", feature))]
-    FeatureNotSupported { feature: &'static str },
+    #[snafu(display("io error: {}", source))]
+    Io { source: std::io::Error },
+    #[snafu(display("couldn't detect language of {}", path.display()))]
+    UnknownLanguage { path: PathBuf },
+    #[snafu(display("invalid build.toml: {}", description))]
+    BadBuildSpec { description: String },
+    #[snafu(display("build did not produce {}", path.display()))]
+    NoOutput { path: PathBuf },
 }
 
 impl<'a> Task<'a> {
@@ -51,37 +64,81 @@ pub(crate) trait BuildBackend {
     fn process_task(&self, task: Task) -> Result<TaskSuccess, TaskError>;
 }
 
-/// Ppc-integrated build system
+/// Ppc-integrated build system.
+///
+/// Sources are built in following way:
+/// - directory with `build.toml` is built with commands specified in it (see `BuildSpec`)
+/// - directory with `CMakeLists.txt` is built with `CMake` backend
+/// - otherwise, language is detected by file extension (or main file name for directories)
 pub(crate) struct Pibs<'a> {
     pub(crate) jjs_dir: &'a Path,
 }
 
+/// Contents of `build.toml`
+#[derive(Deserialize)]
+struct BuildSpec {
+    /// Commands, executed in source directory. Each command is list of arguments.
+    /// `$JJS_BUILD_SRC`, `$JJS_BUILD_DEST` and `$JJS_BUILD_TMP` in arguments are replaced
+    /// with corresponding paths. Build must produce executable `$JJS_BUILD_DEST/bin`.
+    build: Vec<Vec<String>>,
+}
+
+impl<'a> Pibs<'a> {
+    fn build_with_spec(&self, task: Task, spec_path: &Path) -> Result<(), TaskError> {
+        let spec = fs::read_to_string(spec_path).context(Io {})?;
+        let spec: BuildSpec = toml::from_str(&spec).map_err(|err| TaskError::BadBuildSpec {
+            description: err.to_string(),
+        })?;
+        let vars = [
+            ("$JJS_BUILD_SRC", task.src),
+            ("$JJS_BUILD_DEST", task.dest),
+            ("$JJS_BUILD_TMP", task.tmp),
+        ];
+        for argv in &spec.build {
+            let argv: Vec<_> = argv
+                .iter()
+                .map(|arg| {
+                    vars.iter().fold(arg.clone(), |arg, (name, path)| {
+                        arg.replace(name, &path.to_string_lossy())
+                    })
+                })
+                .collect();
+            if argv.is_empty() {
+                return Err(TaskError::BadBuildSpec {
+                    description: "empty command".to_string(),
+                });
+            }
+            std::process::Command::new(&argv[0])
+                .args(&argv[1..])
+                .current_dir(task.src)
+                .env("JJS_PATH", self.jjs_dir)
+                .run()?;
+        }
+        Ok(())
+    }
+}
+
 impl<'a> BuildBackend for Pibs<'a> {
     fn process_task(&self, task: Task) -> Result<TaskSuccess, TaskError> {
-        if task.multi_file() {
-            return Err(TaskError::FeatureNotSupported {
-                feature: "multi-file sources",
-            });
+        let spec_path = task.src.join("build.toml");
+        if task.multi_file() && spec_path.exists() {
+            self.build_with_spec(task, &spec_path)?;
+        } else if task.multi_file() && task.src.join("CMakeLists.txt").exists() {
+            return cmake::CMake {
+                jjs_dir: self.jjs_dir,
+            }
+            .process_task(task);
+        } else {
+            let (lang, main_file) = lang::Language::detect(task.src).context(UnknownLanguage {
+                path: task.src.to_path_buf(),
+            })?;
+            lang::build(&task, lang, &main_file, self.jjs_dir)?;
         }
 
-        let incl_arg = format!("-I{}/include", self.jjs_dir.display());
-        let link_arg = format!("-L{}/lib", self.jjs_dir.display());
-
         let dest_file = task.dest.join("bin");
-        std::process::Command::new("g++")
-            .arg("-std=c++17")
-            .arg(incl_arg)
-            .arg(link_arg)
-            .arg("-DPPC=1")
-            .arg(task.src)
-            .arg("-o")
-            .arg(&dest_file)
-            .arg("-ljtl")
-            .arg("-ljtlrs")
-            .arg("-lpthread")
-            .arg("-ldl")
-            .run()?;
-
+        if !dest_file.is_file() {
+            return Err(TaskError::NoOutput { path: dest_file });
+        }
         let command = crate::command::Command::new(&dest_file);
         Ok(TaskSuccess { command })
     }
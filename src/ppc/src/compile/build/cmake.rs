@@ -0,0 +1,38 @@
+//! Builds directory sources using CMake
+use super::{BuildBackend, CommandExt, Io, Task, TaskError, TaskSuccess};
+use snafu::ResultExt;
+use std::{fs, path::Path, process::Command};
+
+/// Name of executable target, which must be defined by project
+const TARGET_NAME: &str = "Out";
+
+/// Builds directories, containing `CMakeLists.txt`.
+/// Jtl can be found with `find_package(Jtl CONFIG REQUIRED)`.
+pub(crate) struct CMake<'a> {
+    pub(crate) jjs_dir: &'a Path,
+}
+
+impl<'a> BuildBackend for CMake<'a> {
+    fn process_task(&self, task: Task) -> Result<TaskSuccess, TaskError> {
+        Command::new("cmake")
+            .arg("-S")
+            .arg(task.src)
+            .arg("-B")
+            .arg(task.tmp)
+            .arg("-DCMAKE_BUILD_TYPE=Release")
+            .arg(format!("-DJtl_DIR={}/share/cmake", self.jjs_dir.display()))
+            .env("JJS_PATH", self.jjs_dir)
+            .run()?;
+        Command::new("cmake")
+            .arg("--build")
+            .arg(task.tmp)
+            .arg("--target")
+            .arg(TARGET_NAME)
+            .run()?;
+        let dest_file = task.dest.join("bin");
+        fs::copy(task.tmp.join(TARGET_NAME), &dest_file).context(Io {})?;
+        Ok(TaskSuccess {
+            command: crate::command::Command::new(&dest_file),
+        })
+    }
+}
@@ -0,0 +1,142 @@
+//! Language detection and single-language builds
+use super::{CommandExt, Io, Task, TaskError};
+use snafu::ResultExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Language {
+    Cpp,
+    Rust,
+    Python,
+    Java,
+}
+
+/// Files, which are considered entry points of multi-file sources
+const MAIN_FILES: &[(&str, Language)] = &[
+    ("main.cpp", Language::Cpp),
+    ("main.rs", Language::Rust),
+    ("main.py", Language::Python),
+    ("Main.java", Language::Java),
+];
+
+impl Language {
+    fn from_extension(ext: &str) -> Option<Language> {
+        match ext {
+            "cpp" | "cc" | "cxx" => Some(Language::Cpp),
+            "rs" => Some(Language::Rust),
+            "py" => Some(Language::Python),
+            "java" => Some(Language::Java),
+            _ => None,
+        }
+    }
+
+    /// Detects language of source (file or directory).
+    /// Returns language and path to main file.
+    pub(crate) fn detect(src: &Path) -> Option<(Language, PathBuf)> {
+        if src.is_dir() {
+            MAIN_FILES
+                .iter()
+                .map(|&(name, lang)| (lang, src.join(name)))
+                .find(|(_, path)| path.is_file())
+        } else {
+            let ext = src.extension()?.to_str()?;
+            Language::from_extension(ext).map(|lang| (lang, src.to_path_buf()))
+        }
+    }
+}
+
+/// Returns all files with one of `extensions` in source directory (or source itself)
+fn sources(task: &Task, extensions: &[&str]) -> Result<Vec<PathBuf>, TaskError> {
+    if !task.src.is_dir() {
+        return Ok(vec![task.src.to_path_buf()]);
+    }
+    let mut out = Vec::new();
+    for item in fs::read_dir(task.src).context(Io {})? {
+        let path = item.context(Io {})?.path();
+        let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if path.is_file() && extensions.contains(&ext) {
+            out.push(path);
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// Writes `dest/bin` script, which executes `command` with all arguments.
+/// `command` is shell code; `$DIR` in it refers to `dest`. Command must only
+/// refer to files in `dest`, because test cache keys only cover this directory.
+fn write_launcher(task: &Task, command: &str) -> Result<(), TaskError> {
+    use std::os::unix::fs::PermissionsExt;
+    let launcher_path = task.dest.join("bin");
+    let script = format!(
+        "#!/bin/sh\nDIR=\"$(dirname \"$0\")\"\nexec {} \"$@\"\n",
+        command
+    );
+    fs::write(&launcher_path, script).context(Io {})?;
+    fs::set_permissions(&launcher_path, fs::Permissions::from_mode(0o755)).context(Io {})
+}
+
+/// Builds source, so that `task.dest/bin` can be executed
+pub(crate) fn build(
+    task: &Task,
+    lang: Language,
+    main_file: &Path,
+    jjs_dir: &Path,
+) -> Result<(), TaskError> {
+    let dest_file = task.dest.join("bin");
+    match lang {
+        Language::Cpp => {
+            let incl_arg = format!("-I{}/include", jjs_dir.display());
+            let link_arg = format!("-L{}/lib", jjs_dir.display());
+            let mut cmd = Command::new("g++");
+            cmd.arg("-std=c++17")
+                .arg(incl_arg)
+                .arg(link_arg)
+                .arg("-DPPC=1");
+            if task.src.is_dir() {
+                cmd.arg(format!("-I{}", task.src.display()));
+            }
+            cmd.args(sources(task, &["cpp", "cc", "cxx"])?)
+                .arg("-o")
+                .arg(&dest_file)
+                .arg("-ljtl")
+                .arg("-ljtlrs")
+                .arg("-lpthread")
+                .arg("-ldl")
+                .run()
+        }
+        Language::Rust => Command::new("rustc")
+            .arg("--edition=2018")
+            .arg("-O")
+            .arg(main_file)
+            .arg("-o")
+            .arg(&dest_file)
+            .run(),
+        Language::Python => {
+            let src_dir = task.dest.join("src");
+            fs::create_dir_all(&src_dir).context(Io {})?;
+            for file in sources(task, &["py"])? {
+                fs::copy(&file, src_dir.join(file.file_name().unwrap())).context(Io {})?;
+            }
+            let main_name = main_file.file_name().unwrap().to_string_lossy();
+            // `-B`: bytecode is not written, so build output does not change
+            // after launch (test cache hashes it)
+            write_launcher(task, &format!("python3 -B \"$DIR/src/{}\"", main_name))
+        }
+        Language::Java => {
+            let classes_dir = task.dest.join("classes");
+            fs::create_dir_all(&classes_dir).context(Io {})?;
+            Command::new("javac")
+                .arg("-d")
+                .arg(&classes_dir)
+                .args(sources(task, &["java"])?)
+                .run()?;
+            let main_class = main_file.file_stem().unwrap().to_string_lossy();
+            write_launcher(task, &format!("java -cp \"$DIR/classes\" {}", main_class))
+        }
+    }
+}
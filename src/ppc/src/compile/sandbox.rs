@@ -0,0 +1,303 @@
+//! Runs problem programs (testgens, primary solution, validator) during compilation
+use crate::command::Command;
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Host directories, exposed to sandbox, so that dynamically linked programs can run
+const SYSTEM_DIRS: &[&str] = &["/bin", "/lib", "/lib64", "/usr"];
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Limits {
+    pub(crate) time: Duration,
+    /// In bytes
+    pub(crate) memory: u64,
+    pub(crate) process_count: u32,
+}
+
+/// Standard streams of launched program
+#[derive(Default)]
+pub(crate) struct Stdio {
+    pub(crate) stdin: Option<fs::File>,
+    /// If not set, output is captured and returned in `Output`
+    pub(crate) stdout: Option<fs::File>,
+}
+
+pub(crate) enum Status {
+    Success,
+    /// Program returned non-zero exit code or was killed
+    Failure {
+        code: Option<i64>,
+    },
+    TimeLimitExceeded,
+    LaunchError(String),
+}
+
+pub(crate) struct Output {
+    pub(crate) status: Status,
+    /// Empty if stdout was redirected
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+}
+
+impl Output {
+    pub(crate) fn success(&self) -> bool {
+        matches!(self.status, Status::Success)
+    }
+}
+
+pub(crate) enum Sandbox {
+    /// Programs are launched directly. Only time and memory limits are enforced:
+    /// program is killed on timeout, and its address space is limited.
+    Disabled { limits: Limits },
+    /// Programs are launched in minion dominion. Directories are exposed
+    /// on the same paths, so that commands can be used as is.
+    Minion {
+        backend: Box<dyn minion::Backend>,
+        limits: Limits,
+        /// Exposed read-only
+        exposed_dirs: Vec<PathBuf>,
+        /// Exposed with write access
+        writable_dirs: Vec<PathBuf>,
+        /// Isolation roots and captured output are stored here
+        work_dir: PathBuf,
+        /// Used to give each launch its own work subdirectory
+        launch_count: AtomicUsize,
+    },
+}
+
+impl Sandbox {
+    /// Returns true if only standard streams are passed to programs,
+    /// so file handles can not be passed via environment
+    pub(crate) fn stdio_only(&self) -> bool {
+        match self {
+            Sandbox::Disabled { .. } => false,
+            Sandbox::Minion { .. } => true,
+        }
+    }
+
+    /// Describes failed status, e.g. "exceeded time limit of 10000 ms"
+    pub(crate) fn describe(&self, status: &Status) -> String {
+        let limits = self.limits();
+        match status {
+            Status::Success => "succeeded".to_string(),
+            Status::TimeLimitExceeded => {
+                format!("exceeded time limit of {} ms", limits.time.as_millis())
+            }
+            Status::Failure { code: Some(code) } => format!(
+                "returned {} (possibly exceeded memory limit of {} MiB)",
+                code,
+                limits.memory / (1024 * 1024)
+            ),
+            Status::Failure { code: None } => format!(
+                "was killed (possibly exceeded memory limit of {} MiB)",
+                limits.memory / (1024 * 1024)
+            ),
+            Status::LaunchError(err) => format!("couldn't be launched: {}", err),
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        match self {
+            Sandbox::Disabled { limits } | Sandbox::Minion { limits, .. } => *limits,
+        }
+    }
+
+    pub(crate) fn run(&self, cmd: &Command, stdio: Stdio) -> Output {
+        match self {
+            Sandbox::Disabled { limits } => run_unsandboxed(cmd, stdio, *limits),
+            Sandbox::Minion {
+                backend,
+                limits,
+                exposed_dirs,
+                writable_dirs,
+                work_dir,
+                launch_count,
+            } => {
+                let launch_dir =
+                    work_dir.join(launch_count.fetch_add(1, Ordering::SeqCst).to_string());
+                let dirs = Dirs {
+                    exposed: exposed_dirs,
+                    writable: writable_dirs,
+                };
+                let status = launch_minion(&**backend, *limits, dirs, &launch_dir, cmd, stdio)
+                    .unwrap_or_else(Status::LaunchError);
+                let out = Output {
+                    status,
+                    stdout: fs::read(launch_dir.join("stdout.txt")).unwrap_or_default(),
+                    stderr: fs::read(launch_dir.join("stderr.txt")).unwrap_or_default(),
+                };
+                fs::remove_dir_all(&launch_dir).ok();
+                out
+            }
+        }
+    }
+}
+
+fn run_unsandboxed(cmd: &Command, stdio: Stdio, limits: Limits) -> Output {
+    use std::{io::Read, os::unix::process::CommandExt, time::Instant};
+    let mut std_cmd = cmd.to_std_command();
+    std_cmd.stdin(match stdio.stdin {
+        Some(file) => std::process::Stdio::from(file),
+        None => std::process::Stdio::null(),
+    });
+    std_cmd.stdout(match stdio.stdout {
+        Some(file) => std::process::Stdio::from(file),
+        None => std::process::Stdio::piped(),
+    });
+    std_cmd.stderr(std::process::Stdio::piped());
+    let memory_limit = limits.memory as libc::rlim_t;
+    unsafe {
+        std_cmd.pre_exec(move || {
+            // own process group, so that children of program are killed together with it
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let rlimit = libc::rlimit {
+                rlim_cur: memory_limit,
+                rlim_max: memory_limit,
+            };
+            if libc::setrlimit(libc::RLIMIT_AS, &rlimit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = match std_cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            return Output {
+                status: Status::LaunchError(err.to_string()),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            }
+        }
+    };
+    // pipes are drained concurrently, so that child does not block on full pipe
+    fn read_all(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                pipe.read_to_end(&mut buf).ok();
+            }
+            buf
+        })
+    }
+    let stdout_reader = read_all(child.stdout.take());
+    let stderr_reader = read_all(child.stderr.take());
+    let deadline = Instant::now() + limits.time;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break Status::Success,
+            Ok(Some(status)) => {
+                break Status::Failure {
+                    code: status.code().map(i64::from),
+                }
+            }
+            Ok(None) if Instant::now() >= deadline => break Status::TimeLimitExceeded,
+            Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            Err(err) => break Status::LaunchError(err.to_string()),
+        }
+    };
+    // also kills processes, left by program
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    child.wait().ok();
+    Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    }
+}
+
+/// Directories, exposed to dominion in addition to system ones
+struct Dirs<'a> {
+    exposed: &'a [PathBuf],
+    writable: &'a [PathBuf],
+}
+
+fn launch_minion(
+    backend: &dyn minion::Backend,
+    limits: Limits,
+    dirs: Dirs,
+    launch_dir: &Path,
+    cmd: &Command,
+    stdio: Stdio,
+) -> Result<Status, String> {
+    let isolation_root = launch_dir.join("root");
+    fs::create_dir_all(&isolation_root).map_err(|err| err.to_string())?;
+    let mut exposed_paths = Vec::new();
+    let system_dirs = SYSTEM_DIRS.iter().map(PathBuf::from);
+    let readonly_dirs = system_dirs
+        .chain(dirs.exposed.iter().cloned())
+        .map(|dir| (dir, minion::DesiredAccess::Readonly));
+    let writable_dirs = dirs
+        .writable
+        .iter()
+        .map(|dir| (dir.clone(), minion::DesiredAccess::Full));
+    for (dir, access) in readonly_dirs.chain(writable_dirs) {
+        if dir.exists() {
+            exposed_paths.push(minion::PathExpositionOptions {
+                src: dir.clone(),
+                dest: dir,
+                access,
+            });
+        }
+    }
+    let dominion = backend
+        .new_dominion(minion::DominionOptions {
+            max_alive_process_count: limits.process_count,
+            memory_limit: limits.memory,
+            time_limit: limits.time,
+            isolation_root,
+            exposed_paths,
+        })
+        .map_err(|err| err.to_string())?;
+
+    let stdin = match stdio.stdin {
+        Some(file) => file,
+        None => fs::File::open("/dev/null").map_err(|err| err.to_string())?,
+    };
+    let stdout = match stdio.stdout {
+        Some(file) => file,
+        None => fs::File::create(launch_dir.join("stdout.txt")).map_err(|err| err.to_string())?,
+    };
+    let stderr = fs::File::create(launch_dir.join("stderr.txt")).map_err(|err| err.to_string())?;
+    let mut minion_cmd = minion::Command::new();
+    minion_cmd
+        .path(cmd.exe())
+        .args(cmd.argv())
+        .envs(cmd.envs())
+        .current_dir(cmd.cwd().unwrap_or_else(|| OsStr::new("/")))
+        .dominion(dominion);
+    unsafe {
+        minion_cmd.stdin(minion::InputSpecification::handle_of(stdin));
+        minion_cmd.stdout(minion::OutputSpecification::handle_of(stdout));
+        minion_cmd.stderr(minion::OutputSpecification::handle_of(stderr));
+    }
+    let mut child = match minion_cmd.spawn(backend) {
+        Ok(child) => child,
+        Err(err) => return Ok(Status::LaunchError(err.to_string())),
+    };
+    match child
+        .wait_for_exit(limits.time)
+        .map_err(|err| err.to_string())?
+    {
+        minion::WaitOutcome::Timeout => {
+            child.kill().ok();
+            Ok(Status::TimeLimitExceeded)
+        }
+        minion::WaitOutcome::AlreadyFinished => unreachable!("not expected other to wait"),
+        minion::WaitOutcome::Exited => {
+            match child.get_exit_code().map_err(|err| err.to_string())? {
+                Some(0) => Ok(Status::Success),
+                code => Ok(Status::Failure { code }),
+            }
+        }
+    }
+}
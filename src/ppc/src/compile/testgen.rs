@@ -0,0 +1,442 @@
+//! Generates tests and correct answers in parallel.
+//!
+//! Generated tests and answers are cached: cache key is hash of
+//! everything output depends on (build output of program, arguments,
+//! problem config, random seed, test id), so unchanged tests are reused
+//! across compilations.
+use super::sandbox::{self, Sandbox};
+use crate::{cfg::TestGenSpec, command::Command};
+use sha3::Digest;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Result of producing one test
+pub(crate) enum TestOutcome {
+    /// Test (and answer, if requested) was written to tests dir.
+    /// `cached` is true if nothing had to be regenerated.
+    Ok { cached: bool },
+    /// Validator rejected test; contains validator output
+    Invalid(String),
+    /// Testgen or primary solution failed; contains description
+    Failed(String),
+}
+
+/// Appends program output (if any) to failure description
+fn with_output(mut message: String, out: &sandbox::Output) -> String {
+    for output in &[&out.stderr, &out.stdout] {
+        let output = String::from_utf8_lossy(output);
+        if !output.trim().is_empty() {
+            message.push_str(": ");
+            message.push_str(output.trim());
+        }
+    }
+    message
+}
+
+fn hash_parts(parts: &[&[u8]]) -> String {
+    let mut hasher = sha3::Sha3_256::new();
+    for part in parts {
+        // length prefix makes encoding unambiguous
+        hasher.input((part.len() as u64).to_le_bytes());
+        hasher.input(part);
+    }
+    format!("{:x}", hasher.result())
+}
+
+/// Appends names and contents of all files in `dir` (recursively, in sorted order) to `parts`
+fn collect_dir(dir: &Path, parts: &mut Vec<Vec<u8>>) -> std::io::Result<()> {
+    let mut items = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    items.sort_by_key(|item| item.file_name());
+    for item in items {
+        let path = item.path();
+        parts.push(item.file_name().to_string_lossy().into_owned().into_bytes());
+        if item.file_type()?.is_dir() {
+            collect_dir(&path, parts)?;
+            parts.push(b"..".to_vec());
+        } else {
+            parts.push(fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// Hashes command line and build output of program.
+/// Built programs are launched as `<build output dir>/bin`, so directory
+/// of executable is hashed as a whole: launchers of interpreted languages
+/// only refer to actual code in it.
+fn hash_command(cmd: &Command) -> String {
+    let mut parts = vec![cmd.to_string_pretty().into_bytes()];
+    let exe = Path::new(cmd.exe());
+    let res = match exe.parent() {
+        Some(build_dir) if build_dir != Path::new("") => collect_dir(build_dir, &mut parts),
+        _ => fs::read(exe).map(|exe| parts.push(exe)),
+    };
+    if let Err(err) = res {
+        eprintln!("error: couldn't read build output of {}: {}", cmd, err);
+        exit(1);
+    }
+    let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+    hash_parts(&parts)
+}
+
+/// Stores files in `<dir>/<key>`.
+///
+/// Entries, which were not used during compilation, are removed by `evict_unused`,
+/// so cache does not grow beyond size of tests of last compilation.
+pub(crate) struct Cache {
+    dir: PathBuf,
+    /// Keys, which were loaded or stored
+    used: Mutex<HashSet<String>>,
+    /// Makes names of temporary files unique between threads
+    tmp_counter: AtomicUsize,
+}
+
+impl Cache {
+    pub(crate) fn new(dir: PathBuf) -> std::io::Result<Cache> {
+        fs::create_dir_all(&dir)?;
+        Ok(Cache {
+            dir,
+            used: Mutex::new(HashSet::new()),
+            tmp_counter: AtomicUsize::new(0),
+        })
+    }
+
+    fn mark_used(&self, key: &str) {
+        self.used.lock().unwrap().insert(key.to_string());
+    }
+
+    /// Copies cached file to `dest`. Returns false if there is no such entry.
+    fn load(&self, key: &str, dest: &Path) -> bool {
+        let path = self.dir.join(key);
+        if path.exists() && fs::copy(&path, dest).is_ok() {
+            self.mark_used(key);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes all entries (and leftover temporary files), which were not used
+    fn evict_unused(&self) {
+        let used = self.used.lock().unwrap();
+        let items = match fs::read_dir(&self.dir) {
+            Ok(items) => items,
+            Err(err) => {
+                eprintln!("warning: couldn't clean up cache: {}", err);
+                return;
+            }
+        };
+        for item in items.filter_map(Result::ok) {
+            if !used.contains(item.file_name().to_string_lossy().as_ref()) {
+                fs::remove_file(item.path()).ok();
+            }
+        }
+    }
+
+    fn store(&self, key: &str, src: &Path) {
+        // write to temporary file first, so that concurrent
+        // or interrupted compilations never observe partial entry
+        let tmp_path = self.dir.join(format!(
+            "{}.tmp-{}-{}",
+            key,
+            std::process::id(),
+            self.tmp_counter.fetch_add(1, Ordering::SeqCst)
+        ));
+        let res = fs::copy(src, &tmp_path).and_then(|_| fs::rename(&tmp_path, self.dir.join(key)));
+        if let Err(err) = res {
+            eprintln!(
+                "warning: couldn't store {} in cache: {}",
+                src.display(),
+                err
+            );
+            fs::remove_file(&tmp_path).ok();
+            return;
+        }
+        self.mark_used(key);
+    }
+}
+
+pub(crate) struct TestGenerator {
+    pub(crate) testgens: HashMap<String, Command>,
+    /// Primary solution, if correct answers should be generated.
+    /// Tests with answer file use it instead.
+    pub(crate) gen_answers: Option<Command>,
+    pub(crate) validator: Option<Command>,
+    pub(crate) problem_dir: PathBuf,
+    pub(crate) out_dir: PathBuf,
+    pub(crate) random_seed: String,
+    /// Contents of `problem.toml`; they are part of cache keys
+    pub(crate) problem_cfg: Vec<u8>,
+    pub(crate) cache: Option<Cache>,
+    pub(crate) sandbox: Sandbox,
+}
+
+/// Hashes of testgens, primary solution and problem config, computed once
+struct Hashes {
+    testgens: HashMap<String, String>,
+    gen_answers: String,
+    problem_cfg: String,
+}
+
+impl TestGenerator {
+    fn tests_dir(&self) -> PathBuf {
+        self.out_dir.join("assets/tests")
+    }
+
+    fn configure_command(&self, cmd: &mut Command) {
+        cmd.current_dir(&self.problem_dir);
+        cmd.env("JJS_PROBLEM_SRC", &self.problem_dir);
+        cmd.env("JJS_PROBLEM_DEST", &self.out_dir);
+    }
+
+    /// Random seed for test: depends only on problem random seed and test id,
+    /// so that generation is reproducible
+    fn test_seed(&self, tid: usize) -> String {
+        hash_parts(&[self.random_seed.as_bytes(), tid.to_string().as_bytes()])
+    }
+
+    /// Generates all tests using `jobs` threads.
+    /// Returned vector contains outcome for each test, in order.
+    pub(crate) fn run(self, tests: Vec<TestGenSpec>, jobs: usize) -> Vec<TestOutcome> {
+        if let Err(err) = fs::create_dir_all(self.tests_dir()) {
+            eprintln!("error: couldn't create tests output dir: {}", err);
+            exit(1);
+        }
+        let hashes = Hashes {
+            testgens: self
+                .testgens
+                .iter()
+                .map(|(name, cmd)| (name.clone(), hash_command(cmd)))
+                .collect(),
+            gen_answers: self
+                .gen_answers
+                .as_ref()
+                .map(hash_command)
+                .unwrap_or_default(),
+            problem_cfg: hash_parts(&[&self.problem_cfg]),
+        };
+        let test_count = tests.len();
+        let shared = Arc::new((self, hashes, tests, AtomicUsize::new(0)));
+        let workers: Vec<_> = (0..jobs.max(1).min(test_count.max(1)))
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let (this, hashes, tests, next_test) = &*shared;
+                    let mut outcomes = Vec::new();
+                    loop {
+                        let i = next_test.fetch_add(1, Ordering::SeqCst);
+                        if i >= tests.len() {
+                            break outcomes;
+                        }
+                        outcomes.push((i, this.build_test(hashes, i + 1, &tests[i])));
+                    }
+                })
+            })
+            .collect();
+        let mut outcomes: Vec<Option<TestOutcome>> = (0..test_count).map(|_| None).collect();
+        for worker in workers {
+            let worker_outcomes = worker.join().unwrap_or_else(|_| {
+                eprintln!("error: test generation thread panicked");
+                exit(1);
+            });
+            for (i, outcome) in worker_outcomes {
+                outcomes[i] = Some(outcome);
+            }
+        }
+        if let Some(cache) = &shared.0.cache {
+            cache.evict_unused();
+        }
+        outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("test was not generated"))
+            .collect()
+    }
+
+    fn build_test(&self, hashes: &Hashes, tid: usize, spec: &TestGenSpec) -> TestOutcome {
+        match self.try_build_test(hashes, tid, spec) {
+            Ok(outcome) => outcome,
+            Err(message) => TestOutcome::Failed(message),
+        }
+    }
+
+    fn try_build_test(
+        &self,
+        hashes: &Hashes,
+        tid: usize,
+        spec: &TestGenSpec,
+    ) -> Result<TestOutcome, String> {
+        let input_path = self.tests_dir().join(format!("{}-in.txt", tid));
+        let mut cached = match spec {
+            TestGenSpec::Generate { testgen, args } => {
+                let testgen_cmd = self.testgens.get(testgen).unwrap_or_else(|| {
+                    eprintln!("error: unknown testgen {}", testgen);
+                    exit(1);
+                });
+                let seed = self.test_seed(tid);
+                let key = hash_parts(&[
+                    b"test",
+                    hashes.testgens[testgen].as_bytes(),
+                    hashes.problem_cfg.as_bytes(),
+                    args.join("\0").as_bytes(),
+                    seed.as_bytes(),
+                    tid.to_string().as_bytes(),
+                ]);
+                self.cached_or(&key, &input_path, || {
+                    self.generate_test(testgen_cmd, args, tid, &seed, &input_path)
+                        .map_err(|err| format!("testgen {} {}", testgen, err))
+                })?
+            }
+            TestGenSpec::File { path, .. } => {
+                self.copy_test_file(path, &input_path)?;
+                true
+            }
+        };
+        if let Some(validator) = &self.validator {
+            if let Err(message) = self.validate_test(validator, tid, &input_path) {
+                // answer can not be generated for invalid test
+                return Ok(TestOutcome::Invalid(message));
+            }
+        }
+        let answer_path = self.tests_dir().join(format!("{}-out.txt", tid));
+        if let TestGenSpec::File {
+            answer: Some(answer),
+            ..
+        } = spec
+        {
+            self.copy_test_file(answer, &answer_path)?;
+        } else if let Some(solution) = &self.gen_answers {
+            let test_data = fs::read(&input_path).expect("couldn't read test");
+            let key = hash_parts(&[
+                b"answer",
+                hashes.gen_answers.as_bytes(),
+                hashes.problem_cfg.as_bytes(),
+                &test_data,
+            ]);
+            cached &= self.cached_or(&key, &answer_path, || {
+                self.generate_answer(solution, &input_path, &answer_path)
+                    .map_err(|err| format!("primary solution {}", err))
+            })?;
+        }
+        Ok(TestOutcome::Ok { cached })
+    }
+
+    /// Copies file from problem tests dir
+    fn copy_test_file(&self, path: &str, dest: &Path) -> Result<(), String> {
+        let src_path = self.problem_dir.join("tests").join(path);
+        fs::copy(&src_path, dest).map(drop).map_err(|err| {
+            format!(
+                "couldn't copy test data from {} to {}: {}",
+                src_path.display(),
+                dest.display(),
+                err
+            )
+        })
+    }
+
+    /// Loads `dest` from cache, or produces it with `produce` and stores in cache.
+    /// Returns true if file was found in cache.
+    fn cached_or(
+        &self,
+        key: &str,
+        dest: &Path,
+        produce: impl FnOnce() -> Result<(), String>,
+    ) -> Result<bool, String> {
+        if let Some(cache) = &self.cache {
+            if cache.load(key, dest) {
+                return Ok(true);
+            }
+        }
+        produce()?;
+        if let Some(cache) = &self.cache {
+            cache.store(key, dest);
+        }
+        Ok(false)
+    }
+
+    /// Runs command and describes failure, if any
+    fn launch(&self, cmd: &Command, stdio: sandbox::Stdio) -> Result<sandbox::Output, String> {
+        let out = self.sandbox.run(cmd, stdio);
+        if out.success() {
+            Ok(out)
+        } else {
+            Err(with_output(self.sandbox.describe(&out.status), &out))
+        }
+    }
+
+    fn generate_test(
+        &self,
+        testgen: &Command,
+        args: &[String],
+        tid: usize,
+        seed: &str,
+        out_path: &Path,
+    ) -> Result<(), String> {
+        let mut cmd = testgen.clone();
+        for a in args {
+            cmd.arg(a);
+        }
+        cmd.env("JJS_TEST_ID", tid.to_string());
+        cmd.env("JJS_RANDOM_SEED", seed);
+        self.configure_command(&mut cmd);
+        let out_file = fs::File::create(out_path).expect("couldn't create test output file");
+        if self.sandbox.stdio_only() {
+            // sandbox only passes standard streams, so test is written to stdout
+            cmd.env("JJS_TEST", "1");
+            let stdio = sandbox::Stdio {
+                stdin: None,
+                stdout: Some(out_file),
+            };
+            self.launch(&cmd, stdio).map(drop)
+        } else {
+            let out_file_handle = crate::inherit_handle(out_file);
+            cmd.env("JJS_TEST", out_file_handle.to_string());
+            let res = self.launch(&cmd, sandbox::Stdio::default()).map(drop);
+            unsafe {
+                libc::close(out_file_handle as i32);
+            }
+            res
+        }
+    }
+
+    fn generate_answer(
+        &self,
+        solution: &Command,
+        test_path: &Path,
+        answer_path: &Path,
+    ) -> Result<(), String> {
+        let stdio = sandbox::Stdio {
+            stdin: Some(fs::File::open(test_path).expect("couldn't open test")),
+            stdout: Some(fs::File::create(answer_path).expect("couldn't create answer file")),
+        };
+        let mut cmd = solution.clone();
+        self.configure_command(&mut cmd);
+        self.launch(&cmd, stdio).map(drop)
+    }
+
+    /// Runs validator on test. On failure, returns validator output.
+    fn validate_test(
+        &self,
+        validator: &Command,
+        tid: usize,
+        test_path: &Path,
+    ) -> Result<(), String> {
+        let stdio = sandbox::Stdio {
+            stdin: Some(fs::File::open(test_path).expect("couldn't open test")),
+            stdout: None,
+        };
+        let mut cmd = validator.clone();
+        cmd.env("JJS_TEST_ID", tid.to_string());
+        self.configure_command(&mut cmd);
+        self.launch(&cmd, stdio)
+            .map(drop)
+            .map_err(|err| format!("validator {}", err))
+    }
+}
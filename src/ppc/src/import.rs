@@ -1,16 +1,165 @@
+mod kattis;
 mod template;
 
+use crate::cfg::{RawGroup, RawLimits};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
 
+/// Format of imported package
+#[derive(Debug, Copy, Clone)]
+pub enum Format {
+    Polygon,
+    /// Kattis (also known as ICPC) problem package format
+    Kattis,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "polygon" => Ok(Format::Polygon),
+            "kattis" | "icpc" => Ok(Format::Kattis),
+            _ => Err(format!("unknown format {}, expected polygon or kattis", s)),
+        }
+    }
+}
+
+/// Summary of import, printed after it is finished
+#[derive(Default)]
+struct Report {
+    /// Everything that was imported
+    imported: Vec<String>,
+    /// Everything that could not be converted
+    skipped: Vec<String>,
+}
+
+impl Report {
+    fn print(&self) {
+        println!("Import summary");
+        for item in &self.imported {
+            println!("  imported: {}", item);
+        }
+        if self.skipped.is_empty() {
+            println!("Everything was converted");
+        } else {
+            println!("Following could not be converted:");
+            for item in &self.skipped {
+                println!("  - {}", item);
+            }
+        }
+    }
+}
+
+/// Test group, as described in `<testset><groups>`
+struct PolygonGroup {
+    name: String,
+    points: Option<f64>,
+    /// If true, points are given for each passed test (`points-policy="each-test"`),
+    /// otherwise only for the whole group
+    each_test: bool,
+    feedback: pom::FeedbackPolicy,
+    dependencies: Vec<String>,
+}
+
+/// Test attributes, relevant for scoring
+struct PolygonTest {
+    group: Option<String>,
+    points: f64,
+}
+
 struct Importer<'a> {
     src: &'a Path,
     dest: &'a Path,
     problem_cfg: crate::cfg::RawProblem,
     known_generators: HashSet<String>,
     doc: roxmltree::Node<'a, 'a>,
+    report: Report,
+    tests: Vec<PolygonTest>,
+    groups: Vec<PolygonGroup>,
+}
+
+fn generate_random_seed() -> String {
+    let mut random_seed = [0; 32];
+    getrandom::getrandom(&mut random_seed).unwrap();
+    hex::encode(&random_seed)
+}
+
+/// Maps Polygon solution tag to expected verdict
+fn tag_to_verdict(tag: &str) -> Option<&'static str> {
+    match tag {
+        "accepted" => Some("ok"),
+        "wrong-answer" => Some("wa"),
+        "presentation-error" => Some("pe"),
+        "time-limit-exceeded" => Some("tl"),
+        _ => None,
+    }
+}
+
+fn parse_feedback_policy(policy: &str) -> Option<pom::FeedbackPolicy> {
+    match policy {
+        "complete" => Some(pom::FeedbackPolicy::Complete),
+        "icpc" => Some(pom::FeedbackPolicy::Icpc),
+        "points" | "none" => Some(pom::FeedbackPolicy::None),
+        _ => None,
+    }
+}
+
+/// Returns attribute, which must be present in problem.xml
+fn required_attribute<'a>(node: roxmltree::Node<'a, 'a>, name: &str) -> Result<&'a str, String> {
+    node.attribute(name).ok_or_else(|| {
+        format!(
+            "<{}>: attribute {} is missing",
+            node.tag_name().name(),
+            name
+        )
+    })
+}
+
+/// Parses attribute, if it is present
+fn parse_attribute<T>(node: roxmltree::Node, name: &str) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match node.attribute(name) {
+        Some(value) => value.parse().map(Some).map_err(|err| {
+            format!(
+                "<{}>: invalid {} {:?}: {}",
+                node.tag_name().name(),
+                name,
+                value,
+                err
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Parses text of element, e.g. `<time-limit>1000</time-limit>`
+fn parse_text<T>(node: roxmltree::Node) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let text = node.text().unwrap_or("").trim();
+    text.parse().map_err(|err| {
+        format!(
+            "<{}>: invalid value {:?}: {}",
+            node.tag_name().name(),
+            text,
+            err
+        )
+    })
+}
+
+/// Interpolates test id into path pattern, e.g. `tests/%02d`
+fn format_test_path(pattern: &str, test_id: usize) -> Result<String, String> {
+    let path = formatf::format(pattern.as_bytes(), &[formatf::Value::Int(test_id as i128)])
+        .map_err(|err| format!("formatting error: {:?}", err))?;
+    String::from_utf8(path).map_err(|_| "interpolation provided non-utf8 data".to_string())
 }
 
 enum FileCategory {
@@ -40,69 +189,120 @@ impl FileCategory {
 impl<'a> Importer<'a> {
     // <problem><judging> is most important section for us: it contains information
     // about tests
-    fn process_judging_section(&mut self, node_judging: roxmltree::Node) {
+    fn process_judging_section(
+        &mut self,
+        node_judging: roxmltree::Node<'a, 'a>,
+    ) -> Result<(), String> {
         println!("Processing <judging> section");
-        let node_testset = node_judging.first_element_child().unwrap();
-        assert_eq!(node_testset.tag_name().name(), "testset");
+        for attr in &["input-file", "output-file"] {
+            if let Some(file) = node_judging
+                .attribute(*attr)
+                .filter(|file| !file.is_empty())
+            {
+                self.skip(format!(
+                    "{} {}: only standard input and output are supported",
+                    attr, file
+                ));
+            }
+        }
+        let mut testsets = node_judging.children().filter(|node| node.is_element());
+        let node_testset = testsets
+            .next()
+            .filter(|node| node.tag_name().name() == "testset")
+            .ok_or_else(|| "<judging>: <testset> is missing".to_string())?;
+        for other in testsets {
+            self.skip(format!(
+                "testset {}: only first testset is imported",
+                other.attribute("name").unwrap_or("<unnamed>")
+            ));
+        }
 
-        let mut memory_limit = None;
-        let mut time_limit = None;
         let mut test_pattern = None;
-        let mut ans_pattern = None;
-        let mut test_count = None;
+        let mut tests_node = None;
         for child in node_testset.children() {
             if !child.is_element() {
                 continue;
             }
             match child.tag_name().name() {
                 "time-limit" => {
-                    let tl = child
-                        .text()
-                        .unwrap()
-                        .parse::<u32>()
-                        .expect("parsing <time-limit>:");
+                    let tl: u64 = parse_text(child)?;
                     println!("time limit: {} ms", tl);
-                    time_limit.replace(tl);
+                    self.problem_cfg.limits.time.replace(tl);
                 }
                 "memory-limit" => {
-                    let ml = child
-                        .text()
-                        .unwrap()
-                        .parse::<u32>()
-                        .expect("parsing <memory-limit>:");
-                    println!("memory limit: {} bytes ({} MiBs)", ml, ml / (1 << 20));
-                    memory_limit.replace(ml);
+                    const MIB: u64 = 1 << 20;
+                    let ml: u64 = parse_text(child)?;
+                    // rounded up, so that limit is never lowered
+                    let ml_mib = (ml + MIB - 1) / MIB;
+                    println!("memory limit: {} bytes ({} MiBs)", ml, ml_mib);
+                    self.problem_cfg.limits.memory.replace(ml_mib);
                 }
                 "input-path-pattern" => {
-                    let pat = child.text().unwrap().to_string();
+                    let pat = parse_text::<String>(child)?;
                     println!("test input file path pattern: {}", &pat);
                     test_pattern.replace(pat);
                 }
                 "answer-path-pattern" => {
-                    let pat = child.text().unwrap().to_string();
-                    println!("test output file path pattern: {}", &pat);
-                    ans_pattern.replace(pat);
+                    // answers are generated by primary solution
                 }
                 "test-count" => {
-                    let cnt = child
-                        .text()
-                        .unwrap()
-                        .parse::<u32>()
-                        .expect("parsing <test-count>:");
+                    let cnt: u32 = parse_text(child)?;
                     println!("test count: {}", cnt);
-                    test_count.replace(cnt);
                 }
                 "tests" => {
-                    self.process_tests(child);
+                    tests_node.replace(child);
                 }
-                _ => {
-                    eprintln!(
-                        "warning: unexpected tag in <problem><judging><testset>: {}",
-                        child.tag_name().name()
-                    );
+                "groups" => {
+                    self.process_groups(child)?;
+                }
+                other => {
+                    self.skip(format!("<testset><{}>: unknown tag", other));
                 }
             }
         }
+        if let Some(tests_node) = tests_node {
+            let test_pattern = test_pattern.unwrap_or_else(|| "tests/%02d".to_string());
+            self.process_tests(tests_node, &test_pattern)?;
+        }
+        Ok(())
+    }
+
+    fn process_groups(&mut self, node_groups: roxmltree::Node<'a, 'a>) -> Result<(), String> {
+        for node_group in node_groups.children() {
+            if !node_group.is_element() {
+                continue;
+            }
+            let name = required_attribute(node_group, "name")?.to_string();
+            let feedback = node_group
+                .attribute("feedback-policy")
+                .unwrap_or("complete");
+            let feedback = parse_feedback_policy(feedback).unwrap_or_else(|| {
+                self.skip(format!(
+                    "group {}: unknown feedback policy {}, complete is used",
+                    name, feedback
+                ));
+                pom::FeedbackPolicy::Complete
+            });
+            let dependencies = node_group
+                .descendants()
+                .filter(|node| node.tag_name().name() == "dependency")
+                .filter_map(|node| node.attribute("group"))
+                .map(ToOwned::to_owned)
+                .collect();
+            self.groups.push(PolygonGroup {
+                points: parse_attribute(node_group, "points")?,
+                each_test: node_group.attribute("points-policy") == Some("each-test"),
+                name,
+                feedback,
+                dependencies,
+            });
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, message: String) {
+        eprintln!("warning: {}", message);
+        self.report.skipped.push(message);
     }
 
     fn import_file(&mut self, src_path: impl AsRef<Path>, dest_path: impl AsRef<Path>) {
@@ -124,14 +324,17 @@ impl<'a> Importer<'a> {
     fn process_file(&mut self, file_path: &str, file_type: &str) {
         println!("processing {} of type {}", file_path, file_type);
         if !file_path.starts_with("files/") {
-            eprintln!("file doesn't start from 'files/'.");
+            self.skip(format!("{}: file doesn't start from 'files/'", file_path));
             return;
         }
         let file_name = file_path.trim_start_matches("files/");
         let period_pos = match file_name.find('.') {
             Some(p) => p,
             None => {
-                eprintln!("file path does not contain extension");
+                self.skip(format!(
+                    "{}: file path does not contain extension",
+                    file_path
+                ));
                 return;
             }
         };
@@ -142,19 +345,16 @@ impl<'a> Importer<'a> {
                 if self.known_generators.contains(file_name) {
                     FileCategory::Generator
                 } else {
-                    eprintln!(
-                        "couldn't derive file category (stripped name: {}).",
-                        file_name
-                    );
+                    self.skip(format!(
+                        "{}: couldn't derive file category (stripped name: {})",
+                        file_path, file_name
+                    ));
                     return;
                 }
             }
         };
         match category {
-            FileCategory::Validator => {
-                // TODO
-            }
-            FileCategory::Checker => {
+            FileCategory::Validator | FileCategory::Checker => {
                 // do nothing here, processed separately
             }
             FileCategory::Generator => {
@@ -175,13 +375,22 @@ impl<'a> Importer<'a> {
                 // currently, CMakeLists are same with generator
                 let cmakedata = template::get_checker_cmakefile(template::CheckerOptions {});
                 std::fs::write(cmakefile, cmakedata).expect("write generator's CMakeLists.txt");
+                self.report
+                    .imported
+                    .push(format!("generator {}", file_name));
             }
         }
     }
 
-    fn process_checker(&mut self, node_checker: roxmltree::Node) {
+    fn process_checker(&mut self, node_checker: roxmltree::Node<'a, 'a>) -> Result<(), String> {
         println!("Importing checker");
-        assert_eq!(node_checker.attribute("type"), Some("testlib"));
+        if node_checker.attribute("type") != Some("testlib") {
+            self.skip(format!(
+                "checker of type {}: only testlib checkers are supported",
+                node_checker.attribute("type").unwrap_or("<unknown>")
+            ));
+            return Ok(());
+        }
         for child in node_checker.children() {
             if !child.is_element() {
                 continue;
@@ -189,12 +398,93 @@ impl<'a> Importer<'a> {
             if child.tag_name().name() != "source" {
                 continue;
             }
-            let file_path = child.attribute("path").unwrap();
+            let file_path = required_attribute(child, "path")?;
             self.import_file(Path::new(file_path), Path::new("modules/checker/main.cpp"));
             let cmakefile = self.dest.join("modules/checker/CMakeLists.txt");
             let cmakedata = template::get_checker_cmakefile(template::CheckerOptions {});
             std::fs::write(cmakefile, cmakedata).expect("write checker's CMakeLists.txt");
+            self.report.imported.push("checker".to_string());
         }
+        Ok(())
+    }
+
+    fn process_validator(&mut self, node_validator: roxmltree::Node<'a, 'a>) -> Result<(), String> {
+        println!("Importing validator");
+        for child in node_validator.children() {
+            if !child.is_element() {
+                continue;
+            }
+            if child.tag_name().name() != "source" {
+                continue;
+            }
+            let file_path = required_attribute(child, "path")?;
+            if self.problem_cfg.validator.is_some() {
+                self.skip(format!(
+                    "validator {}: only one validator is supported",
+                    file_path
+                ));
+                continue;
+            }
+            std::fs::create_dir_all(self.dest.join("validators")).expect("create validators dir");
+            // validator is built as single file, so testlib is placed next to it
+            self.import_file(Path::new(file_path), Path::new("validators/main.cpp"));
+            self.import_file(
+                Path::new("files/testlib.h"),
+                Path::new("validators/testlib.h"),
+            );
+            self.problem_cfg.validator = Some("validators/main.cpp".to_string());
+            self.report.imported.push("validator".to_string());
+        }
+        Ok(())
+    }
+
+    fn process_interactor(
+        &mut self,
+        node_interactor: roxmltree::Node<'a, 'a>,
+    ) -> Result<(), String> {
+        println!("Importing interactor");
+        let node_source = node_interactor
+            .children()
+            .find(|child| child.tag_name().name() == "source");
+        if let Some(node_source) = node_source {
+            let file_path = required_attribute(node_source, "path")?;
+            std::fs::create_dir_all(self.dest.join("interactors/main"))
+                .expect("create interactor dir");
+            self.import_file(Path::new(file_path), Path::new("interactors/main/main.cpp"));
+            let cmakefile = self.dest.join("interactors/main/CMakeLists.txt");
+            // interactors are built same way as checkers
+            let cmakedata = template::get_checker_cmakefile(template::CheckerOptions {});
+            std::fs::write(cmakefile, cmakedata).expect("write interactor's CMakeLists.txt");
+            self.problem_cfg.interactor = Some("interactors/main".to_string());
+            self.report.imported.push("interactor".to_string());
+            self.skip("interactor: interactive problems are not supported by invoker yet; it is only built".to_string());
+        }
+        Ok(())
+    }
+
+    fn process_statements(
+        &mut self,
+        node_statements: roxmltree::Node<'a, 'a>,
+    ) -> Result<(), String> {
+        println!("Importing statements");
+        for node_statement in node_statements.children() {
+            if !node_statement.is_element() {
+                continue;
+            }
+            let file_path = required_attribute(node_statement, "path")?;
+            let language = node_statement.attribute("language").unwrap_or("unknown");
+            let file_name = match Path::new(file_path).file_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let dest_dir = Path::new("statements").join(language);
+            std::fs::create_dir_all(self.dest.join(&dest_dir)).expect("create statements dir");
+            self.import_file(Path::new(file_path), dest_dir.join(file_name));
+            self.report
+                .imported
+                .push(format!("statement {}", dest_dir.join(file_name).display()));
+        }
+        Ok(())
     }
 
     fn produce_generator_shim(&mut self) {
@@ -210,73 +500,229 @@ impl<'a> Importer<'a> {
         }
     }
 
-    fn process_executable(&mut self, node_executable: roxmltree::Node) {
+    fn process_executable(
+        &mut self,
+        node_executable: roxmltree::Node<'a, 'a>,
+    ) -> Result<(), String> {
         for node_source in node_executable.children() {
             if node_source.tag_name().name() != "source" {
                 continue;
             }
-            let attr_path = node_source.attribute("path").unwrap();
-            let attr_type = node_source.attribute("type").unwrap();
+            let attr_path = required_attribute(node_source, "path")?;
+            let attr_type = required_attribute(node_source, "type")?;
             self.process_file(attr_path, attr_type);
         }
+        Ok(())
     }
 
-    fn process_tests(&mut self, tests_node: roxmltree::Node) {
+    fn process_tests(
+        &mut self,
+        tests_node: roxmltree::Node<'a, 'a>,
+        test_pattern: &str,
+    ) -> Result<(), String> {
         println!("Importing tests");
-        assert_eq!(tests_node.tag_name().name(), "tests");
         let mut cnt = 0;
         for test_node in tests_node.children() {
             if !test_node.is_element() {
                 continue;
             }
-            assert_eq!(test_node.tag_name().name(), "test");
+            if test_node.tag_name().name() != "test" {
+                return Err(format!(
+                    "<tests>: unexpected <{}>",
+                    test_node.tag_name().name()
+                ));
+            }
             cnt += 1;
             let mut ts = crate::cfg::RawTestsSpec {
                 map: cnt.to_string(),
                 testgen: None,
                 files: None,
+                answers: None,
             };
-            let is_generated = test_node.attribute("method").unwrap() == "generated";
+            let is_generated = required_attribute(test_node, "method")? == "generated";
             if is_generated {
-                let cmd_iter = test_node.attribute("cmd").unwrap().split_whitespace();
+                let cmd_iter = required_attribute(test_node, "cmd")?.split_whitespace();
                 let mut testgen_cmd = cmd_iter.map(ToOwned::to_owned).collect::<Vec<_>>();
-                let gen_name = testgen_cmd[0].clone();
+                let gen_name = match testgen_cmd.first() {
+                    Some(name) => name.clone(),
+                    None => return Err(format!("test {}: generator command is empty", cnt)),
+                };
                 self.known_generators.insert(gen_name);
                 testgen_cmd.insert(0, "shim".to_string());
                 ts.testgen = Some(testgen_cmd);
             } else {
-                // TODO: use formatf here instead of hardcoded format strings
-                ts.files = Some("%02d.txt".to_string());
-                let src_path = format!("tests/{:0>2}", cnt);
-                let dest_path = format!("tests/{:0>2}.txt", cnt);
-                self.import_file(&src_path, &dest_path);
+                const DEST_PATTERN: &str = "%02d.txt";
+                ts.files = Some(DEST_PATTERN.to_string());
+                let paths = format_test_path(test_pattern, cnt).and_then(|src_path| {
+                    let dest_path = format_test_path(DEST_PATTERN, cnt)?;
+                    Ok((src_path, Path::new("tests").join(dest_path)))
+                });
+                match paths {
+                    Ok((src_path, dest_path)) => self.import_file(&src_path, &dest_path),
+                    Err(err) => self.skip(format!("test {}: {}", cnt, err)),
+                }
             }
+            let points = parse_attribute(test_node, "points")?.unwrap_or(0.0);
+            self.tests.push(PolygonTest {
+                group: test_node.attribute("group").map(ToOwned::to_owned),
+                points,
+            });
             self.problem_cfg.tests.push(ts);
         }
         println!("{} tests imported", cnt);
+        self.report.imported.push(format!("{} tests", cnt));
+        Ok(())
     }
 
-    fn process_solutions(&mut self, node: roxmltree::Node) {
-        println!("Importing solution");
+    /// Converts Polygon groups and points into `[[groups]]`.
+    /// Must be called after tests and groups are processed.
+    fn convert_groups(&mut self) {
+        let has_points = self.tests.iter().any(|test| test.points != 0.0)
+            || self
+                .groups
+                .iter()
+                .any(|g| g.points.map_or(false, |p| p != 0.0));
+        let has_groups = self.tests.iter().any(|test| test.group.is_some());
+        if !has_points && !has_groups {
+            return;
+        }
+        // groups can be used in tests without being described in <groups>
+        for i in 0..self.tests.len() {
+            if let Some(name) = &self.tests[i].group {
+                if self.groups.iter().all(|g| &g.name != name) {
+                    let name = name.clone();
+                    self.skip(format!(
+                        "group {} has no settings, points are given for each passed test",
+                        name
+                    ));
+                    self.groups.push(PolygonGroup {
+                        name,
+                        points: None,
+                        each_test: true,
+                        feedback: pom::FeedbackPolicy::Complete,
+                        dependencies: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        let mut raw_groups = Vec::new();
+        let mut scores = Vec::new();
+        // maps Polygon group name to names of groups it was converted to
+        let mut converted: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut skipped = Vec::new();
+        for group in &self.groups {
+            let test_ids: Vec<usize> = (0..self.tests.len())
+                .filter(|&i| self.tests[i].group.as_ref() == Some(&group.name))
+                .map(|i| i + 1)
+                .collect();
+            if test_ids.is_empty() {
+                skipped.push(format!("group {} has no tests", group.name));
+                continue;
+            }
+            let mut depends_on = Vec::new();
+            for dep in &group.dependencies {
+                match converted.get(dep.as_str()) {
+                    Some(names) => depends_on.extend(names.iter().cloned()),
+                    None => skipped.push(format!(
+                        "group {}: dependency on {} is dropped, because it is not defined earlier",
+                        group.name, dep
+                    )),
+                }
+            }
+            let mut names = Vec::new();
+            if group.each_test {
+                // each test becomes separate group
+                for &tid in &test_ids {
+                    let name = format!("{}-{}", group.name, tid);
+                    scores.push(self.tests[tid - 1].points);
+                    raw_groups.push(RawGroup {
+                        name: name.clone(),
+                        tests: tid.to_string(),
+                        score: 0,
+                        depends_on: depends_on.clone(),
+                        feedback: group.feedback,
+                        limits: RawLimits::default(),
+                    });
+                    names.push(name);
+                }
+            } else {
+                let points = group.points.unwrap_or_else(|| {
+                    test_ids.iter().map(|&tid| self.tests[tid - 1].points).sum()
+                });
+                scores.push(points);
+                let tests: Vec<_> = test_ids.iter().map(ToString::to_string).collect();
+                raw_groups.push(RawGroup {
+                    name: group.name.clone(),
+                    tests: tests.join(","),
+                    score: 0,
+                    depends_on,
+                    feedback: group.feedback,
+                    limits: RawLimits::default(),
+                });
+                names.push(group.name.clone());
+            }
+            converted.insert(&group.name, names);
+        }
+        // every test must belong to some group
+        for (i, test) in self.tests.iter().enumerate() {
+            if test.group.is_none() {
+                scores.push(test.points);
+                raw_groups.push(RawGroup {
+                    name: format!("test-{}", i + 1),
+                    tests: (i + 1).to_string(),
+                    score: 0,
+                    depends_on: Vec::new(),
+                    feedback: pom::FeedbackPolicy::Complete,
+                    limits: RawLimits::default(),
+                });
+            }
+        }
+        for message in skipped {
+            self.skip(message);
+        }
+
+        if scores
+            .iter()
+            .any(|score| score.fract() != 0.0 || *score < 0.0)
+        {
+            self.skip("groups: fractional points are not supported".to_string());
+            return;
+        }
+        let total: f64 = scores.iter().sum();
+        if total != 100.0 {
+            self.skip(format!(
+                "groups: points sum up to {}, but must sum up to 100",
+                total
+            ));
+            return;
+        }
+        for (group, score) in raw_groups.iter_mut().zip(scores) {
+            group.score = score as u32;
+        }
+        self.report
+            .imported
+            .push(format!("{} groups", raw_groups.len()));
+        self.problem_cfg.groups = raw_groups;
+        self.problem_cfg.valuer = "groups".to_string();
+    }
+
+    fn process_solutions(&mut self, node: roxmltree::Node<'a, 'a>) -> Result<(), String> {
+        println!("Importing solutions");
         for solution_node in node.children() {
             if !solution_node.is_element() {
                 continue;
             }
-            let tag = solution_node.attribute("tag").unwrap();
+            let tag = required_attribute(solution_node, "tag")?;
+            let src_path = solution_node
+                .children()
+                .find(|child| child.tag_name().name() == "source")
+                .ok_or_else(|| format!("solution with tag {}: <source> is missing", tag))?;
+            let src_path = required_attribute(src_path, "path")?;
             if tag == "main" {
                 println!("importing main solution");
                 self.problem_cfg.primary_solution = Some("main".to_string());
                 let dir = self.dest.join("solutions/main");
-                let mut src_path = None;
-                for child in solution_node.children() {
-                    if !child.is_element() {
-                        continue;
-                    }
-                    if child.tag_name().name() == "source" {
-                        src_path = Some(child.attribute("path").unwrap());
-                    }
-                }
-                let src_path = src_path.unwrap();
                 std::fs::create_dir_all(&dir).expect("create main solution dir");
                 self.import_file(Path::new(&src_path), Path::new("solutions/main/main.cpp"));
                 {
@@ -284,25 +730,61 @@ impl<'a> Importer<'a> {
                     let data = include_str!("./import/solution.cmake");
                     std::fs::write(&cmake_path, data).expect("write CMakeLists.txt for solution");
                 }
+                self.report.imported.push("solution main".to_string());
+                continue;
+            }
+            let src_path = Path::new(src_path);
+            let name: String = src_path
+                .file_stem()
+                .ok_or_else(|| format!("solution {}: path has no file name", src_path.display()))?
+                .to_string_lossy()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            let name = if name == "main" {
+                "main-2".to_string()
             } else {
-                println!("skipping solution with tag {}: not main", &tag);
+                name
+            };
+            let mut dest_path = Path::new("solutions").join(&name);
+            if let Some(ext) = src_path.extension() {
+                dest_path.set_extension(ext);
+            }
+            self.import_file(src_path, &dest_path);
+            match tag_to_verdict(tag) {
+                Some(verdict) => {
+                    self.problem_cfg
+                        .solutions
+                        .insert(name.clone(), verdict.to_string());
+                    self.report
+                        .imported
+                        .push(format!("solution {} (expected {})", name, verdict));
+                }
+                None => {
+                    self.report.imported.push(format!("solution {}", name));
+                    self.skip(format!(
+                        "solution {}: tag {} can not be expressed as expected verdict",
+                        name, tag
+                    ));
+                }
             }
         }
+        Ok(())
     }
 
-    fn process_names(&mut self, node_names: roxmltree::Node) {
+    fn process_names(&mut self, node_names: roxmltree::Node<'a, 'a>) -> Result<(), String> {
         println!("Importing name");
-        assert!(node_names.is_element());
         for child in node_names.children() {
             if !child.is_element() {
                 continue;
             }
-            let title = child.attribute("value").unwrap();
+            let title = required_attribute(child, "value")?;
 
             self.problem_cfg.title = title.to_string();
             println!("problem title: {}", &title);
-            return;
+            break;
         }
+        Ok(())
     }
 
     fn fill_manifest(&mut self) {
@@ -315,10 +797,7 @@ impl<'a> Importer<'a> {
         m.check_options = Some(crate::cfg::CheckOptions {
             args: vec!["assets/module-checker/bin".to_string()],
         });
-        let mut random_seed = [0; 32];
-        getrandom::getrandom(&mut random_seed).unwrap();
-        let rand_seed_hex = hex::encode(&random_seed);
-        m.random_seed = Some(rand_seed_hex);
+        m.random_seed = Some(generate_random_seed());
     }
 
     fn init_dirs(&mut self) {
@@ -339,29 +818,58 @@ impl<'a> Importer<'a> {
         self.import_file(Path::new("files/testlib.h"), Path::new("testlib.h"));
     }
 
-    fn feed(&mut self, node: roxmltree::Node) {
+    fn feed(&mut self, node: roxmltree::Node<'a, 'a>) -> Result<(), String> {
         match node.tag_name().name() {
             "names" => self.process_names(node),
             "solutions" => self.process_solutions(node),
             "judging" => self.process_judging_section(node),
             "executable" => self.process_executable(node),
             "checker" => self.process_checker(node),
+            "validator" => self.process_validator(node),
+            "interactor" => self.process_interactor(node),
+            "statements" => self.process_statements(node),
             _ => {
                 for ch in node.children() {
-                    self.feed(ch);
+                    self.feed(ch)?;
                 }
+                Ok(())
             }
         }
     }
 
-    fn run(&mut self) {
+    fn run(&mut self) -> Result<(), String> {
+        if let Some(name) = self.doc.attribute("short-name") {
+            self.problem_cfg.name = name.to_string();
+        }
         self.init_dirs();
         self.fill_manifest();
         self.produce_generator_shim();
-        self.feed(self.doc);
+        self.feed(self.doc)?;
+        self.convert_groups();
+        Ok(())
     }
 }
 
+fn import_polygon(src: &Path, dest: &Path) -> Result<(crate::cfg::RawProblem, Report), String> {
+    let manifest_path = src.join("problem.xml");
+    let manifest = std::fs::read_to_string(manifest_path).expect("failed read problem.xml");
+    let doc = roxmltree::Document::parse(&manifest).map_err(|err| err.to_string())?;
+
+    let mut importer = Importer {
+        src,
+        dest,
+        problem_cfg: Default::default(),
+        known_generators: HashSet::new(),
+        doc: doc.root_element(),
+        report: Report::default(),
+        tests: Vec::new(),
+        groups: Vec::new(),
+    };
+
+    importer.run()?;
+    Ok((importer.problem_cfg, importer.report))
+}
+
 pub fn exec(args: crate::args::ImportArgs) {
     if args.force {
         std::fs::remove_dir_all(&args.out_path).expect("remove out dir");
@@ -373,28 +881,22 @@ pub fn exec(args: crate::args::ImportArgs) {
     let src = PathBuf::from(&args.in_path);
     let dest = PathBuf::from(&args.out_path);
 
-    let manifest_path = src.join("problem.xml");
-    let manifest = std::fs::read_to_string(manifest_path).expect("failed read problem.xml");
-    let doc = roxmltree::Document::parse(&manifest).expect("parse error");
-
-    let mut importer = Importer {
-        src: &src,
-        dest: &dest,
-        problem_cfg: Default::default(),
-        known_generators: HashSet::new(),
-        doc: doc.root_element(),
+    let (problem_cfg, report) = match args.format {
+        Format::Polygon => import_polygon(&src, &dest).unwrap_or_else(|err| {
+            eprintln!("error: invalid problem.xml: {}", err);
+            std::process::exit(1);
+        }),
+        Format::Kattis => kattis::import(&src, &dest),
     };
 
-    importer.run();
-
     let manifest_path = dest.join("problem.toml");
-    let manifest_toml =
-        toml::Value::try_from(importer.problem_cfg.clone()).expect("serialize ppc config");
+    let manifest_toml = toml::Value::try_from(problem_cfg.clone()).expect("serialize ppc config");
     let manifest_data = toml::ser::to_string_pretty(&manifest_toml).unwrap_or_else(|err| {
         panic!(
             "stringify ppc config: {}\n\nraw config: {:#?}",
-            err, &importer.problem_cfg
+            err, &problem_cfg
         )
     });
     std::fs::write(manifest_path, manifest_data).expect("write ppc manifest");
+    report.print();
 }
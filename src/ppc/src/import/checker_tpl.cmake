@@ -1,2 +1,6 @@
+cmake_minimum_required(VERSION 3.13.4)
+project(Module CXX)
+set(CMAKE_CXX_STANDARD 17)
+
 add_executable(Out main.cpp)
-target_include_directories(Out PUBLIC ../../)
\ No newline at end of file
+target_include_directories(Out PUBLIC ../../)
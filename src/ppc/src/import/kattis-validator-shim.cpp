@@ -0,0 +1,57 @@
+// this is thin wrapper for real Kattis input validator (imported as module `input-validator`)
+// Kattis validators exit with 42 if test is valid and with 43 otherwise, while
+// JJS expects zero exit code for valid test.
+
+#include <cstdio>
+#include <cstdlib>
+#include <unistd.h>
+#include <wait.h>
+
+static const int EXIT_VALID = 42;
+static const int EXIT_INVALID = 43;
+
+int main(int argc, char** argv) {
+    char real_validator[1024];
+    char* dest_dir_path = getenv("JJS_PROBLEM_DEST");
+    if (dest_dir_path == nullptr) {
+        fprintf(stderr, "error: JJS_PROBLEM_DEST env var is not set");
+        return 1;
+    }
+    int len = snprintf(real_validator, sizeof(real_validator), "%s/assets/module-input-validator/bin", dest_dir_path);
+    if (len < 0 || (size_t) len >= sizeof(real_validator)) {
+        fprintf(stderr, "error: JJS_PROBLEM_DEST is too long");
+        return 1;
+    }
+    argv[0] = real_validator;
+
+    int fres = fork();
+    if (fres == -1) {
+        fprintf(stderr, "error: fork() failed: %m");
+        return 1;
+    }
+    if (fres == 0) {
+        // stdin (test data) is inherited
+        execv(argv[0], argv);
+        fprintf(stderr, "error: execv (path: %s) failed: %m", argv[0]);
+        exit(66);
+    }
+    int wstatus;
+    if (waitpid(fres, &wstatus, 0) == -1) {
+        fprintf(stderr, "error: waitpid() failed: %m");
+        return 1;
+    }
+    if (!WIFEXITED(wstatus)) {
+        fprintf(stderr, "error: input validator didn't terminate normally: %d", wstatus);
+        return 1;
+    }
+    switch (WEXITSTATUS(wstatus)) {
+        case EXIT_VALID:
+            return 0;
+        case EXIT_INVALID:
+            fprintf(stderr, "input validator rejected test");
+            return 1;
+        default:
+            fprintf(stderr, "unexpected return code from input validator: %d", WEXITSTATUS(wstatus));
+            return 1;
+    }
+}
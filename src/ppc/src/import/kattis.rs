@@ -0,0 +1,627 @@
+//! Importer for Kattis problem package format (also used by ICPC).
+//!
+//! Input and output validators are imported as modules. Input validator is
+//! wrapped with shim, translating Kattis exit codes, and output validator is
+//! launched by builtin `kattis-compat` checker.
+use super::Report;
+use crate::cfg::{BuiltinCheck, CheckOptions, RawProblem, RawTestsSpec};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Relevant part of `problem.yaml`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ProblemYaml {
+    /// Either string or map from language to name
+    name: Option<serde_yaml::Value>,
+    #[serde(rename = "type")]
+    problem_type: Option<String>,
+    /// `default` or `custom`, optionally followed by `score` and `interactive`
+    validation: Option<String>,
+    validator_flags: Option<String>,
+    limits: YamlLimits,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct YamlLimits {
+    /// In seconds
+    time_limit: Option<f64>,
+    time_multiplier: Option<f64>,
+    /// In megabytes
+    memory: Option<u64>,
+}
+
+/// Maps submissions subdirectory to expected verdict
+fn category_to_verdict(category: &str) -> Option<&'static str> {
+    match category {
+        "accepted" => Some("ok"),
+        "wrong_answer" => Some("wa"),
+        "time_limit_exceeded" => Some("tl"),
+        "run_time_error" => Some("re"),
+        _ => None,
+    }
+}
+
+/// Extensions of single-file sources, which can be renamed to `main.<ext>`
+/// (java is not here: class name must match file name)
+const RENAMEABLE_EXTENSIONS: &[&str] = &["cpp", "cc", "cxx", "rs", "py"];
+
+/// Names of entry points, recognized by ppc in multi-file sources
+const MAIN_FILES: &[&str] = &["main.cpp", "main.rs", "main.py", "Main.java"];
+
+/// Converts file name to identifier, usable as solution name
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Returns directory entries, sorted by name
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<_> = match fs::read_dir(dir) {
+        Ok(items) => items
+            .map(|item| item.expect("read dir entry").path())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    entries
+}
+
+fn copy_dir(src: &Path, dest: &Path) {
+    fs::create_dir_all(dest).unwrap_or_else(|err| panic!("create {}: {}", dest.display(), err));
+    for item in sorted_entries(src) {
+        let item_dest = dest.join(item.file_name().unwrap());
+        if item.is_dir() {
+            copy_dir(&item, &item_dest);
+        } else {
+            fs::copy(&item, &item_dest).unwrap_or_else(|err| {
+                panic!(
+                    "copy {} to {}: {}",
+                    item.display(),
+                    item_dest.display(),
+                    err
+                )
+            });
+        }
+    }
+}
+
+/// Collects `*.in` files of `dir` and its subdirectories, in lexicographical order
+fn collect_tests(dir: &Path, out: &mut Vec<PathBuf>) {
+    for item in sorted_entries(dir) {
+        if item.is_dir() {
+            collect_tests(&item, out);
+        } else if item.extension().map_or(false, |ext| ext == "in") {
+            out.push(item);
+        }
+    }
+}
+
+struct Importer<'a> {
+    src: &'a Path,
+    dest: &'a Path,
+    problem_cfg: RawProblem,
+    manifest: ProblemYaml,
+    report: Report,
+}
+
+impl<'a> Importer<'a> {
+    fn skip(&mut self, message: String) {
+        eprintln!("warning: {}", message);
+        self.report.skipped.push(message);
+    }
+
+    /// Copies program (single file or directory) to `dest_dir`, so that
+    /// it can be built by ppc. Returns false if entry point can not be determined.
+    fn import_program(&mut self, src_path: &Path, dest_dir: &Path) -> bool {
+        let full_dest_dir = self.dest.join(dest_dir);
+        if src_path.is_dir() {
+            copy_dir(src_path, &full_dest_dir);
+        } else {
+            fs::create_dir_all(&full_dest_dir).expect("create program dir");
+            fs::copy(src_path, full_dest_dir.join(src_path.file_name().unwrap()))
+                .expect("copy program source");
+        }
+        if MAIN_FILES
+            .iter()
+            .any(|name| full_dest_dir.join(name).is_file())
+        {
+            return true;
+        }
+        let sources: Vec<_> = sorted_entries(&full_dest_dir)
+            .into_iter()
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .map_or(false, |ext| RENAMEABLE_EXTENSIONS.contains(&ext))
+            })
+            .collect();
+        if sources.len() != 1 {
+            self.skip(format!(
+                "{}: couldn't determine entry point (expected one of {} or single source file)",
+                src_path.display(),
+                MAIN_FILES.join(", ")
+            ));
+            fs::remove_dir_all(&full_dest_dir).ok();
+            return false;
+        }
+        let ext = sources[0].extension().unwrap().to_str().unwrap();
+        let ext = if ext == "cc" || ext == "cxx" {
+            "cpp"
+        } else {
+            ext
+        };
+        fs::rename(&sources[0], full_dest_dir.join(format!("main.{}", ext)))
+            .expect("rename entry point");
+        true
+    }
+
+    fn process_name(&mut self) {
+        let short_name = self
+            .src
+            .canonicalize()
+            .ok()
+            .and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            })
+            .unwrap_or_default();
+        self.problem_cfg.name = short_name.clone();
+        let title = match &self.manifest.name {
+            Some(serde_yaml::Value::String(name)) => Some(name.clone()),
+            Some(serde_yaml::Value::Mapping(names)) => names
+                .get(&serde_yaml::Value::String("en".to_string()))
+                .or_else(|| names.iter().next().map(|(_, name)| name))
+                .and_then(|name| name.as_str())
+                .map(ToOwned::to_owned),
+            _ => None,
+        };
+        self.problem_cfg.title = title.unwrap_or(short_name);
+        println!("problem title: {}", self.problem_cfg.title);
+    }
+
+    fn process_limits(&mut self) {
+        let time_limit = match self.manifest.limits.time_limit {
+            Some(time_limit) => Some(time_limit),
+            // legacy packages store time limit separately
+            None => fs::read_to_string(self.src.join(".timelimit"))
+                .ok()
+                .and_then(|data| data.trim().parse::<f64>().ok()),
+        };
+        match time_limit {
+            Some(time_limit) => {
+                self.problem_cfg.limits.time = Some((time_limit * 1000.0).round() as u64);
+                self.report
+                    .imported
+                    .push(format!("time limit {} s", time_limit));
+            }
+            None => {
+                let multiplier = self.manifest.limits.time_multiplier.unwrap_or(5.0);
+                self.skip(format!(
+                    "time limit: it is derived from accepted submissions (time multiplier {}), set it in problem.toml manually",
+                    multiplier
+                ));
+            }
+        }
+        if let Some(memory) = self.manifest.limits.memory {
+            self.problem_cfg.limits.memory = Some(memory);
+            self.report
+                .imported
+                .push(format!("memory limit {} MiB", memory));
+        }
+    }
+
+    fn process_statements(&mut self) {
+        println!("Importing statements");
+        let statement_dir = ["problem_statement", "statement"]
+            .iter()
+            .map(|name| self.src.join(name))
+            .find(|path| path.is_dir());
+        let statement_dir = match statement_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        for item in sorted_entries(&statement_dir) {
+            let file_name = item.file_name().unwrap().to_string_lossy().to_string();
+            let parts: Vec<_> = file_name.split('.').collect();
+            // statements are named `problem.<language>.<ext>` or `problem.<ext>`
+            let (language, dest_name) = match parts.as_slice() {
+                ["problem", language, ext] => (*language, format!("problem.{}", ext)),
+                ["problem", ext] => ("en", format!("problem.{}", ext)),
+                _ => {
+                    // images and other files, used by statements
+                    let dest = self.dest.join("statements").join(&file_name);
+                    if item.is_dir() {
+                        copy_dir(&item, &dest);
+                    } else {
+                        fs::create_dir_all(self.dest.join("statements"))
+                            .expect("create statements dir");
+                        fs::copy(&item, &dest).expect("copy statement file");
+                    }
+                    continue;
+                }
+            };
+            let dest_dir = Path::new("statements").join(language);
+            fs::create_dir_all(self.dest.join(&dest_dir)).expect("create statements dir");
+            fs::copy(&item, self.dest.join(&dest_dir).join(&dest_name)).expect("copy statement");
+            self.report
+                .imported
+                .push(format!("statement {}", dest_dir.join(dest_name).display()));
+        }
+    }
+
+    fn process_tests(&mut self) {
+        println!("Importing tests");
+        // samples are judged first
+        let mut tests = Vec::new();
+        collect_tests(&self.src.join("data/sample"), &mut tests);
+        let sample_count = tests.len();
+        collect_tests(&self.src.join("data/secret"), &mut tests);
+        if tests.is_empty() {
+            self.skip("tests: no *.in files found in data/sample and data/secret".to_string());
+            return;
+        }
+        const DEST_PATTERN: &str = "%02d.in";
+        const ANSWER_DEST_PATTERN: &str = "%02d.ans";
+        let missing_answers: Vec<_> = tests
+            .iter()
+            .filter(|test_path| !test_path.with_extension("ans").is_file())
+            .collect();
+        let has_answers = missing_answers.is_empty();
+        if !has_answers {
+            self.skip(format!(
+                "answers (*.ans): {} is missing, so correct answers are generated by primary solution instead",
+                missing_answers[0].with_extension("ans").display()
+            ));
+        }
+        fs::create_dir_all(self.dest.join("tests")).expect("create tests dir");
+        for (i, test_path) in tests.iter().enumerate() {
+            let mut files = vec![(test_path.clone(), DEST_PATTERN)];
+            if has_answers {
+                files.push((test_path.with_extension("ans"), ANSWER_DEST_PATTERN));
+            }
+            for (src_path, pattern) in files {
+                let dest_name = super::format_test_path(pattern, i + 1).unwrap();
+                fs::copy(&src_path, self.dest.join("tests").join(dest_name)).unwrap_or_else(
+                    |err| {
+                        panic!("copy test {}: {}", src_path.display(), err);
+                    },
+                );
+            }
+        }
+        self.problem_cfg.tests.push(RawTestsSpec {
+            map: format!("1..{}", tests.len()),
+            testgen: None,
+            files: Some(DEST_PATTERN.to_string()),
+            answers: if has_answers {
+                Some(ANSWER_DEST_PATTERN.to_string())
+            } else {
+                None
+            },
+        });
+        self.report.imported.push(format!(
+            "{} tests ({} samples){}",
+            tests.len(),
+            sample_count,
+            if has_answers { " with answers" } else { "" }
+        ));
+        if sorted_entries(&self.src.join("data/secret"))
+            .iter()
+            .any(|path| path.is_dir())
+        {
+            self.skip("test groups: subdirectories of data/secret are merged".to_string());
+        }
+    }
+
+    /// Converts flags of Kattis default output validator to `cmp-tokens` arguments
+    fn convert_default_validator_flags(&mut self, flags: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut case_sensitive = false;
+        let mut flags = flags.split_whitespace();
+        while let Some(flag) = flags.next() {
+            match flag {
+                "case_sensitive" => case_sensitive = true,
+                "float_tolerance" | "float_absolute_tolerance" => match flags.next() {
+                    Some(eps) => {
+                        args.push("--epsilon".to_string());
+                        args.push(eps.to_string());
+                    }
+                    None => self.skip(format!("validator flag {}: value is missing", flag)),
+                },
+                "float_relative_tolerance" => {
+                    flags.next();
+                    self.skip(format!(
+                        "validator flag {}: relative tolerance is not supported",
+                        flag
+                    ));
+                }
+                other => self.skip(format!("validator flag {}: not supported", other)),
+            }
+        }
+        if !case_sensitive {
+            args.push("--ignore-case".to_string());
+        }
+        args
+    }
+
+    fn process_checker(&mut self) {
+        println!("Importing checker");
+        let validation = self
+            .manifest
+            .validation
+            .clone()
+            .unwrap_or_else(|| "default".to_string());
+        let flags = self.manifest.validator_flags.clone().unwrap_or_default();
+        let modes: Vec<_> = validation.split_whitespace().collect();
+        for &mode in modes.iter().skip(1) {
+            match mode {
+                "interactive" => self.skip(
+                    "validation: interactive problems are not supported by invoker yet".to_string(),
+                ),
+                other => self.skip(format!("validation: {} is not supported", other)),
+            }
+        }
+        self.problem_cfg.check_type = "builtin".to_string();
+        if modes.first() != Some(&"custom") {
+            let args = self.convert_default_validator_flags(&flags);
+            self.problem_cfg.builtin_check = Some(BuiltinCheck {
+                name: "cmp-tokens".to_string(),
+            });
+            self.problem_cfg.check_options = Some(CheckOptions { args });
+            self.report
+                .imported
+                .push("default output validator (as cmp-tokens)".to_string());
+            return;
+        }
+        let validators = sorted_entries(&self.src.join("output_validators"));
+        let validator = match validators.first() {
+            Some(v) => v,
+            None => {
+                self.skip("validation: custom, but output_validators is empty".to_string());
+                return;
+            }
+        };
+        for extra in &validators[1..] {
+            self.skip(format!(
+                "output validator {}: only one output validator is supported",
+                extra.display()
+            ));
+        }
+        if !self.import_program(validator, Path::new("modules/output-validator")) {
+            return;
+        }
+        let mut args = vec!["assets/module-output-validator/bin".to_string()];
+        args.extend(flags.split_whitespace().map(ToOwned::to_owned));
+        self.problem_cfg.builtin_check = Some(BuiltinCheck {
+            name: "kattis-compat".to_string(),
+        });
+        self.problem_cfg.check_options = Some(CheckOptions { args });
+        self.report.imported.push("output validator".to_string());
+    }
+
+    fn process_validator(&mut self) {
+        println!("Importing input validator");
+        let validators = sorted_entries(&self.src.join("input_validators"));
+        let validator = match validators.first() {
+            Some(v) => v,
+            None => return,
+        };
+        for extra in &validators[1..] {
+            self.skip(format!(
+                "input validator {}: only one validator is supported",
+                extra.display()
+            ));
+        }
+        if !self.import_program(validator, Path::new("modules/input-validator")) {
+            return;
+        }
+        static SHIM: &str = include_str!("./kattis-validator-shim.cpp");
+        fs::create_dir_all(self.dest.join("validators")).expect("create validators dir");
+        fs::write(self.dest.join("validators/main.cpp"), SHIM).expect("put validator shim");
+        self.problem_cfg.validator = Some("validators/main.cpp".to_string());
+        self.report.imported.push("input validator".to_string());
+    }
+
+    fn process_submissions(&mut self) {
+        println!("Importing submissions");
+        let mut categories = sorted_entries(&self.src.join("submissions"));
+        // primary solution is chosen from accepted submissions, so they go first
+        categories.sort_by_key(|path| path.file_name().map_or(true, |name| name != "accepted"));
+        for category_dir in categories {
+            let category = category_dir
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            let verdict = category_to_verdict(&category);
+            for submission in sorted_entries(&category_dir) {
+                let stem = submission.file_stem().unwrap().to_string_lossy();
+                let base_name = sanitize_name(&stem);
+                let mut name = base_name.clone();
+                let mut suffix = 1;
+                while self.problem_cfg.solutions.contains_key(&name)
+                    || self.dest.join("solutions").join(&name).exists()
+                {
+                    suffix += 1;
+                    name = format!("{}-{}", base_name, suffix);
+                }
+                if !self.import_program(&submission, &Path::new("solutions").join(&name)) {
+                    continue;
+                }
+                let verdict = match verdict {
+                    Some(verdict) => verdict,
+                    None => {
+                        self.report.imported.push(format!("solution {}", name));
+                        self.skip(format!(
+                            "solution {}: category {} can not be expressed as expected verdict",
+                            name, category
+                        ));
+                        continue;
+                    }
+                };
+                if verdict == "ok" && self.problem_cfg.primary_solution.is_none() {
+                    self.problem_cfg.primary_solution = Some(name.clone());
+                }
+                self.problem_cfg
+                    .solutions
+                    .insert(name.clone(), verdict.to_string());
+                self.report
+                    .imported
+                    .push(format!("solution {} (expected {})", name, verdict));
+            }
+        }
+        let has_answers = self
+            .problem_cfg
+            .tests
+            .iter()
+            .all(|spec| spec.answers.is_some());
+        if self.problem_cfg.primary_solution.is_none() && !has_answers {
+            self.skip(
+                "primary solution: there are no accepted submissions, add primary-solution manually"
+                    .to_string(),
+            );
+        }
+    }
+
+    fn run(&mut self) {
+        if self.manifest.problem_type.as_deref() == Some("scoring") {
+            self.skip("type: scoring problems are imported as pass-fail".to_string());
+        }
+        self.problem_cfg.valuer = "icpc".to_string();
+        self.problem_cfg.random_seed = Some(super::generate_random_seed());
+        fs::create_dir_all(self.dest.join("solutions")).expect("create solutions dir");
+        self.process_name();
+        self.process_limits();
+        self.process_statements();
+        self.process_tests();
+        self.process_checker();
+        self.process_validator();
+        self.process_submissions();
+    }
+}
+
+pub(super) fn import(src: &Path, dest: &Path) -> (RawProblem, Report) {
+    let manifest = match fs::read_to_string(src.join("problem.yaml")) {
+        // all keys are optional, so problem.yaml may be empty
+        Ok(data) if data.trim().is_empty() => ProblemYaml::default(),
+        Ok(data) => serde_yaml::from_str(&data).expect("problem.yaml parse error"),
+        Err(err) => {
+            eprintln!("warning: couldn't read problem.yaml: {}", err);
+            ProblemYaml::default()
+        }
+    };
+    let mut importer = Importer {
+        src,
+        dest,
+        problem_cfg: Default::default(),
+        manifest,
+        report: Report::default(),
+    };
+    importer.run();
+    (importer.problem_cfg, importer.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates package directory with given files
+    fn make_package(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jjs-ppc-kattis-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        for (path, data) in files {
+            let path = dir.join("src").join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = make_package(
+            "import",
+            &[
+                (
+                    "problem.yaml",
+                    "name:\n  en: Sum\nvalidator_flags: float_tolerance 1e-6\nlimits:\n  time_limit: 1.5\n  memory: 256\n",
+                ),
+                ("data/sample/1.in", "1 2\n"),
+                ("data/sample/1.ans", "3\n"),
+                ("data/secret/b.in", "3 4\n"),
+                ("data/secret/b.ans", "7\n"),
+                ("data/secret/a.in", "5 6\n"),
+                ("data/secret/a.ans", "11\n"),
+                ("submissions/wrong_answer/bad.py", "print(0)\n"),
+                ("submissions/accepted/sum.cc", "int main() {}\n"),
+            ],
+        );
+        let dest = dir.join("dest");
+        let (cfg, report) = import(&dir.join("src"), &dest);
+        assert_eq!(cfg.title, "Sum");
+        assert_eq!(cfg.limits.time, Some(1500));
+        assert_eq!(cfg.limits.memory, Some(256));
+        assert_eq!(cfg.tests.len(), 1);
+        assert_eq!(cfg.tests[0].map, "1..3");
+        assert_eq!(cfg.tests[0].answers.as_deref(), Some("%02d.ans"));
+        // samples go first, secret tests are sorted by name
+        for (i, test, answer) in &[
+            (1, "1 2\n", "3\n"),
+            (2, "5 6\n", "11\n"),
+            (3, "3 4\n", "7\n"),
+        ] {
+            let test_path = dest.join(format!("tests/{:02}.in", i));
+            assert_eq!(fs::read_to_string(test_path).unwrap(), *test);
+            let answer_path = dest.join(format!("tests/{:02}.ans", i));
+            assert_eq!(fs::read_to_string(answer_path).unwrap(), *answer);
+        }
+        assert_eq!(
+            cfg.check_options.unwrap().args,
+            vec!["--epsilon", "1e-6", "--ignore-case"]
+        );
+        assert_eq!(cfg.primary_solution.as_deref(), Some("sum"));
+        assert_eq!(cfg.solutions["sum"], "ok");
+        assert_eq!(cfg.solutions["bad"], "wa");
+        assert!(dest.join("solutions/sum/main.cpp").is_file());
+        assert!(dest.join("solutions/bad/main.py").is_file());
+        assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_without_answers() {
+        let dir = make_package(
+            "without-answers",
+            &[
+                ("problem.yaml", ""),
+                ("data/secret/1.in", "1\n"),
+                ("data/secret/1.ans", "1\n"),
+                ("data/secret/2.in", "2\n"),
+            ],
+        );
+        let dest = dir.join("dest");
+        let (cfg, report) = import(&dir.join("src"), &dest);
+        assert_eq!(cfg.tests[0].map, "1..2");
+        assert_eq!(cfg.tests[0].answers, None);
+        assert!(!dest.join("tests/01.ans").exists());
+        assert!(report
+            .skipped
+            .iter()
+            .any(|message| message.starts_with("answers (*.ans)")));
+        // answers must be generated, but there is no accepted submission
+        assert_eq!(cfg.primary_solution, None);
+        assert!(report
+            .skipped
+            .iter()
+            .any(|message| message.starts_with("primary solution")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
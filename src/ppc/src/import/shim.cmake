@@ -1,5 +1,9 @@
+cmake_minimum_required(VERSION 3.13.4)
+project(TestgenShim CXX)
+set(CMAKE_CXX_STANDARD 17)
+
 find_package(Jtl CONFIG REQUIRED)
 
 add_executable(Out main.cpp)
 target_include_directories(Out PUBLIC ${Jtl_INCLUDES})
-target_link_libraries(Out PUBLIC ${Jtl_LIBS})
\ No newline at end of file
+target_link_libraries(Out PUBLIC ${Jtl_LIBS})
@@ -1 +1,5 @@
-add_executable(Out main.cpp)
\ No newline at end of file
+cmake_minimum_required(VERSION 3.13.4)
+project(Solution CXX)
+set(CMAKE_CXX_STANDARD 17)
+
+add_executable(Out main.cpp)
@@ -6,6 +6,8 @@ mod cfg;
 mod command;
 mod compile;
 mod import;
+mod pack;
+mod verify;
 
 mod args {
     use std::path::PathBuf;
@@ -25,6 +27,25 @@ mod args {
         /// Verbose
         #[structopt(long, short = "V")]
         pub verbose: bool,
+        /// Count of tests generated simultaneously (defaults to CPU count)
+        #[structopt(long, short = "j")]
+        pub jobs: Option<usize>,
+        /// Directory for caching generated tests (defaults to `<out>/.cache`)
+        #[structopt(long)]
+        pub cache_dir: Option<PathBuf>,
+        /// Regenerate all tests, ignoring cache
+        #[structopt(long)]
+        pub no_cache: bool,
+        /// Run testgens, primary solution and validator in minion sandbox (requires same
+        /// privileges as invoker). Sandboxed testgens must write test to stdout.
+        #[structopt(long)]
+        pub sandbox: bool,
+        /// Time limit for testgens, primary solution and validator, in milliseconds
+        #[structopt(long, default_value = "10000")]
+        pub time_limit: u64,
+        /// Memory limit for testgens, primary solution and validator, in megabytes
+        #[structopt(long, default_value = "1024")]
+        pub memory_limit: u64,
     }
 
     #[derive(StructOpt)]
@@ -38,6 +59,57 @@ mod args {
         /// Rewrite dir
         #[structopt(long, short = "F")]
         pub force: bool,
+        /// Format of imported package: `polygon` or `kattis`
+        #[structopt(long, default_value = "polygon")]
+        pub format: crate::import::Format,
+    }
+
+    #[derive(StructOpt)]
+    pub struct VerifyArgs {
+        /// Path to problem package root
+        #[structopt(long = "pkg", short = "P")]
+        pub pkg_path: PathBuf,
+        /// Path to compiled package (output of `compile`)
+        #[structopt(long = "out", short = "O")]
+        pub out_path: PathBuf,
+        /// Only verify these solutions (by default all solutions from problem.toml are verified)
+        #[structopt(long = "solution", short = "S")]
+        pub solutions: Vec<String>,
+        /// Time limit for tests without limits in manifest, in milliseconds
+        #[structopt(long, default_value = "1000")]
+        pub time_limit: u64,
+        /// Memory limit for tests without limits in manifest, in megabytes
+        #[structopt(long, default_value = "256")]
+        pub memory_limit: u64,
+    }
+
+    #[derive(StructOpt)]
+    pub struct PackArgs {
+        /// Path to compiled package (output of `compile`)
+        #[structopt(long = "pkg", short = "P")]
+        pub pkg_path: PathBuf,
+        /// Path to created archive
+        #[structopt(long = "out", short = "O")]
+        pub out_path: PathBuf,
+        /// Package version (defaults to prefix of package checksum)
+        #[structopt(long = "package-version")]
+        pub version: Option<String>,
+    }
+
+    #[derive(StructOpt)]
+    pub struct InstallArgs {
+        /// Path to archive, created by `pack`
+        #[structopt(long = "archive", short = "A")]
+        pub archive_path: PathBuf,
+        /// JJS sysroot (defaults to JJS_SYSROOT)
+        #[structopt(long)]
+        pub sysroot: Option<PathBuf>,
+        /// Number of previous versions to keep; older ones are removed
+        #[structopt(long = "keep-versions", default_value = "3")]
+        pub keep_versions: usize,
+        /// Print installed name, version and checksum as JSON on the last line
+        #[structopt(long)]
+        pub json: bool,
     }
 
     #[derive(StructOpt)]
@@ -45,6 +117,12 @@ mod args {
     pub enum Args {
         Compile(CompileArgs),
         Import(ImportArgs),
+        /// Check that solutions get expected verdicts
+        Verify(VerifyArgs),
+        /// Pack compiled package into archive
+        Pack(PackArgs),
+        /// Install archive into sysroot
+        Install(InstallArgs),
     }
 }
 
@@ -60,7 +138,7 @@ mod errors {
 
 use args::Args;
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     process::{exit, Stdio},
 };
 
@@ -79,31 +157,31 @@ fn check_dir(path: &Path, allow_nonempty: bool) {
     }
 }
 
-fn open_as_handle(path: &str) -> std::io::Result<i64> {
+/// Returns handle of file, which is inherited by child processes
+fn inherit_handle(file: std::fs::File) -> i64 {
     use std::os::unix::io::IntoRawFd;
     // note: platform-dependent code
-    let file = std::fs::File::create(path)?;
     let fd = file.into_raw_fd();
     let fd_dup = unsafe { libc::dup(fd) }; // to cancel CLOEXEC behavior
     unsafe {
         libc::close(fd);
     }
-    Ok(i64::from(fd_dup))
+    i64::from(fd_dup)
 }
 
-fn compile_problem(args: args::CompileArgs) {
-    if args.force {
-        //std::fs::remove_dir_all(&args.out_path).expect("couldn't remove");
-        std::fs::create_dir_all(&args.out_path).ok();
-    } else {
-        check_dir(&args.out_path, false /* TODO */);
-    }
-    let toplevel_manifest = args.pkg_path.join("problem.toml");
+fn load_problem_cfg(pkg_path: &Path) -> cfg::Problem {
+    let toplevel_manifest = pkg_path.join("problem.toml");
     let toplevel_manifest = std::fs::read_to_string(toplevel_manifest).unwrap();
 
     let raw_problem_cfg: cfg::RawProblem =
         toml::from_str(&toplevel_manifest).expect("problem.toml parse error");
-    let (problem_cfg, warnings) = raw_problem_cfg.postprocess().unwrap();
+    let (problem_cfg, warnings) = match raw_problem_cfg.postprocess() {
+        Ok(res) => res,
+        Err(err) => {
+            eprintln!("error: invalid problem.toml: {}", err);
+            exit(1);
+        }
+    };
 
     if !warnings.is_empty() {
         eprintln!("{} warnings", warnings.len());
@@ -111,12 +189,33 @@ fn compile_problem(args: args::CompileArgs) {
             eprintln!("- {}", warn);
         }
     }
+    problem_cfg
+}
+
+fn compile_problem(args: args::CompileArgs) {
+    if args.force {
+        //std::fs::remove_dir_all(&args.out_path).expect("couldn't remove");
+        std::fs::create_dir_all(&args.out_path).ok();
+    } else {
+        check_dir(&args.out_path, false /* TODO */);
+    }
+    let problem_cfg = load_problem_cfg(&args.pkg_path);
 
     let jjs_dir = env::var("JJS_PATH").expect("JJS_PATH not set");
 
     let out_dir = args.out_path.canonicalize().expect("resolve out dir");
     let problem_dir = args.pkg_path.canonicalize().expect("resolve problem dir");
 
+    let jobs = args.jobs.unwrap_or_else(|| {
+        let cpu_count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+        if cpu_count > 0 {
+            cpu_count as usize
+        } else {
+            1
+        }
+    });
+    let cache_dir = args.cache_dir.unwrap_or_else(|| out_dir.join(".cache"));
+
     let builder = compile::ProblemBuilder {
         cfg: &problem_cfg,
         problem_dir: &problem_dir,
@@ -124,10 +223,127 @@ fn compile_problem(args: args::CompileArgs) {
         build_backend: &compile::build::Pibs {
             jjs_dir: Path::new(&jjs_dir),
         },
+        jobs,
+        cache_dir: if args.no_cache {
+            None
+        } else {
+            Some(&cache_dir)
+        },
+        limits: compile::sandbox::Limits {
+            time: std::time::Duration::from_millis(args.time_limit),
+            memory: args.memory_limit * 1024 * 1024,
+            process_count: 16,
+        },
+        sandbox: args.sandbox,
     };
     builder.build();
 }
 
+fn verify_problem(args: args::VerifyArgs) {
+    let problem_cfg = load_problem_cfg(&args.pkg_path);
+    check_dir(&args.out_path, true);
+    let manifest_path = args.out_path.join("manifest.json");
+    let manifest = std::fs::read(&manifest_path).unwrap_or_else(|err| {
+        eprintln!("error: couldn't read {}: {}", manifest_path.display(), err);
+        exit(1);
+    });
+    let manifest: pom::Problem = serde_json::from_slice(&manifest).expect("invalid manifest");
+
+    let mut solutions: Vec<_> = problem_cfg.solutions.iter().collect();
+    if !args.solutions.is_empty() {
+        for name in &args.solutions {
+            if !problem_cfg.solutions.contains_key(name) {
+                eprintln!("error: solution {} is not listed in problem.toml", name);
+                exit(1);
+            }
+        }
+        solutions.retain(|&(name, _)| args.solutions.contains(name));
+    }
+    if solutions.is_empty() {
+        eprintln!("warning: no solutions to verify");
+        return;
+    }
+
+    let jjs_dir = env::var("JJS_PATH").expect("JJS_PATH not set");
+    let out_dir = args.out_path.canonicalize().expect("resolve out dir");
+    let work_dir = env::temp_dir().join(format!("jjs-ppc-verify-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir).expect("couldn't create work dir");
+    let minion_backend = minion::setup();
+
+    let verifier = verify::Verifier {
+        manifest: &manifest,
+        out_dir: &out_dir,
+        jjs_dir: Path::new(&jjs_dir),
+        work_dir: &work_dir,
+        limits: verify::Limits {
+            time: std::time::Duration::from_millis(args.time_limit),
+            memory: args.memory_limit * 1024 * 1024,
+        },
+        minion_backend: &*minion_backend,
+    };
+    let mut failed = 0;
+    for &(name, &expected) in &solutions {
+        match verifier.verify_solution(name, expected) {
+            Ok(report) => {
+                report.print();
+                if !report.matches() {
+                    failed += 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("error: solution {}: {}", name, err);
+                failed += 1;
+            }
+        }
+    }
+    std::fs::remove_dir_all(&work_dir).ok();
+    if failed != 0 {
+        eprintln!(
+            "error: {} of {} solutions did not get expected verdict",
+            failed,
+            solutions.len()
+        );
+        exit(1);
+    }
+}
+
+fn pack_problem(args: args::PackArgs) {
+    check_dir(&args.pkg_path, true);
+    match pack::pack(&args.pkg_path, args.version.as_deref(), &args.out_path) {
+        Ok(info) => println!(
+            "Packed problem {} version {} (checksum {})",
+            info.name, info.version, info.checksum
+        ),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(1);
+        }
+    }
+}
+
+fn install_problem(args: args::InstallArgs) {
+    let sysroot = args
+        .sysroot
+        .or_else(|| env::var_os("JJS_SYSROOT").map(PathBuf::from))
+        .unwrap_or_else(|| {
+            eprintln!("error: sysroot must be provided in --sysroot or JJS_SYSROOT");
+            exit(1);
+        });
+    let problems_dir = sysroot.join("var/problems");
+    check_dir(&problems_dir, true);
+    match pack::install(&args.archive_path, &problems_dir, args.keep_versions) {
+        Ok(info) if args.json => println!(
+            "{}",
+            serde_json::to_string(&info).expect("serialize pack info")
+        ),
+        Ok(info) => println!("Installed problem {} version {}", info.name, info.version),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(1);
+        }
+    }
+}
+
 fn main() {
     use structopt::StructOpt;
 
@@ -136,5 +352,8 @@ fn main() {
     match args {
         Args::Compile(compile_args) => compile_problem(compile_args),
         Args::Import(import_args) => import::exec(import_args),
+        Args::Verify(verify_args) => verify_problem(verify_args),
+        Args::Pack(pack_args) => pack_problem(pack_args),
+        Args::Install(install_args) => install_problem(install_args),
     }
 }
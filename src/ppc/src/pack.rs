@@ -0,0 +1,521 @@
+//! Packing compiled problems into archives and installing them into sysroot.
+//!
+//! Archive is gzipped tarball, containing `pack.json` (see `PackInfo`) and
+//! compiled package in `problem/` directory.
+//!
+//! Installed versions are stored in `var/problems/.versions/<name>/<version>`,
+//! `var/problems/.versions/<name>/.current` is symlink to current version, and
+//! `var/problems/<name>` is symlink to it. Switching symlink is atomic, and
+//! recent versions are kept, so runs being judged are not affected.
+use serde::{Deserialize, Serialize};
+use sha3::Digest;
+use snafu::{ResultExt, Snafu};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Version of archive format, incremented on incompatible changes
+const FORMAT_VERSION: u32 = 1;
+
+/// Directories of compiled package, which are not packed
+const EXCLUDED_DIRS: &[&str] = &[".cache"];
+
+/// Symlink to current version in versions dir. Valid versions never start
+/// with dot, so it can not clash with them.
+const CURRENT_LINK: &str = ".current";
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("io error: {}", source))]
+    Io { source: std::io::Error },
+    #[snafu(display("invalid manifest.json (was package compiled?): {}", source))]
+    Manifest { source: serde_json::Error },
+    #[snafu(display("{} '{}' can not be used as directory name", what, value))]
+    InvalidName { what: &'static str, value: String },
+    #[snafu(display("invalid archive: {}", description))]
+    BadArchive { description: String },
+    #[snafu(display(
+        "archive format {} is not supported (expected {})",
+        format,
+        FORMAT_VERSION
+    ))]
+    UnsupportedFormat { format: u32 },
+    #[snafu(display("checksum mismatch: expected {}, got {}", expected, actual))]
+    ChecksumMismatch { expected: String, actual: String },
+    #[snafu(display(
+        "version {} of problem {} is already installed with different contents",
+        version,
+        name
+    ))]
+    VersionConflict { name: String, version: String },
+}
+
+/// Describes archive contents, stored in `pack.json`
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct PackInfo {
+    /// Version of archive format
+    pub(crate) format: u32,
+    /// Problem name
+    pub(crate) name: String,
+    /// Version of problem package
+    pub(crate) version: String,
+    /// SHA3-256 of package contents (see `checksum`)
+    pub(crate) checksum: String,
+}
+
+/// Checks that `s` can be used as directory name
+fn is_valid_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('.')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Returns paths of all files in `dir`, relative to it, sorted
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    fn visit(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for item in fs::read_dir(dir)? {
+            let path = item?.path();
+            let rel_path = path.strip_prefix(root).unwrap().to_path_buf();
+            if path.is_dir() {
+                if !EXCLUDED_DIRS
+                    .iter()
+                    .any(|&excluded| rel_path == Path::new(excluded))
+                {
+                    visit(root, &path, out)?;
+                }
+            } else {
+                out.push(rel_path);
+            }
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    visit(dir, dir, &mut out).context(Io {})?;
+    out.sort();
+    Ok(out)
+}
+
+/// Hashes paths, executable bits and contents of all files in `dir`
+fn checksum(dir: &Path) -> Result<String, Error> {
+    let mut hasher = sha3::Sha3_256::new();
+    for rel_path in list_files(dir)? {
+        let path = dir.join(&rel_path);
+        let data = fs::read(&path).context(Io {})?;
+        let mode = fs::metadata(&path).context(Io {})?.permissions().mode();
+        let rel_path = rel_path.to_string_lossy();
+        // length prefixes make encoding unambiguous
+        hasher.input((rel_path.len() as u64).to_le_bytes());
+        hasher.input(rel_path.as_bytes());
+        hasher.input([(mode & 0o111 != 0) as u8]);
+        hasher.input((data.len() as u64).to_le_bytes());
+        hasher.input(&data);
+    }
+    Ok(format!("{:x}", hasher.result()))
+}
+
+/// Packs compiled package from `pkg_dir` into archive `out_path`.
+/// If `version` is not given, prefix of checksum is used.
+pub(crate) fn pack(
+    pkg_dir: &Path,
+    version: Option<&str>,
+    out_path: &Path,
+) -> Result<PackInfo, Error> {
+    let manifest = fs::read(pkg_dir.join("manifest.json")).context(Io {})?;
+    let manifest: pom::Problem = serde_json::from_slice(&manifest).context(Manifest {})?;
+    if !is_valid_name(&manifest.name) {
+        return Err(Error::InvalidName {
+            what: "problem name",
+            value: manifest.name,
+        });
+    }
+    let checksum = checksum(pkg_dir)?;
+    let version = version.unwrap_or(&checksum[..16]).to_string();
+    if !is_valid_name(&version) {
+        return Err(Error::InvalidName {
+            what: "version",
+            value: version,
+        });
+    }
+    let info = PackInfo {
+        format: FORMAT_VERSION,
+        name: manifest.name,
+        version,
+        checksum,
+    };
+
+    let out_file = fs::File::create(out_path).context(Io {})?;
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        out_file,
+        flate2::Compression::default(),
+    ));
+    let info_data = serde_json::to_vec_pretty(&info).context(Manifest {})?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(info_data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "pack.json", info_data.as_slice())
+        .context(Io {})?;
+    for rel_path in list_files(pkg_dir)? {
+        builder
+            .append_path_with_name(
+                pkg_dir.join(&rel_path),
+                Path::new("problem").join(&rel_path),
+            )
+            .context(Io {})?;
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context(Io {})?;
+    Ok(info)
+}
+
+/// Atomically creates or replaces symlink `link_path`, pointing to `target`
+fn replace_symlink(target: &Path, link_path: &Path) -> Result<(), Error> {
+    let file_name = link_path.file_name().unwrap().to_string_lossy();
+    let tmp_link_path =
+        link_path.with_file_name(format!(".{}.link-{}", file_name, std::process::id()));
+    std::os::unix::fs::symlink(target, &tmp_link_path).context(Io {})?;
+    // rename atomically replaces previous symlink
+    if let Err(err) = fs::rename(&tmp_link_path, link_path) {
+        fs::remove_file(&tmp_link_path).ok();
+        return Err(err).context(Io {});
+    }
+    Ok(())
+}
+
+/// Makes `var/problems/<name>` point to installed version
+fn switch_version(problems_dir: &Path, info: &PackInfo) -> Result<(), Error> {
+    let versions_dir = problems_dir.join(".versions").join(&info.name);
+    replace_symlink(Path::new(&info.version), &versions_dir.join(CURRENT_LINK))?;
+    let link_path = problems_dir.join(&info.name);
+    match fs::symlink_metadata(&link_path) {
+        Ok(metadata) if !metadata.file_type().is_symlink() => {
+            // problem was installed manually (e.g. compiled directly to sysroot).
+            // It can be used by runs being judged, so it is not touched:
+            // invoker prefers current version anyway.
+            println!(
+                "Note: {} was not installed from archive and is now unused; remove it when runs being judged are finished",
+                link_path.display()
+            );
+            Ok(())
+        }
+        _ => replace_symlink(
+            &Path::new(".versions").join(&info.name).join(CURRENT_LINK),
+            &link_path,
+        ),
+    }
+}
+
+/// Removes old versions, so that at most `keep` versions besides
+/// current one remain. Versions are ordered by installation time.
+fn prune_versions(versions_dir: &Path, current: &str, keep: usize) -> Result<(), Error> {
+    let mut versions = Vec::new();
+    for item in fs::read_dir(versions_dir).context(Io {})? {
+        let item = item.context(Io {})?;
+        let name = item.file_name().to_string_lossy().into_owned();
+        // skips current version and symlinks
+        if name == current || !is_valid_name(&name) || !item.file_type().context(Io {})?.is_dir() {
+            continue;
+        }
+        let installed_at = item.metadata().and_then(|m| m.modified()).context(Io {})?;
+        versions.push((installed_at, name));
+    }
+    // newest first
+    versions.sort_by(|a, b| b.cmp(a));
+    for (_, name) in versions.into_iter().skip(keep) {
+        println!("Removing old version {}", name);
+        fs::remove_dir_all(versions_dir.join(name)).context(Io {})?;
+    }
+    Ok(())
+}
+
+fn install_staged(
+    staging_dir: &Path,
+    problems_dir: &Path,
+    keep_versions: usize,
+) -> Result<PackInfo, Error> {
+    let info_data = fs::read(staging_dir.join("pack.json")).map_err(|err| Error::BadArchive {
+        description: format!("couldn't read pack.json: {}", err),
+    })?;
+    let info: PackInfo = serde_json::from_slice(&info_data).map_err(|err| Error::BadArchive {
+        description: format!("couldn't parse pack.json: {}", err),
+    })?;
+    if info.format != FORMAT_VERSION {
+        return Err(Error::UnsupportedFormat {
+            format: info.format,
+        });
+    }
+    for &(what, value) in &[("problem name", &info.name), ("version", &info.version)] {
+        if !is_valid_name(value) {
+            return Err(Error::InvalidName {
+                what,
+                value: value.clone(),
+            });
+        }
+    }
+    let staged_problem = staging_dir.join("problem");
+    let actual = checksum(&staged_problem)?;
+    if actual != info.checksum {
+        return Err(Error::ChecksumMismatch {
+            expected: info.checksum,
+            actual,
+        });
+    }
+
+    let versions_dir = problems_dir.join(".versions").join(&info.name);
+    fs::create_dir_all(&versions_dir).context(Io {})?;
+    let version_dir = versions_dir.join(&info.version);
+    if version_dir.exists() {
+        if checksum(&version_dir)? != info.checksum {
+            return Err(Error::VersionConflict {
+                name: info.name,
+                version: info.version,
+            });
+        }
+        println!("Version {} is already installed", info.version);
+    } else {
+        fs::rename(&staged_problem, &version_dir).context(Io {})?;
+    }
+    switch_version(problems_dir, &info)?;
+    prune_versions(&versions_dir, &info.version, keep_versions)?;
+    Ok(info)
+}
+
+/// Unpacks archive into `dest`. Only regular files and directories are
+/// allowed: links could point outside of package.
+fn unpack(archive_path: &Path, dest: &Path) -> Result<(), Error> {
+    let file = fs::File::open(archive_path).context(Io {})?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    for entry in archive.entries().context(Io {})? {
+        let mut entry = entry.context(Io {})?;
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(Error::BadArchive {
+                description: format!(
+                    "{} is not regular file or directory",
+                    entry.path().context(Io {})?.display()
+                ),
+            });
+        }
+        // `unpack_in` refuses paths, escaping `dest`
+        if !entry.unpack_in(dest).context(Io {})? {
+            return Err(Error::BadArchive {
+                description: format!(
+                    "{} is outside of archive",
+                    entry.path().context(Io {})?.display()
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Installs archive into `problems_dir` (usually `$JJS_SYSROOT/var/problems`),
+/// keeping at most `keep_versions` previous versions
+pub(crate) fn install(
+    archive_path: &Path,
+    problems_dir: &Path,
+    keep_versions: usize,
+) -> Result<PackInfo, Error> {
+    // staging dir is on the same filesystem, so that it can be moved atomically
+    let staging_dir = problems_dir.join(format!(".staging-{}", std::process::id()));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).context(Io {})?;
+    }
+    fs::create_dir(&staging_dir).context(Io {})?;
+    let res = unpack(archive_path, &staging_dir)
+        .and_then(|_| install_staged(&staging_dir, problems_dir, keep_versions));
+    fs::remove_dir_all(&staging_dir).ok();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jjs-ppc-pack-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(dir.join("problems")).unwrap();
+        dir
+    }
+
+    /// Writes compiled package with one test to `dir`
+    fn make_package(dir: &Path, test_data: &str) {
+        let file_ref = |path: &str| pom::FileRef {
+            root: pom::FileRefRoot::Problem,
+            path: path.to_string(),
+        };
+        let manifest = pom::Problem {
+            title: "A + B".to_string(),
+            name: "a-plus-b".to_string(),
+            tests: vec![pom::Test {
+                path: file_ref("tests/1-in.txt"),
+                correct: None,
+                group: None,
+                limits: Default::default(),
+            }],
+            checker_exe: file_ref("checker"),
+            checker_cmd: Vec::new(),
+            valuer_exe: file_ref("valuer"),
+            valuer_cfg: file_ref("valuer-cfg.yaml"),
+            groups: Vec::new(),
+            limits: Default::default(),
+        };
+        fs::create_dir_all(dir.join("assets/tests")).unwrap();
+        fs::create_dir_all(dir.join(".cache")).unwrap();
+        fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("assets/tests/1-in.txt"), test_data).unwrap();
+        fs::write(dir.join("assets/checker"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(
+            dir.join("assets/checker"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        fs::write(dir.join(".cache/entry"), "cached").unwrap();
+    }
+
+    fn pack_version(dir: &Path, test_data: &str, version: &str) -> (PathBuf, PackInfo) {
+        let pkg_dir = dir.join(format!("pkg-{}", version));
+        make_package(&pkg_dir, test_data);
+        let archive_path = dir.join(format!("{}.tar.gz", version));
+        let info = pack(&pkg_dir, Some(version), &archive_path).unwrap();
+        assert_eq!(info.checksum, checksum(&pkg_dir).unwrap());
+        (archive_path, info)
+    }
+
+    #[test]
+    fn test_pack_install() {
+        let dir = temp_dir("install");
+        let problems_dir = dir.join("problems");
+        let (archive_v1, info_v1) = pack_version(&dir, "1 2\n", "v1");
+        let installed = install(&archive_v1, &problems_dir, 3).unwrap();
+        assert_eq!(installed.name, "a-plus-b");
+        assert_eq!(installed.version, "v1");
+        let current_dir = problems_dir.join("a-plus-b");
+        assert_eq!(checksum(&current_dir).unwrap(), info_v1.checksum);
+        assert!(!current_dir.join(".cache").exists());
+        let checker_mode = fs::metadata(current_dir.join("assets/checker"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_ne!(checker_mode & 0o111, 0);
+
+        // reinstalling same version is allowed
+        install(&archive_v1, &problems_dir, 3).unwrap();
+        // but different contents under same version are not
+        let conflicting_archive = dir.join("conflict.tar.gz");
+        make_package(&dir.join("pkg-conflict"), "3 4\n");
+        pack(&dir.join("pkg-conflict"), Some("v1"), &conflicting_archive).unwrap();
+        match install(&conflicting_archive, &problems_dir, 3) {
+            Err(Error::VersionConflict { .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(checksum(&current_dir).unwrap(), info_v1.checksum);
+
+        let (archive_v2, info_v2) = pack_version(&dir, "5 6\n", "v2");
+        install(&archive_v2, &problems_dir, 3).unwrap();
+        assert_eq!(checksum(&current_dir).unwrap(), info_v2.checksum);
+        // previous version is kept for runs being judged
+        let v1_dir = problems_dir.join(".versions/a-plus-b/v1");
+        assert_eq!(checksum(&v1_dir).unwrap(), info_v1.checksum);
+        // staging dir is removed
+        let leftovers: Vec<_> = fs::read_dir(&problems_dir)
+            .unwrap()
+            .map(|item| item.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers.len(), 2, "{:?}", leftovers);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_install_prunes_old_versions() {
+        let dir = temp_dir("prune");
+        let problems_dir = dir.join("problems");
+        for version in &["v1", "v2", "v3", "v4"] {
+            let (archive_path, _) = pack_version(&dir, version, version);
+            install(&archive_path, &problems_dir, 2).unwrap();
+        }
+        let versions_dir = problems_dir.join(".versions/a-plus-b");
+        let mut versions: Vec<_> = fs::read_dir(&versions_dir)
+            .unwrap()
+            .map(|item| item.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        versions.sort();
+        assert_eq!(versions, vec![".current", "v2", "v3", "v4"]);
+        assert_eq!(
+            fs::read_link(versions_dir.join(".current")).unwrap(),
+            Path::new("v4")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_install_keeps_manually_installed() {
+        let dir = temp_dir("legacy");
+        let problems_dir = dir.join("problems");
+        let legacy_dir = problems_dir.join("a-plus-b");
+        make_package(&legacy_dir, "legacy\n");
+        let legacy_checksum = checksum(&legacy_dir).unwrap();
+        let (archive_path, info) = pack_version(&dir, "1 2\n", "v1");
+        install(&archive_path, &problems_dir, 3).unwrap();
+        // runs can still be judged from it
+        assert_eq!(checksum(&legacy_dir).unwrap(), legacy_checksum);
+        let current_dir = problems_dir.join(".versions/a-plus-b/.current");
+        assert_eq!(checksum(&current_dir).unwrap(), info.checksum);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_install_rejects_links() {
+        let dir = temp_dir("links");
+        let problems_dir = dir.join("problems");
+        let (archive_path, info) = pack_version(&dir, "1 2\n", "v1");
+        // appends symlink to otherwise valid archive
+        let evil_path = dir.join("evil.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&evil_path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+            fs::File::open(&archive_path).unwrap(),
+        ));
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut header = entry.header().clone();
+            let path = entry.path().unwrap().into_owned();
+            builder.append_data(&mut header, path, &mut entry).unwrap();
+        }
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_link_name("/etc/passwd").unwrap();
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "problem/passwd", std::io::empty())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        match install(&evil_path, &problems_dir, 3) {
+            Err(Error::BadArchive { .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(!problems_dir.join("a-plus-b").exists());
+        assert!(!problems_dir
+            .join(".versions/a-plus-b")
+            .join(&info.version)
+            .exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
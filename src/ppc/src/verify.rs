@@ -0,0 +1,525 @@
+//! This module implements judging solutions on compiled package and checking
+//! that they get expected verdicts
+use crate::cfg::Verdict;
+use invoker_api::{status_codes, Status, StatusKind};
+use snafu::{ResultExt, Snafu};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+#[derive(Debug, Snafu)]
+pub(crate) enum Error {
+    #[snafu(display("io error: {}", source))]
+    Io { source: std::io::Error },
+    #[snafu(display("sandbox error: {}", source))]
+    Minion { source: minion::Error },
+    #[snafu(display("solution binary {} not found (was package compiled?)", path.display()))]
+    SolutionMissing { path: PathBuf },
+    #[snafu(display("checker failed on test {}: {}", test_id, description))]
+    Checker { test_id: u32, description: String },
+    #[snafu(display("valuer failed: {}", description))]
+    Valuer { description: String },
+}
+
+/// Host directories, exposed to sandbox, so that dynamically linked solutions can run
+const SYSTEM_DIRS: &[&str] = &["/bin", "/lib", "/lib64", "/usr"];
+
+/// Limits for tests, which do not specify them
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Limits {
+    pub(crate) time: Duration,
+    /// In bytes
+    pub(crate) memory: u64,
+}
+
+/// Result of judging one solution
+pub(crate) struct SolutionReport {
+    pub(crate) name: String,
+    pub(crate) expected: Verdict,
+    pub(crate) score: u32,
+    /// Outcomes of tests, requested by valuer
+    pub(crate) tests: Vec<(u32, Verdict)>,
+}
+
+impl SolutionReport {
+    fn failures(&self) -> impl Iterator<Item = &(u32, Verdict)> {
+        self.tests
+            .iter()
+            .filter(|(_, outcome)| *outcome != Verdict::Ok)
+    }
+
+    /// Verdict describing actual outcome: for failed solution it is verdict of first failed test
+    pub(crate) fn actual(&self) -> Verdict {
+        match self.failures().next() {
+            Some(&(_, outcome)) => outcome,
+            None if self.score == 100 => Verdict::Ok,
+            None => Verdict::Partial(self.score),
+        }
+    }
+
+    pub(crate) fn matches(&self) -> bool {
+        match self.expected {
+            Verdict::Ok => self.score == 100 && self.failures().next().is_none(),
+            Verdict::Partial(score) => self.score == score,
+            expected => {
+                self.score < 100
+                    && self.failures().next().is_some()
+                    && self.failures().all(|&(_, outcome)| outcome == expected)
+            }
+        }
+    }
+
+    pub(crate) fn print(&self) {
+        if self.matches() {
+            println!(
+                "solution {}: {} (score {})",
+                self.name, self.expected, self.score
+            );
+            return;
+        }
+        eprintln!(
+            "solution {}: expected {}, got {} (score {})",
+            self.name,
+            self.expected,
+            self.actual(),
+            self.score
+        );
+        for (test_id, outcome) in &self.tests {
+            eprintln!("  test {}: {}", test_id, outcome);
+        }
+    }
+}
+
+fn test_status(outcome: Verdict) -> Status {
+    let (kind, code) = match outcome {
+        Verdict::Ok => (StatusKind::Accepted, status_codes::TEST_PASSED),
+        Verdict::WrongAnswer => (StatusKind::Rejected, status_codes::WRONG_ANSWER),
+        Verdict::PresentationError => (StatusKind::Rejected, status_codes::PRESENTATION_ERROR),
+        Verdict::TimeLimitExceeded => (StatusKind::Rejected, status_codes::TIME_LIMIT_EXCEEDED),
+        Verdict::RuntimeError => (StatusKind::Rejected, status_codes::RUNTIME_ERROR),
+        Verdict::Partial(_) => unreachable!("partial verdict is not test outcome"),
+    };
+    Status {
+        kind,
+        code: code.to_string(),
+    }
+}
+
+fn close_handles(handles: &[i64]) {
+    for &handle in handles {
+        unsafe {
+            libc::close(handle as i32);
+        }
+    }
+}
+
+pub(crate) struct Verifier<'a> {
+    /// Compiled package manifest
+    pub(crate) manifest: &'a pom::Problem,
+    /// Compiled package root
+    pub(crate) out_dir: &'a Path,
+    pub(crate) jjs_dir: &'a Path,
+    /// Directory for solution outputs and sandboxes
+    pub(crate) work_dir: &'a Path,
+    pub(crate) limits: Limits,
+    pub(crate) minion_backend: &'a dyn minion::Backend,
+}
+
+impl<'a> Verifier<'a> {
+    fn time_limit(&self, limits: &pom::Limits) -> Duration {
+        match limits.time {
+            Some(time) => Duration::from_millis(time),
+            None => self.limits.time,
+        }
+    }
+
+    /// Creates dominion, in which system directories and `exposed_paths` are available
+    fn new_dominion(
+        &self,
+        isolation_root: PathBuf,
+        limits: &pom::Limits,
+        mut exposed_paths: Vec<minion::PathExpositionOptions>,
+    ) -> Result<minion::DominionRef, Error> {
+        fs::create_dir_all(&isolation_root).context(Io {})?;
+        for dir in SYSTEM_DIRS {
+            if Path::new(dir).exists() {
+                exposed_paths.push(minion::PathExpositionOptions {
+                    src: PathBuf::from(dir),
+                    dest: PathBuf::from(dir),
+                    access: minion::DesiredAccess::Readonly,
+                });
+            }
+        }
+        self.minion_backend
+            .new_dominion(minion::DominionOptions {
+                max_alive_process_count: limits.process_count.unwrap_or(16) as u32,
+                memory_limit: limits.memory.unwrap_or(self.limits.memory),
+                time_limit: self.time_limit(limits),
+                isolation_root,
+                exposed_paths,
+            })
+            .context(Minion {})
+    }
+
+    fn resolve(&self, file_ref: &pom::FileRef) -> PathBuf {
+        let root = match file_ref.root {
+            pom::FileRefRoot::Problem => self.out_dir.join("assets"),
+            pom::FileRefRoot::System => self.jjs_dir.to_path_buf(),
+            pom::FileRefRoot::Root => PathBuf::from("/"),
+        };
+        root.join(&file_ref.path)
+    }
+
+    /// Runs solution on test. Returns path to solution output, or failure verdict.
+    fn run_solution(
+        &self,
+        solution_dir: &Path,
+        test: &pom::Test,
+        run_dir: &Path,
+    ) -> Result<Result<PathBuf, Verdict>, Error> {
+        let time_limit = self.time_limit(&test.limits);
+        let dominion = self.new_dominion(
+            run_dir.join("root"),
+            &test.limits,
+            vec![minion::PathExpositionOptions {
+                src: solution_dir.to_path_buf(),
+                dest: PathBuf::from("/sol"),
+                access: minion::DesiredAccess::Readonly,
+            }],
+        )?;
+
+        let output_path = run_dir.join("output.txt");
+        let input = fs::File::open(self.resolve(&test.path)).context(Io {})?;
+        let output = fs::File::create(&output_path).context(Io {})?;
+        let mut cmd = minion::Command::new();
+        cmd.path("/sol/bin").dominion(dominion);
+        unsafe {
+            cmd.stdin(minion::InputSpecification::handle_of(input));
+            cmd.stdout(minion::OutputSpecification::handle_of(output));
+        }
+        cmd.stderr(minion::OutputSpecification::ignore());
+        let mut child = match cmd.spawn(self.minion_backend) {
+            Ok(child) => child,
+            Err(err) if err.is_system() => return Err(err).context(Minion {}),
+            Err(_) => return Ok(Err(Verdict::RuntimeError)),
+        };
+        match child.wait_for_exit(time_limit).context(Minion {})? {
+            minion::WaitOutcome::Timeout => {
+                child.kill().ok();
+                return Ok(Err(Verdict::TimeLimitExceeded));
+            }
+            minion::WaitOutcome::AlreadyFinished => unreachable!("not expected other to wait"),
+            minion::WaitOutcome::Exited => {
+                if child.get_exit_code().context(Minion {})? != Some(0) {
+                    return Ok(Err(Verdict::RuntimeError));
+                }
+            }
+        }
+        Ok(Ok(output_path))
+    }
+
+    /// Runs checker (see checker protocol in `man/src/problems/checker.md`).
+    ///
+    /// Checker is sandboxed with default limits. Sandbox only passes standard
+    /// streams, so files are opened by shell inside it and passed to checker
+    /// as handles 3-7.
+    fn check(
+        &self,
+        test_id: u32,
+        test: &pom::Test,
+        solution_output: &Path,
+        run_dir: &Path,
+    ) -> Result<Verdict, Error> {
+        let checker_out_path = run_dir.join("checker-out.txt");
+        let correct_path = match &test.correct {
+            Some(correct) => self.resolve(correct),
+            None => {
+                let path = run_dir.join("correct.txt");
+                fs::File::create(&path).context(Io {})?;
+                path
+            }
+        };
+        let expose = |path: &Path, access| minion::PathExpositionOptions {
+            src: path.to_path_buf(),
+            dest: path.to_path_buf(),
+            access,
+        };
+        let limits = pom::Limits::default();
+        // isolation root must not be inside exposed `run_dir`
+        let dominion = self.new_dominion(
+            run_dir.with_extension("checker-root"),
+            &limits,
+            vec![
+                expose(self.out_dir, minion::DesiredAccess::Readonly),
+                expose(self.jjs_dir, minion::DesiredAccess::Readonly),
+                expose(run_dir, minion::DesiredAccess::Full),
+            ],
+        )?;
+        let mut cmd = minion::Command::new();
+        cmd.path("/bin/sh")
+            .arg("-c")
+            .arg(r#"exec 3<"$1" 4<"$2" 5<"$3" 6>"$4" 7>"$5"; shift 5; exec "$@""#)
+            .arg("sh")
+            .arg(self.resolve(&test.path))
+            .arg(correct_path)
+            .arg(solution_output)
+            .arg(&checker_out_path)
+            .arg(run_dir.join("checker-comment.txt"))
+            .arg(self.resolve(&self.manifest.checker_exe))
+            .args(&self.manifest.checker_cmd)
+            .current_dir(self.out_dir)
+            .env("JJS_TEST", "3")
+            .env("JJS_CORR", "4")
+            .env("JJS_SOL", "5")
+            .env("JJS_CHECKER_OUT", "6")
+            .env("JJS_CHECKER_COMMENT", "7")
+            .stdin(minion::InputSpecification::null())
+            .stdout(minion::OutputSpecification::ignore())
+            .stderr(minion::OutputSpecification::ignore())
+            .dominion(dominion);
+        let checker_error = |description: &str| Error::Checker {
+            test_id,
+            description: description.to_string(),
+        };
+        let mut child = match cmd.spawn(self.minion_backend) {
+            Ok(child) => child,
+            Err(err) if err.is_system() => return Err(err).context(Minion {}),
+            Err(_) => return Err(checker_error("checker couldn't be launched")),
+        };
+        match child
+            .wait_for_exit(self.time_limit(&limits))
+            .context(Minion {})?
+        {
+            minion::WaitOutcome::Timeout => {
+                child.kill().ok();
+                return Err(checker_error("checker exceeded time limit"));
+            }
+            minion::WaitOutcome::AlreadyFinished => unreachable!("not expected other to wait"),
+            minion::WaitOutcome::Exited => {
+                if child.get_exit_code().context(Minion {})? != Some(0) {
+                    return Err(checker_error("checker returned non-zero code"));
+                }
+            }
+        }
+        let checker_out = fs::read_to_string(&checker_out_path).context(Io {})?;
+        let outcome = checker_out
+            .lines()
+            .find(|line| line.starts_with("outcome="))
+            .map(|line| line["outcome=".len()..].trim());
+        match outcome {
+            Some("Ok") => Ok(Verdict::Ok),
+            Some("WrongAnswer") => Ok(Verdict::WrongAnswer),
+            Some("PresentationError") => Ok(Verdict::PresentationError),
+            Some(other) => Err(checker_error(&format!("checker reported {}", other))),
+            None => Err(checker_error("checker did not report outcome")),
+        }
+    }
+
+    fn judge_test(&self, solution_name: &str, test_id: u32) -> Result<Verdict, Error> {
+        let test = match self.manifest.tests.get((test_id as usize).wrapping_sub(1)) {
+            Some(test) => test,
+            None => {
+                return Err(Error::Valuer {
+                    description: format!("test {} requested, but it does not exist", test_id),
+                })
+            }
+        };
+        let run_dir = self
+            .work_dir
+            .join(format!("sol-{}", solution_name))
+            .join(test_id.to_string());
+        fs::create_dir_all(&run_dir).context(Io {})?;
+        let solution_dir = self
+            .out_dir
+            .join("assets")
+            .join(format!("sol-{}", solution_name));
+        let output = match self.run_solution(&solution_dir, test, &run_dir)? {
+            Ok(output) => output,
+            Err(verdict) => return Ok(verdict),
+        };
+        self.check(test_id, test, &output, &run_dir)
+    }
+
+    /// Judges solution as invoker does: valuer decides which tests are run and what score is.
+    pub(crate) fn verify_solution(
+        &self,
+        name: &str,
+        expected: Verdict,
+    ) -> Result<SolutionReport, Error> {
+        let solution_bin = self
+            .out_dir
+            .join("assets")
+            .join(format!("sol-{}", name))
+            .join("bin");
+        if !solution_bin.exists() {
+            return Err(Error::SolutionMissing { path: solution_bin });
+        }
+        let comment_handles = [
+            fs::File::create(self.work_dir.join("valuer-comment-pub.txt"))
+                .map(crate::inherit_handle)
+                .context(Io {})?,
+            fs::File::create(self.work_dir.join("valuer-comment-priv.txt"))
+                .map(crate::inherit_handle)
+                .context(Io {})?,
+        ];
+        let mut cmd = std::process::Command::new(self.resolve(&self.manifest.valuer_exe));
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .env("JJS_VALUER_COMMENT_PUB", comment_handles[0].to_string())
+            .env("JJS_VALUER_COMMENT_PRIV", comment_handles[1].to_string());
+        let valuer_cfg_dir = self.resolve(&self.manifest.valuer_cfg);
+        if valuer_cfg_dir.is_dir() {
+            cmd.current_dir(valuer_cfg_dir);
+        }
+        let child = cmd.spawn();
+        close_handles(&comment_handles);
+        let mut child = child.context(Io {})?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let res = drive_valuer(stdout, stdin, self.manifest.tests.len(), |test_id| {
+            self.judge_test(name, test_id)
+        });
+        child.kill().ok();
+        child.wait().ok();
+        let (score, tests) = res?;
+        Ok(SolutionReport {
+            name: name.to_string(),
+            expected,
+            score,
+            tests,
+        })
+    }
+}
+
+/// Talks to valuer (see valuer protocol in `man/src/problems/valuer.md`):
+/// runs tests it requests with `judge` and returns final score and test outcomes
+fn drive_valuer(
+    mut valuer_out: impl BufRead,
+    mut valuer_in: impl Write,
+    test_count: usize,
+    mut judge: impl FnMut(u32) -> Result<Verdict, Error>,
+) -> Result<(u32, Vec<(u32, Verdict)>), Error> {
+    let mut tests = Vec::new();
+    writeln!(valuer_in, "{} ", test_count).context(Io {})?;
+    valuer_in.flush().context(Io {})?;
+    let mut line = String::new();
+    let parse = |item: Option<&&str>| -> Result<u32, Error> {
+        item.and_then(|item| item.parse().ok())
+            .ok_or_else(|| Error::Valuer {
+                description: "malformed response".to_string(),
+            })
+    };
+    loop {
+        line.clear();
+        if valuer_out.read_line(&mut line).context(Io {})? == 0 {
+            return Err(Error::Valuer {
+                description: "valuer exited unexpectedly".to_string(),
+            });
+        }
+        let items: Vec<_> = line.split_whitespace().collect();
+        match items.first().copied() {
+            Some("RUN") => {
+                let test_id = parse(items.get(1))?;
+                let outcome = judge(test_id)?;
+                tests.push((test_id, outcome));
+                let status = test_status(outcome);
+                writeln!(valuer_in, "{} {} {}", test_id, status.kind, status.code)
+                    .context(Io {})?;
+                valuer_in.flush().context(Io {})?;
+            }
+            Some("DONE") => {
+                let score = parse(items.get(1))?;
+                return Ok((score, tests));
+            }
+            _ => {
+                return Err(Error::Valuer {
+                    description: format!("unexpected response: {}", line.trim()),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(expected: Verdict, score: u32, tests: &[Verdict]) -> SolutionReport {
+        SolutionReport {
+            name: "sol".to_string(),
+            expected,
+            score,
+            tests: (1..).zip(tests.iter().copied()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_report_matches() {
+        use Verdict::*;
+        assert!(report(Ok, 100, &[Ok, Ok]).matches());
+        assert!(!report(Ok, 50, &[Ok, Ok]).matches());
+        assert!(!report(Ok, 100, &[Ok, WrongAnswer]).matches());
+        assert!(report(Partial(50), 50, &[Ok, WrongAnswer]).matches());
+        assert!(!report(Partial(50), 40, &[Ok, WrongAnswer]).matches());
+        assert!(report(WrongAnswer, 0, &[Ok, WrongAnswer, WrongAnswer]).matches());
+        assert!(!report(WrongAnswer, 0, &[Ok, WrongAnswer, RuntimeError]).matches());
+        assert!(!report(WrongAnswer, 0, &[Ok, Ok]).matches());
+        assert!(!report(TimeLimitExceeded, 100, &[TimeLimitExceeded]).matches());
+
+        assert_eq!(report(Ok, 0, &[Ok, RuntimeError]).actual(), RuntimeError);
+        assert_eq!(report(Ok, 30, &[Ok]).actual(), Partial(30));
+        assert_eq!(report(WrongAnswer, 100, &[Ok]).actual(), Ok);
+    }
+
+    #[test]
+    fn test_drive_valuer() {
+        let valuer_out = "RUN 1\nRUN 2\nDONE 50\n";
+        let mut valuer_in = Vec::new();
+        let (score, tests) = drive_valuer(valuer_out.as_bytes(), &mut valuer_in, 2, |test_id| {
+            Ok(if test_id == 1 {
+                Verdict::Ok
+            } else {
+                Verdict::WrongAnswer
+            })
+        })
+        .unwrap();
+        assert_eq!(score, 50);
+        assert_eq!(tests, [(1, Verdict::Ok), (2, Verdict::WrongAnswer)]);
+        assert_eq!(
+            String::from_utf8(valuer_in).unwrap(),
+            format!(
+                "2 \n1 Accepted {}\n2 Rejected {}\n",
+                status_codes::TEST_PASSED,
+                status_codes::WRONG_ANSWER
+            )
+        );
+    }
+
+    #[test]
+    fn test_drive_valuer_errors() {
+        let is_valuer_error = |valuer_out: &str| {
+            drive_valuer(valuer_out.as_bytes(), Vec::new(), 1, |_| Ok(Verdict::Ok))
+                .unwrap_err()
+                .to_string()
+                .starts_with("valuer failed")
+        };
+        assert!(is_valuer_error("RUN 1\n"));
+        assert!(is_valuer_error("RUN x\n"));
+        assert!(is_valuer_error("HELLO\n"));
+        let err = drive_valuer("RUN 1\n".as_bytes(), Vec::new(), 1, |test_id| {
+            Err(Error::Checker {
+                test_id,
+                description: "failed".to_string(),
+            })
+        })
+        .unwrap_err();
+        match err {
+            Error::Checker { test_id: 1, .. } => {}
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
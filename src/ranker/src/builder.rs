@@ -0,0 +1,501 @@
+//! Incremental monitor construction
+use crate::{
+    Cell, Monitor, PartyId, PartyRow, PartyStats, ProblemConfig, ProblemId, ProblemStats, Ranking,
+    Run, RunId, RunScoreAggregation, RunScoreAggregationTarget, Score, StatsRow, SubtaskId, Time,
+    ATTEMPT_PENALTY,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Returned when run can not be added to monitor, e.g. because its score is
+/// greater than maximal possible one
+#[derive(Debug)]
+pub struct InvalidRunError {
+    pub run: RunId,
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidRunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid run {}: {}", self.run.0, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidRunError {}
+
+/// Multiset of scores, allowing to maintain maximum under insertions and removals
+#[derive(Debug, Default, Clone)]
+struct ScoreMultiset(BTreeMap<Score, u32>);
+
+impl ScoreMultiset {
+    fn add(&mut self, score: Score) {
+        *self.0.entry(score).or_insert(0) += 1;
+    }
+
+    fn remove(&mut self, score: Score) {
+        let cnt = self.0.get_mut(&score).expect("removed score was not added");
+        *cnt -= 1;
+        if *cnt == 0 {
+            self.0.remove(&score);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn max(&self) -> Option<Score> {
+        self.0.keys().next_back().copied()
+    }
+}
+
+/// Scores of set of runs, maintained in `O(log n)` per run
+#[derive(Debug, Default)]
+struct ScoreSet {
+    scores: ScoreMultiset,
+    subtasks: HashMap<SubtaskId, ScoreMultiset>,
+    /// Sum of maximal scores over subtasks
+    merged: Score,
+}
+
+impl ScoreSet {
+    fn add(&mut self, run: &Run) {
+        self.scores.add(run.score());
+        for (&subtask, &score) in &run.subtasks {
+            let scores = self.subtasks.entry(subtask).or_default();
+            let prev_best = scores.max().unwrap_or(0);
+            scores.add(score);
+            self.merged += scores.max().unwrap() - prev_best;
+        }
+    }
+
+    fn remove(&mut self, run: &Run) {
+        self.scores.remove(run.score());
+        for (subtask, &score) in &run.subtasks {
+            let scores = self.subtasks.get_mut(subtask).unwrap();
+            let prev_best = scores.max().unwrap();
+            scores.remove(score);
+            self.merged -= prev_best - scores.max().unwrap_or(0);
+            if scores.is_empty() {
+                self.subtasks.remove(subtask);
+            }
+        }
+    }
+
+    fn score(&self, aggregation: &RunScoreAggregation) -> Score {
+        match aggregation {
+            RunScoreAggregation::Max => self.scores.max().unwrap_or(0),
+            RunScoreAggregation::MergeSubtasks => self.merged,
+        }
+    }
+}
+
+pub(crate) fn empty_cell() -> Cell {
+    Cell {
+        empty: true,
+        ok: false,
+        score: 0,
+        // TODO
+        marked: false,
+        attempts: 0,
+        pending: 0,
+        penalty: 0,
+    }
+}
+
+fn is_accepted(run: &Run, problem: &ProblemConfig) -> bool {
+    run.score() >= problem.accepted_score
+}
+
+/// Contest times, which affect cells
+#[derive(Debug, Default, Clone, Copy)]
+struct Timing {
+    start_time: Option<Time>,
+    freeze_time: Option<Time>,
+}
+
+fn is_frozen(run: &Run, timing: Timing) -> bool {
+    match timing.freeze_time {
+        Some(freeze_time) => run.time >= freeze_time,
+        None => false,
+    }
+}
+
+/// Set of runs of one party for one problem
+#[derive(Debug, Default)]
+struct RunSet {
+    /// Ordered by run id, i.e. by submission time
+    runs: BTreeMap<RunId, Run>,
+    /// Runs which are full solutions
+    accepted: BTreeSet<RunId>,
+    /// Runs which are not full solutions
+    failed: BTreeSet<RunId>,
+    scores: ScoreSet,
+}
+
+impl RunSet {
+    fn insert(&mut self, id: RunId, run: Run, problem: &ProblemConfig) {
+        if is_accepted(&run, problem) {
+            self.accepted.insert(id);
+        } else {
+            self.failed.insert(id);
+        }
+        self.scores.add(&run);
+        self.runs.insert(id, run);
+    }
+
+    fn remove(&mut self, id: RunId) -> Option<Run> {
+        let run = self.runs.remove(&id)?;
+        self.accepted.remove(&id);
+        self.failed.remove(&id);
+        self.scores.remove(&run);
+        Some(run)
+    }
+
+    fn calculate_cell(&self, problem: &ProblemConfig, timing: Timing) -> Cell {
+        let mut cell = empty_cell();
+        cell.empty = self.runs.is_empty();
+        cell.score = match problem.score_runs {
+            RunScoreAggregationTarget::All => self.scores.score(&problem.aggregation),
+            // best run's score is sum of its subtask scores, so both aggregations
+            // give maximal run score
+            RunScoreAggregationTarget::Best => self.scores.score(&RunScoreAggregation::Max),
+            RunScoreAggregationTarget::Latest(cnt) => {
+                let mut latest = ScoreSet::default();
+                for run in self.runs.values().rev().take(cnt as usize) {
+                    latest.add(run);
+                }
+                latest.score(&problem.aggregation)
+            }
+        };
+        cell.ok = cell.score >= problem.accepted_score;
+        if !cell.ok {
+            cell.attempts = self.failed.len() as u32;
+            return cell;
+        }
+        // Problem is solved at first full solution. If problem was solved by
+        // merging several runs, there is no such run, so latest run is used.
+        let (solution_id, solution_time) = match self.accepted.iter().next() {
+            Some(id) => (*id, self.runs[id].time),
+            None => {
+                let (id, run) = self.runs.iter().next_back().unwrap();
+                (*id, run.time)
+            }
+        };
+        cell.attempts = self.failed.range(..=solution_id).count() as u32;
+        let solution_minutes = match timing.start_time {
+            Some(start_time) => solution_time.saturating_sub(start_time) / 60,
+            None => 0,
+        };
+        cell.penalty = solution_minutes as u32 + cell.attempts * ATTEMPT_PENALTY;
+        cell
+    }
+}
+
+/// All runs of one party for one problem, together with computed cell
+#[derive(Debug)]
+struct CellData {
+    runs: RunSet,
+    /// Runs submitted before freeze
+    visible_runs: RunSet,
+    cell: Cell,
+    /// Cell as seen by contestants, i.e. without runs submitted after freeze
+    frozen_cell: Cell,
+}
+
+impl CellData {
+    fn new() -> CellData {
+        CellData {
+            runs: RunSet::default(),
+            visible_runs: RunSet::default(),
+            cell: empty_cell(),
+            frozen_cell: empty_cell(),
+        }
+    }
+
+    fn insert(&mut self, id: RunId, run: Run, problem: &ProblemConfig, timing: Timing) {
+        if !is_frozen(&run, timing) {
+            self.visible_runs.insert(id, run.clone(), problem);
+        }
+        self.runs.insert(id, run, problem);
+        self.update_cells(problem, timing);
+    }
+
+    fn remove(&mut self, id: RunId, problem: &ProblemConfig, timing: Timing) -> Run {
+        self.visible_runs.remove(id);
+        let run = self.runs.remove(id).expect("run not found in its cell");
+        self.update_cells(problem, timing);
+        run
+    }
+
+    /// Recalculates set of visible runs. This operation is linear in run count.
+    fn refreeze(&mut self, problem: &ProblemConfig, timing: Timing) {
+        self.visible_runs = RunSet::default();
+        for (&id, run) in &self.runs.runs {
+            if !is_frozen(run, timing) {
+                self.visible_runs.insert(id, run.clone(), problem);
+            }
+        }
+        self.update_cells(problem, timing);
+    }
+
+    fn update_cells(&mut self, problem: &ProblemConfig, timing: Timing) {
+        self.cell = self.runs.calculate_cell(problem, timing);
+        self.frozen_cell = match timing.freeze_time {
+            Some(_) => {
+                let mut cell = self.visible_runs.calculate_cell(problem, timing);
+                cell.empty = self.runs.runs.is_empty();
+                cell.pending = (self.runs.runs.len() - self.visible_runs.runs.len()) as u32;
+                cell
+            }
+            None => self.cell.clone(),
+        };
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProblemStatsData {
+    total_runs: u32,
+    accepted_runs: u32,
+    /// Used to maintain maximal score on run removal
+    scores: ScoreMultiset,
+}
+
+impl ProblemStatsData {
+    fn add(&mut self, score: Score, accepted: bool) {
+        self.total_runs += 1;
+        if accepted {
+            self.accepted_runs += 1;
+        }
+        self.scores.add(score);
+    }
+
+    fn remove(&mut self, score: Score, accepted: bool) {
+        self.total_runs -= 1;
+        if accepted {
+            self.accepted_runs -= 1;
+        }
+        self.scores.remove(score);
+    }
+
+    fn to_stats(&self) -> ProblemStats {
+        ProblemStats {
+            total_runs: self.total_runs,
+            accepted_runs: self.accepted_runs,
+            max_score: self.scores.max().unwrap_or(0),
+        }
+    }
+}
+
+/// Maintains monitor state, allowing to add, update and remove runs one by one.
+///
+/// Updating run costs `O(s log n)`, where `n` is total run count and `s` is
+/// count of subtasks. For problems with `RunScoreAggregationTarget::Latest(cnt)`
+/// additional `O(s cnt)` is spent.
+#[derive(Debug)]
+pub struct MonitorBuilder {
+    problems: HashMap<ProblemId, ProblemConfig>,
+    parties: BTreeSet<PartyId>,
+    /// Maps run to cell it belongs to
+    run_locations: HashMap<RunId, (PartyId, ProblemId)>,
+    cells: HashMap<(PartyId, ProblemId), CellData>,
+    stats: HashMap<ProblemId, ProblemStatsData>,
+    /// Same as `stats`, but only accounts for runs submitted before freeze
+    frozen_stats: HashMap<ProblemId, ProblemStatsData>,
+    timing: Timing,
+}
+
+impl MonitorBuilder {
+    pub fn new(problems: &[(ProblemId, ProblemConfig)]) -> MonitorBuilder {
+        MonitorBuilder {
+            problems: problems.iter().cloned().collect(),
+            parties: BTreeSet::new(),
+            run_locations: HashMap::new(),
+            cells: HashMap::new(),
+            stats: problems
+                .iter()
+                .map(|(id, _)| (*id, ProblemStatsData::default()))
+                .collect(),
+            frozen_stats: problems
+                .iter()
+                .map(|(id, _)| (*id, ProblemStatsData::default()))
+                .collect(),
+            timing: Timing::default(),
+        }
+    }
+
+    /// Sets contest start time, which is used to calculate penalty.
+    ///
+    /// If start time is not set, penalty only accounts for rejected attempts.
+    /// This operation is linear in total run count.
+    pub fn set_start_time(&mut self, start_time: Option<Time>) {
+        self.timing.start_time = start_time;
+        for (&(_, problem), cell_data) in &mut self.cells {
+            cell_data.update_cells(&self.problems[&problem], self.timing);
+        }
+    }
+
+    /// Sets time, starting from which run results are hidden in `monitor`.
+    ///
+    /// `None` means that standings are not frozen.
+    /// This operation is linear in total run count.
+    pub fn set_freeze_time(&mut self, freeze_time: Option<Time>) {
+        self.timing.freeze_time = freeze_time;
+        for data in self.frozen_stats.values_mut() {
+            *data = ProblemStatsData::default();
+        }
+        for (&(_, problem), cell_data) in &mut self.cells {
+            let problem_config = &self.problems[&problem];
+            cell_data.refreeze(problem_config, self.timing);
+            let frozen_stats = self.frozen_stats.get_mut(&problem).unwrap();
+            for run in cell_data.visible_runs.runs.values() {
+                frozen_stats.add(run.score(), is_accepted(run, problem_config));
+            }
+        }
+    }
+
+    /// Adds party to monitor, even if it has no runs
+    pub fn add_party(&mut self, party: PartyId) {
+        self.parties.insert(party);
+    }
+
+    /// Adds new run or replaces previous version of run with the same id.
+    ///
+    /// Party of run is added to monitor if needed.
+    /// Runs for problems that were not passed to `new` are ignored.
+    /// On error monitor is not modified.
+    pub fn upsert_run(&mut self, id: RunId, run: Run) -> Result<(), InvalidRunError> {
+        let problem = match self.problems.get(&run.problem) {
+            Some(problem) => problem,
+            None => return Ok(()),
+        };
+        if run.score() > problem.accepted_score {
+            return Err(InvalidRunError {
+                run: id,
+                reason: format!(
+                    "score is {}, but maximal possible score is {}",
+                    run.score(),
+                    problem.accepted_score
+                ),
+            });
+        }
+        if let Some((subtask, score)) = run.subtasks.iter().find(|(_, &score)| score < 0) {
+            return Err(InvalidRunError {
+                run: id,
+                reason: format!("subtask {} has negative score {}", subtask.0, score),
+            });
+        }
+        self.remove_run(id);
+        let accepted = is_accepted(&run, &self.problems[&run.problem]);
+        self.stats
+            .get_mut(&run.problem)
+            .unwrap()
+            .add(run.score(), accepted);
+        if !is_frozen(&run, self.timing) {
+            self.frozen_stats
+                .get_mut(&run.problem)
+                .unwrap()
+                .add(run.score(), accepted);
+        }
+        self.parties.insert(run.party);
+        let location = (run.party, run.problem);
+        self.run_locations.insert(id, location);
+        self.cells
+            .entry(location)
+            .or_insert_with(CellData::new)
+            .insert(id, run, &self.problems[&location.1], self.timing);
+        Ok(())
+    }
+
+    /// Removes run. Does nothing if run is not known.
+    pub fn remove_run(&mut self, id: RunId) {
+        let location = match self.run_locations.remove(&id) {
+            Some(loc) => loc,
+            None => return,
+        };
+        let problem = &self.problems[&location.1];
+        let cell_data = self.cells.get_mut(&location).unwrap();
+        let run = cell_data.remove(id, problem, self.timing);
+        let accepted = is_accepted(&run, problem);
+        self.stats
+            .get_mut(&location.1)
+            .unwrap()
+            .remove(run.score(), accepted);
+        if !is_frozen(&run, self.timing) {
+            self.frozen_stats
+                .get_mut(&location.1)
+                .unwrap()
+                .remove(run.score(), accepted);
+        }
+    }
+
+    /// Produces monitor snapshot, as seen by contestants.
+    ///
+    /// If freeze time is set, results of runs submitted after it are hidden.
+    pub fn monitor(&self) -> Monitor {
+        self.make_monitor(true, None)
+    }
+
+    /// Produces monitor snapshot, ignoring freeze
+    pub fn unfrozen_monitor(&self) -> Monitor {
+        self.make_monitor(false, None)
+    }
+
+    /// Same as `monitor`, but results of `party` are not hidden.
+    ///
+    /// This is how monitor is seen by contestant: own results are always visible.
+    pub fn monitor_for_party(&self, party: PartyId) -> Monitor {
+        self.make_monitor(true, Some(party))
+    }
+
+    /// Contest is ranked by score if some problem merges subtasks of runs
+    fn ranking(&self) -> Ranking {
+        let merges_subtasks = self
+            .problems
+            .values()
+            .any(|problem| match problem.aggregation {
+                RunScoreAggregation::MergeSubtasks => true,
+                RunScoreAggregation::Max => false,
+            });
+        if merges_subtasks {
+            Ranking::Ioi
+        } else {
+            Ranking::Icpc
+        }
+    }
+
+    fn make_monitor(&self, frozen: bool, unfrozen_party: Option<PartyId>) -> Monitor {
+        let mut parties = HashMap::new();
+        for &party in &self.parties {
+            let hide_frozen = frozen && unfrozen_party != Some(party);
+            let mut row = PartyRow {
+                stats: PartyStats { color: 0 },
+                problems: HashMap::new(),
+            };
+            for &problem in self.problems.keys() {
+                let cell = match self.cells.get(&(party, problem)) {
+                    Some(cell_data) if hide_frozen => cell_data.frozen_cell.clone(),
+                    Some(cell_data) => cell_data.cell.clone(),
+                    None => empty_cell(),
+                };
+                row.problems.insert(problem, cell);
+            }
+            parties.insert(party, row);
+        }
+        let stats = if frozen {
+            &self.frozen_stats
+        } else {
+            &self.stats
+        };
+        let stats = StatsRow {
+            problems: stats
+                .iter()
+                .map(|(&id, data)| (id, data.to_stats()))
+                .collect(),
+        };
+        Monitor {
+            parties,
+            stats,
+            ranking: self.ranking(),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Rendering monitor to formats, suitable for publishing
+use crate::{party_totals, Cell, Monitor, PartyId, ProblemId, Score};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+/// Penalty for each rejected attempt on solved problem
+pub const ATTEMPT_PENALTY: u32 = 20;
+
+/// Human-readable names of problems and parties
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    /// Problem codes, in order in which columns should appear
+    pub problems: Vec<(ProblemId, String)>,
+    /// Party names. Parties without name are shown by id
+    pub parties: HashMap<PartyId, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Csv,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct UnknownFormatError(pub String);
+
+impl std::fmt::Display for UnknownFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown export format '{}' (supported: html, csv, json)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownFormatError {}
+
+impl FromStr for ExportFormat {
+    type Err = UnknownFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(ExportFormat::Html),
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(UnknownFormatError(s.to_string())),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// MIME type of rendered standings
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+/// One cell of exported standings
+#[derive(Debug, Serialize)]
+pub struct StandingsCell {
+    /// True if party haven't attempted to solve problem
+    pub empty: bool,
+    /// True if problem is solved
+    pub ok: bool,
+    pub score: Score,
+    /// Count of rejected attempts
+    pub attempts: u32,
+    /// Count of attempts, hidden because standings are frozen
+    pub pending: u32,
+    /// If problem is solved, minutes from contest start to solution plus
+    /// `ATTEMPT_PENALTY` for each rejected attempt
+    pub penalty: u32,
+}
+
+/// One row of exported standings
+#[derive(Debug, Serialize)]
+pub struct StandingsRow {
+    /// 1-based place. Parties with equal results share place
+    pub place: u32,
+    #[serde(skip)]
+    pub party_id: PartyId,
+    pub party: String,
+    pub solved: u32,
+    pub score: Score,
+    pub penalty: u32,
+    /// `cells[i]` corresponds to `Standings::problems[i]`
+    pub cells: Vec<StandingsCell>,
+}
+
+/// Standings, ready for publishing.
+///
+/// JSON export is serialized form of this struct, so its layout is
+/// part of public interface and must be changed carefully:
+/// ```text
+/// {
+///     "problems": ["A", "B"],
+///     "rows": [{
+///         "place": 1, "party": "alice", "solved": 1, "score": 100, "penalty": 20,
+///         "cells": [
+///             {"empty": false, "ok": true, "score": 100, "attempts": 1, "pending": 0, "penalty": 20},
+///             {"empty": true, "ok": false, "score": 0, "attempts": 0, "pending": 0, "penalty": 0}
+///         ]
+///     }]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+pub struct Standings {
+    /// Problem codes
+    pub problems: Vec<String>,
+    /// Rows, ordered by place
+    pub rows: Vec<StandingsRow>,
+}
+
+fn export_cell(cell: &Cell) -> StandingsCell {
+    StandingsCell {
+        empty: cell.empty,
+        ok: cell.ok,
+        score: cell.score,
+        attempts: cell.attempts,
+        pending: cell.pending,
+        penalty: cell.penalty,
+    }
+}
+
+/// Short textual representation of cell, e.g. `+2` or `-1 ?3`
+fn cell_text(cell: &StandingsCell) -> String {
+    let mut text = if cell.empty {
+        String::new()
+    } else if cell.ok {
+        match cell.attempts {
+            0 => "+".to_string(),
+            cnt => format!("+{}", cnt),
+        }
+    } else if cell.score > 0 {
+        cell.score.to_string()
+    } else if cell.attempts > 0 {
+        format!("-{}", cell.attempts)
+    } else {
+        String::new()
+    };
+    if cell.pending > 0 {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        write!(text, "?{}", cell.pending).unwrap();
+    }
+    text
+}
+
+fn cell_class(cell: &StandingsCell) -> &'static str {
+    if cell.pending > 0 {
+        "pending"
+    } else if cell.ok {
+        "ok"
+    } else if cell.empty {
+        "empty"
+    } else {
+        "fail"
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Standings {
+    pub fn new(monitor: &Monitor, labels: &Labels) -> Standings {
+        let mut rows = Vec::new();
+        for (party, party_row) in &monitor.parties {
+            let cells = labels
+                .problems
+                .iter()
+                .map(|(problem, _)| match party_row.problems.get(problem) {
+                    Some(cell) => export_cell(cell),
+                    None => export_cell(&crate::builder::empty_cell()),
+                })
+                .collect::<Vec<_>>();
+            let (solved, score, _) = party_totals(&party_row.problems);
+            let name = match labels.parties.get(party) {
+                Some(name) => name.clone(),
+                None => party.0.to_string(),
+            };
+            rows.push(StandingsRow {
+                place: 0,
+                party_id: *party,
+                party: name,
+                solved,
+                score,
+                penalty: cells.iter().map(|cell| cell.penalty).sum(),
+                cells,
+            });
+        }
+        let result_key = |row: &StandingsRow| {
+            std::cmp::Reverse(monitor.ranking.key(row.solved, row.score, row.penalty))
+        };
+        rows.sort_by(|a, b| {
+            result_key(a)
+                .cmp(&result_key(b))
+                .then_with(|| a.party.cmp(&b.party))
+        });
+        for i in 0..rows.len() {
+            rows[i].place = if i > 0 && result_key(&rows[i - 1]) == result_key(&rows[i]) {
+                rows[i - 1].place
+            } else {
+                i as u32 + 1
+            };
+        }
+        Standings {
+            problems: labels
+                .problems
+                .iter()
+                .map(|(_, code)| code.clone())
+                .collect(),
+            rows,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        let mut header = vec!["place".to_string(), "party".to_string()];
+        header.extend(self.problems.iter().map(|code| escape_csv(code)));
+        header.extend(vec![
+            "solved".to_string(),
+            "score".to_string(),
+            "penalty".to_string(),
+        ]);
+        writeln!(out, "{}", header.join(",")).unwrap();
+        for row in &self.rows {
+            let mut line = vec![row.place.to_string(), escape_csv(&row.party)];
+            line.extend(row.cells.iter().map(|cell| escape_csv(&cell_text(cell))));
+            line.extend(vec![
+                row.solved.to_string(),
+                row.score.to_string(),
+                row.penalty.to_string(),
+            ]);
+            writeln!(out, "{}", line.join(",")).unwrap();
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>Standings</title>\n</head>\n<body>\n");
+        out.push_str("<table class=\"standings\">\n<thead>\n<tr><th>Place</th><th>Party</th>");
+        for code in &self.problems {
+            write!(out, "<th>{}</th>", escape_html(code)).unwrap();
+        }
+        out.push_str("<th>Solved</th><th>Score</th><th>Penalty</th></tr>\n</thead>\n<tbody>\n");
+        for row in &self.rows {
+            write!(
+                out,
+                "<tr><td>{}</td><td>{}</td>",
+                row.place,
+                escape_html(&row.party)
+            )
+            .unwrap();
+            for cell in &row.cells {
+                write!(
+                    out,
+                    "<td class=\"{}\">{}</td>",
+                    cell_class(cell),
+                    escape_html(&cell_text(cell))
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "<td>{}</td><td>{}</td><td>{}</td></tr>",
+                row.solved, row.score, row.penalty
+            )
+            .unwrap();
+        }
+        out.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+        out
+    }
+
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Html => self.to_html(),
+            ExportFormat::Csv => self.to_csv(),
+            ExportFormat::Json => self.to_json(),
+        }
+    }
+}
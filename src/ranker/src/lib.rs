@@ -1,8 +1,18 @@
 //! Ranker is library, responsible for generating monitor
 //! Is is used in both frontend and invoker
 
+mod builder;
+mod export;
+mod resolver;
+
+pub use builder::{InvalidRunError, MonitorBuilder};
+pub use export::{
+    ExportFormat, Labels, Standings, StandingsCell, StandingsRow, UnknownFormatError,
+    ATTEMPT_PENALTY,
+};
+pub use resolver::{reveal_sequence, RevealStep};
 use serde::Serialize;
-use std::{cmp::Ordering, collections::HashMap, num::NonZeroU32};
+use std::{cmp::Reverse, collections::HashMap, num::NonZeroU32};
 
 #[derive(Hash, Ord, PartialOrd, Eq, PartialEq, Debug, Serialize, Copy, Clone)]
 pub struct SubtaskId(pub NonZeroU32);
@@ -18,15 +28,27 @@ pub struct PartyId(pub NonZeroU32);
 
 pub type Score = i32;
 
-#[derive(Debug)]
+/// Point in time in seconds, e.g. seconds since unix epoch.
+pub type Time = u64;
+
+#[derive(Debug, Clone)]
 pub struct Run {
     pub subtasks: HashMap<SubtaskId, Score>,
     pub party: PartyId,
     pub problem: ProblemId,
+    /// Submission time
+    pub time: Time,
+}
+
+impl Run {
+    /// Total score of run, i.e. sum of scores over all subtasks
+    pub fn score(&self) -> Score {
+        self.subtasks.values().sum()
+    }
 }
 
 /// Represents one cell in monitor
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Cell {
     /// True if party haven't attempted to solve problem
     pub empty: bool,
@@ -41,6 +63,11 @@ pub struct Cell {
     ///
     /// E.g. not accounts for runs after full solution
     pub attempts: u32,
+    /// Count of runs, results of which are hidden because standings are frozen
+    pub pending: u32,
+    /// If problem is solved, minutes from contest start to solution plus
+    /// `ATTEMPT_PENALTY` for each rejected attempt. Otherwise zero.
+    pub penalty: u32,
 }
 
 /// Represents some properties of row, describing party
@@ -74,20 +101,27 @@ pub struct StatsRow {
     pub problems: HashMap<ProblemId, ProblemStats>,
 }
 
-#[derive(Debug)]
+/// Describes which runs are taken into account when computing problem score
+#[derive(Debug, Clone)]
 pub enum RunScoreAggregationTarget {
+    /// Only `n` latest runs are considered
     Latest(u32),
+    /// Only run with maximal score is considered
     Best,
     All,
 }
 
-#[derive(Debug)]
+/// Describes how scores of several runs are combined into problem score
+#[derive(Debug, Clone)]
 pub enum RunScoreAggregation {
+    /// Problem score is maximal score of considered runs
     Max,
+    /// For each subtask, maximal score among considered runs is selected.
+    /// Problem score is sum of these values.
     MergeSubtasks,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ProblemConfig {
     pub name: String,
     pub accepted_score: Score,
@@ -95,124 +129,528 @@ pub struct ProblemConfig {
     pub aggregation: RunScoreAggregation,
 }
 
-#[derive(Debug)]
-pub enum PenaltyAggregation {
-    Sum,
-    Max,
-}
-
-#[derive(Debug)]
-pub enum ProblemScoreAggregationTarget {
-    All,
-    Best(u32),
+/// Describes how parties are ordered in monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Ranking {
+    /// By solved problem count, then by penalty
+    Icpc,
+    /// By total score
+    Ioi,
 }
 
-#[derive(Debug)]
-pub struct Config {
-    pub penalty_aggregation: PenaltyAggregation,
-    pub score_problems: ProblemScoreAggregationTarget,
+impl Ranking {
+    /// Key, by which parties are ordered. Greater is better.
+    pub fn key(self, solved: u32, score: Score, penalty: u32) -> (i64, Reverse<u32>) {
+        match self {
+            Ranking::Icpc => (i64::from(solved), Reverse(penalty)),
+            Ranking::Ioi => (i64::from(score), Reverse(0)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct Monitor {
     pub parties: HashMap<PartyId, PartyRow>,
     pub stats: StatsRow,
+    pub ranking: Ranking,
+}
+
+/// Returns solved problem count, total score and total penalty
+pub(crate) fn party_totals(cells: &HashMap<ProblemId, Cell>) -> (u32, Score, u32) {
+    let solved = cells.values().filter(|cell| cell.ok).count() as u32;
+    let score = cells.values().map(|cell| cell.score).sum();
+    let penalty = cells.values().map(|cell| cell.penalty).sum();
+    (solved, score, penalty)
+}
+
+/// Key, by which parties are ordered. Greater is better.
+pub(crate) fn rank_key(ranking: Ranking, cells: &HashMap<ProblemId, Cell>) -> (i64, Reverse<u32>) {
+    let (solved, score, penalty) = party_totals(cells);
+    ranking.key(solved, score, penalty)
 }
 
 /// Builds a `Monitor`, given list of all runs
-// Probably, later some means to build this incrementally will be implemented
+///
+/// Runs are expected to be ordered by submission time, which matters
+/// for `RunScoreAggregationTarget::Latest`.
+/// See `MonitorBuilder` if monitor should be updated incrementally.
 pub fn build_monitor(
     runs: &[Run],
     problems: &[(ProblemId, ProblemConfig)],
     parties: &[PartyId],
-    _config: &Config,
-) -> Monitor {
-    let mut party_info = HashMap::new();
-    let mut runs_by_party_and_problem = HashMap::new();
+) -> Result<Monitor, InvalidRunError> {
+    let mut builder = MonitorBuilder::new(problems);
+    for &party in parties {
+        builder.add_party(party);
+    }
     for (i, run) in runs.iter().enumerate() {
-        let k = (run.party, run.problem);
-        runs_by_party_and_problem
-            .entry(k)
-            .or_insert_with(Vec::new)
-            .push(i);
-    }
-    let mut cell_by_party_and_problem = HashMap::new();
-
-    let mut stats = StatsRow {
-        problems: HashMap::new(),
-    };
-    for problem in problems {
-        stats.problems.insert(
-            problem.0,
-            ProblemStats {
-                total_runs: 0,
-                accepted_runs: 0,
-                max_score: 0,
-            },
-        );
+        if !parties.contains(&run.party) {
+            continue;
+        }
+        let id = RunId(NonZeroU32::new(i as u32 + 1).unwrap());
+        builder.upsert_run(id, run.clone())?;
     }
+    Ok(builder.monitor())
+}
 
-    for &party in parties {
-        for problem in problems {
-            let mut cell = Cell {
-                empty: true,
-                ok: false,
-                score: 0,
-                // TODO
-                marked: false,
-                attempts: 0,
-            };
-            let empty_run_ids = Vec::new();
-            let run_ids = match runs_by_party_and_problem.get(&(party, problem.0)) {
-                Some(ids) => ids,
-                None => &empty_run_ids,
-            };
-            let problem_stats = stats.problems.get_mut(&problem.0).unwrap();
-            for &run_serial_id in run_ids {
-                let run = &runs[run_serial_id];
-                cell.empty = false;
-                let mut run_score = 0;
-                for &sc in run.subtasks.values() {
-                    run_score += sc;
-                }
-                cell.score = std::cmp::max(cell.score, run_score);
-                problem_stats.total_runs += 1;
-                match run_score.cmp(&problem.1.accepted_score) {
-                    Ordering::Less => {
-                        cell.attempts += 1;
-                    }
-                    Ordering::Equal => {
-                        cell.ok = true;
-                        problem_stats.accepted_runs += 1;
-                    }
-                    Ordering::Greater => {
-                        // TODO handle error gracefully
-                        panic!("run's score is more then total possible")
-                    }
-                }
-                problem_stats.max_score = std::cmp::max(problem_stats.max_score, run_score);
-            }
-            cell_by_party_and_problem.insert((party, problem.0), cell);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(x: u32) -> NonZeroU32 {
+        NonZeroU32::new(x).unwrap()
+    }
+
+    fn run(party: u32, subtasks: &[Score]) -> Run {
+        Run {
+            subtasks: subtasks
+                .iter()
+                .enumerate()
+                .map(|(i, &score)| (SubtaskId(id(i as u32 + 1)), score))
+                .collect(),
+            party: PartyId(id(party)),
+            problem: ProblemId(id(1)),
+            time: 0,
         }
     }
-    for &party in parties {
-        let stats = PartyStats { color: 0 };
-        let mut row = PartyRow {
-            stats,
-            problems: HashMap::new(),
+
+    fn cell_score(
+        runs: &[Run],
+        score_runs: RunScoreAggregationTarget,
+        aggregation: RunScoreAggregation,
+    ) -> (Score, bool) {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs,
+            aggregation,
         };
-        for problem in problems {
-            row.problems.insert(
-                problem.0,
-                cell_by_party_and_problem
-                    .remove(&(party, problem.0))
-                    .unwrap(),
-            );
-        }
-        party_info.insert(party, row);
+        let monitor = build_monitor(
+            runs,
+            &[(ProblemId(id(1)), problem)],
+            &[PartyId(id(1)), PartyId(id(2))],
+        )
+        .unwrap();
+        let cell = &monitor.parties[&PartyId(id(1))].problems[&ProblemId(id(1))];
+        (cell.score, cell.ok)
+    }
+
+    #[test]
+    fn test_merge_subtasks() {
+        let runs = vec![
+            run(1, &[30, 0, 10]),
+            run(2, &[30, 40, 30]),
+            run(1, &[0, 40, 0]),
+        ];
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::All,
+                RunScoreAggregation::Max
+            ),
+            (40, false)
+        );
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::All,
+                RunScoreAggregation::MergeSubtasks
+            ),
+            (80, false)
+        );
+        let runs = vec![run(1, &[30, 0, 10]), run(1, &[0, 40, 30])];
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::All,
+                RunScoreAggregation::MergeSubtasks
+            ),
+            (100, true)
+        );
+    }
+
+    #[test]
+    fn test_latest_runs() {
+        let runs = vec![
+            run(1, &[30, 40, 0]),
+            run(1, &[0, 0, 30]),
+            run(1, &[30, 0, 0]),
+        ];
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::Latest(2),
+                RunScoreAggregation::MergeSubtasks
+            ),
+            (60, false)
+        );
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::Latest(5),
+                RunScoreAggregation::MergeSubtasks
+            ),
+            (100, true)
+        );
+        assert_eq!(
+            cell_score(
+                &runs,
+                RunScoreAggregationTarget::Best,
+                RunScoreAggregation::MergeSubtasks
+            ),
+            (70, false)
+        );
     }
-    Monitor {
-        parties: party_info,
-        stats,
+
+    #[test]
+    fn test_incremental_update() {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs: RunScoreAggregationTarget::All,
+            aggregation: RunScoreAggregation::MergeSubtasks,
+        };
+        let mut builder = MonitorBuilder::new(&[(ProblemId(id(1)), problem)]);
+        let run_id = |x| RunId(id(x));
+        let cell = |builder: &MonitorBuilder| {
+            let monitor = builder.monitor();
+            let cell = &monitor.parties[&PartyId(id(1))].problems[&ProblemId(id(1))];
+            let stats = &monitor.stats.problems[&ProblemId(id(1))];
+            (cell.score, cell.attempts, stats.total_runs, stats.max_score)
+        };
+
+        builder.upsert_run(run_id(1), run(1, &[0, 0])).unwrap();
+        builder.upsert_run(run_id(2), run(1, &[0, 0])).unwrap();
+        assert_eq!(cell(&builder), (0, 2, 2, 0));
+        // run was judged
+        builder.upsert_run(run_id(1), run(1, &[50, 0])).unwrap();
+        assert_eq!(cell(&builder), (50, 2, 2, 50));
+        builder.upsert_run(run_id(2), run(1, &[0, 50])).unwrap();
+        assert_eq!(cell(&builder), (100, 2, 2, 50));
+        builder.remove_run(run_id(1));
+        assert_eq!(cell(&builder), (50, 1, 1, 50));
+        builder.remove_run(run_id(2));
+        assert_eq!(cell(&builder), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_invalid_run() {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs: RunScoreAggregationTarget::All,
+            aggregation: RunScoreAggregation::Max,
+        };
+        let mut builder = MonitorBuilder::new(&[(ProblemId(id(1)), problem)]);
+        builder.upsert_run(RunId(id(1)), run(1, &[40])).unwrap();
+        assert!(builder.upsert_run(RunId(id(1)), run(1, &[60, 50])).is_err());
+        assert!(builder.upsert_run(RunId(id(2)), run(1, &[-1])).is_err());
+        let monitor = builder.monitor();
+        let cell = &monitor.parties[&PartyId(id(1))].problems[&ProblemId(id(1))];
+        assert_eq!((cell.score, cell.attempts), (40, 1));
+    }
+
+    #[test]
+    fn test_incremental_latest() {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs: RunScoreAggregationTarget::Latest(2),
+            aggregation: RunScoreAggregation::MergeSubtasks,
+        };
+        let mut builder = MonitorBuilder::new(&[(ProblemId(id(1)), problem)]);
+        let score = |builder: &MonitorBuilder| {
+            builder.monitor().parties[&PartyId(id(1))].problems[&ProblemId(id(1))].score
+        };
+        builder.upsert_run(RunId(id(1)), run(1, &[50, 0])).unwrap();
+        builder.upsert_run(RunId(id(2)), run(1, &[0, 20])).unwrap();
+        assert_eq!(score(&builder), 70);
+        builder.upsert_run(RunId(id(3)), run(1, &[10, 0])).unwrap();
+        assert_eq!(score(&builder), 30);
+        // rejudge of run which is not among latest does not change score
+        builder.upsert_run(RunId(id(1)), run(1, &[50, 50])).unwrap();
+        assert_eq!(score(&builder), 30);
+        builder.remove_run(RunId(id(3)));
+        assert_eq!(score(&builder), 100);
+    }
+
+    #[test]
+    fn test_freeze() {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs: RunScoreAggregationTarget::Best,
+            aggregation: RunScoreAggregation::Max,
+        };
+        let mut builder = MonitorBuilder::new(&[(ProblemId(id(1)), problem)]);
+        builder
+            .upsert_run(
+                RunId(id(1)),
+                Run {
+                    time: 10,
+                    ..run(1, &[40])
+                },
+            )
+            .unwrap();
+        builder
+            .upsert_run(
+                RunId(id(2)),
+                Run {
+                    time: 20,
+                    ..run(1, &[100])
+                },
+            )
+            .unwrap();
+        builder.set_freeze_time(Some(15));
+        let cell = |monitor: &Monitor| {
+            let cell = &monitor.parties[&PartyId(id(1))].problems[&ProblemId(id(1))];
+            let stats = &monitor.stats.problems[&ProblemId(id(1))];
+            (cell.score, cell.pending, stats.total_runs)
+        };
+        assert_eq!(cell(&builder.monitor()), (40, 1, 1));
+        assert_eq!(cell(&builder.unfrozen_monitor()), (100, 0, 2));
+        // party sees own results, but problem stats are still frozen
+        assert_eq!(
+            cell(&builder.monitor_for_party(PartyId(id(1)))),
+            (100, 0, 1)
+        );
+        assert_eq!(cell(&builder.monitor_for_party(PartyId(id(2)))), (40, 1, 1));
+        builder
+            .upsert_run(
+                RunId(id(3)),
+                Run {
+                    time: 30,
+                    ..run(1, &[0])
+                },
+            )
+            .unwrap();
+        assert_eq!(cell(&builder.monitor()), (40, 2, 1));
+        builder.set_freeze_time(None);
+        assert_eq!(cell(&builder.monitor()), (100, 0, 3));
+    }
+
+    #[test]
+    fn test_reveal_sequence() {
+        let problems = (1..=2)
+            .map(|i| {
+                let problem = ProblemConfig {
+                    name: i.to_string(),
+                    accepted_score: 100,
+                    score_runs: RunScoreAggregationTarget::Best,
+                    aggregation: RunScoreAggregation::Max,
+                };
+                (ProblemId(id(i)), problem)
+            })
+            .collect::<Vec<_>>();
+        let mut builder = MonitorBuilder::new(&problems);
+        let on_problem = |problem, run: Run| Run {
+            problem: ProblemId(id(problem)),
+            time: 20,
+            ..run
+        };
+        // party 1 leads before freeze, party 2 solves both problems after freeze.
+        // After first reveal parties are tied, and party 2 is placed lower
+        builder
+            .upsert_run(
+                RunId(id(1)),
+                Run {
+                    time: 5,
+                    ..run(1, &[100])
+                },
+            )
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(2)), on_problem(1, run(2, &[100])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(3)), on_problem(2, run(2, &[100])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(4)), on_problem(2, run(1, &[0])))
+            .unwrap();
+        builder.set_freeze_time(Some(10));
+
+        let steps = reveal_sequence(&builder.monitor(), &builder.unfrozen_monitor())
+            .into_iter()
+            .map(|step| (step.party.0.get(), step.problem.0.get(), step.cell.ok))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![(2, 1, true), (2, 2, true), (1, 2, false)]);
+    }
+
+    #[test]
+    fn test_penalty() {
+        let problem = ProblemConfig {
+            name: "A".to_string(),
+            accepted_score: 100,
+            score_runs: RunScoreAggregationTarget::Best,
+            aggregation: RunScoreAggregation::Max,
+        };
+        let mut builder = MonitorBuilder::new(&[(ProblemId(id(1)), problem)]);
+        let at = |time, run: Run| Run { time, ..run };
+        builder
+            .upsert_run(RunId(id(1)), at(1060, run(1, &[0])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(2)), at(1630, run(1, &[100])))
+            .unwrap();
+        // runs after solution are not taken into account
+        builder
+            .upsert_run(RunId(id(3)), at(1700, run(1, &[0])))
+            .unwrap();
+        let cell = |builder: &MonitorBuilder| {
+            let monitor = builder.monitor();
+            let cell = &monitor.parties[&PartyId(id(1))].problems[&ProblemId(id(1))];
+            (cell.attempts, cell.penalty)
+        };
+        assert_eq!(cell(&builder), (1, ATTEMPT_PENALTY));
+        builder.set_start_time(Some(1000));
+        assert_eq!(cell(&builder), (1, 10 + ATTEMPT_PENALTY));
+        builder.remove_run(RunId(id(2)));
+        assert_eq!(cell(&builder), (2, 0));
+    }
+
+    #[test]
+    fn test_reveal_sequence_penalty() {
+        let problems = (1..=2)
+            .map(|i| {
+                let problem = ProblemConfig {
+                    name: i.to_string(),
+                    accepted_score: 100,
+                    score_runs: RunScoreAggregationTarget::Best,
+                    aggregation: RunScoreAggregation::Max,
+                };
+                (ProblemId(id(i)), problem)
+            })
+            .collect::<Vec<_>>();
+        let mut builder = MonitorBuilder::new(&problems);
+        let at = |time, problem, run: Run| Run {
+            problem: ProblemId(id(problem)),
+            time,
+            ..run
+        };
+        // both parties solved first problem before freeze, but party 1 has bigger
+        // penalty, so it is placed lower and is revealed first
+        builder
+            .upsert_run(RunId(id(1)), at(1, 1, run(1, &[0])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(2)), at(2, 1, run(1, &[100])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(3)), at(2, 1, run(2, &[100])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(4)), at(20, 2, run(1, &[0])))
+            .unwrap();
+        builder
+            .upsert_run(RunId(id(5)), at(20, 2, run(2, &[0])))
+            .unwrap();
+        builder.set_freeze_time(Some(10));
+
+        let steps = reveal_sequence(&builder.monitor(), &builder.unfrozen_monitor())
+            .into_iter()
+            .map(|step| (step.party.0.get(), step.problem.0.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_export() {
+        let problems = (1..=2)
+            .map(|i| {
+                let problem = ProblemConfig {
+                    name: i.to_string(),
+                    accepted_score: 100,
+                    score_runs: RunScoreAggregationTarget::Best,
+                    aggregation: RunScoreAggregation::Max,
+                };
+                (ProblemId(id(i)), problem)
+            })
+            .collect::<Vec<_>>();
+        let mut builder = MonitorBuilder::new(&problems);
+        builder.upsert_run(RunId(id(1)), run(1, &[0])).unwrap();
+        builder.upsert_run(RunId(id(2)), run(1, &[100])).unwrap();
+        builder.upsert_run(RunId(id(3)), run(2, &[100])).unwrap();
+        builder
+            .upsert_run(
+                RunId(id(4)),
+                Run {
+                    problem: ProblemId(id(2)),
+                    ..run(3, &[0])
+                },
+            )
+            .unwrap();
+        let labels = Labels {
+            problems: vec![
+                (ProblemId(id(1)), "A".to_string()),
+                (ProblemId(id(2)), "B".to_string()),
+            ],
+            parties: vec![
+                (PartyId(id(1)), "bob".to_string()),
+                (PartyId(id(2)), "alice, jr".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let standings = Standings::new(&builder.monitor(), &labels);
+        let places = standings
+            .rows
+            .iter()
+            .map(|row| (row.place, row.party.as_str(), row.penalty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            places,
+            vec![(1, "alice, jr", 0), (2, "bob", 20), (3, "3", 0)]
+        );
+        assert_eq!(
+            standings.to_csv(),
+            "place,party,A,B,solved,score,penalty\n\
+             1,\"alice, jr\",+,,1,100,0\n\
+             2,bob,+1,,1,100,20\n\
+             3,3,,-1,0,0,0\n"
+        );
+    }
+
+    #[test]
+    fn test_ranking() {
+        let problems = |aggregation: RunScoreAggregation| {
+            (1..=2)
+                .map(|i| {
+                    let problem = ProblemConfig {
+                        name: i.to_string(),
+                        accepted_score: 100,
+                        score_runs: RunScoreAggregationTarget::Best,
+                        aggregation: aggregation.clone(),
+                    };
+                    (ProblemId(id(i)), problem)
+                })
+                .collect::<Vec<_>>()
+        };
+        let places = |aggregation| {
+            let mut builder = MonitorBuilder::new(&problems(aggregation));
+            // party 1 solved one problem, party 2 has partial scores on both
+            builder.upsert_run(RunId(id(1)), run(1, &[100])).unwrap();
+            builder.upsert_run(RunId(id(2)), run(2, &[90])).unwrap();
+            builder
+                .upsert_run(
+                    RunId(id(3)),
+                    Run {
+                        problem: ProblemId(id(2)),
+                        ..run(2, &[90])
+                    },
+                )
+                .unwrap();
+            let standings = Standings::new(&builder.monitor(), &Labels::default());
+            standings
+                .rows
+                .iter()
+                .map(|row| (row.place, row.party_id.0.get()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(places(RunScoreAggregation::Max), vec![(1, 1), (2, 2)]);
+        assert_eq!(
+            places(RunScoreAggregation::MergeSubtasks),
+            vec![(1, 2), (2, 1)]
+        );
     }
 }
@@ -0,0 +1,56 @@
+//! Step-by-step unfreezing of standings, as done by ICPC resolver
+use crate::{rank_key, Cell, Monitor, PartyId, ProblemId};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Reveals result of one frozen cell
+#[derive(Debug, Serialize, Clone)]
+pub struct RevealStep {
+    pub party: PartyId,
+    pub problem: ProblemId,
+    /// Cell after reveal
+    pub cell: Cell,
+}
+
+/// Computes order, in which frozen cells should be revealed.
+///
+/// On each step, the lowest-placed party that still has pending cells is
+/// selected, and its pending cell for the problem with the smallest id is revealed.
+/// `frozen` and `unfrozen` must be produced by the same `MonitorBuilder`.
+pub fn reveal_sequence(frozen: &Monitor, unfrozen: &Monitor) -> Vec<RevealStep> {
+    let mut current = frozen
+        .parties
+        .iter()
+        .map(|(&party, row)| (party, row.problems.clone()))
+        .collect::<HashMap<_, _>>();
+    let mut steps = Vec::new();
+    loop {
+        // among parties with equal keys, one with greater id is placed lower
+        let next_party = current
+            .iter()
+            .filter(|(_, cells)| cells.values().any(|cell| cell.pending != 0))
+            .min_by_key(|(&party, cells)| {
+                (rank_key(frozen.ranking, cells), std::cmp::Reverse(party))
+            })
+            .map(|(&party, _)| party);
+        let party = match next_party {
+            Some(party) => party,
+            None => break,
+        };
+        let cells = current.get_mut(&party).unwrap();
+        let problem = cells
+            .iter()
+            .filter(|(_, cell)| cell.pending != 0)
+            .map(|(&problem, _)| problem)
+            .min()
+            .unwrap();
+        let cell = unfrozen.parties[&party].problems[&problem].clone();
+        cells.insert(problem, cell.clone());
+        steps.push(RevealStep {
+            party,
+            problem,
+            cell,
+        });
+    }
+    steps
+}
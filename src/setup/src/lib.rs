@@ -53,7 +53,7 @@ fn create_dirs(params: &SetupParams) -> Result<(), SetupError> {
     }
 
     add(params, "var")?;
-    add(params, "var/submissions")?;
+    add(params, "var/blobs")?;
     add(params, "var/problems")?;
     add(params, "opt")?;
     add(params, "opt/bin")?;
@@ -8,3 +8,43 @@ use uuid::Uuid;
     response_derives = "Debug"
 )]
 pub struct CreateUser;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../frontend-api/src/schema-gen.json",
+    query_path = "./src/list_users.graphql",
+    response_derives = "Debug"
+)]
+pub struct ListUsers;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../frontend-api/src/schema-gen.json",
+    query_path = "./src/reset_password.graphql",
+    response_derives = "Debug"
+)]
+pub struct ResetPassword;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../frontend-api/src/schema-gen.json",
+    query_path = "./src/modify_user_groups.graphql",
+    response_derives = "Debug"
+)]
+pub struct ModifyUserGroups;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../frontend-api/src/schema-gen.json",
+    query_path = "./src/set_user_disabled.graphql",
+    response_derives = "Debug"
+)]
+pub struct SetUserDisabled;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../frontend-api/src/schema-gen.json",
+    query_path = "./src/delete_user.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteUser;
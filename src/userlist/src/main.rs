@@ -1,5 +1,6 @@
 mod client;
 mod list_parse;
+mod manage;
 
 use graphql_client::GraphQLQuery;
 use snafu::{ResultExt, Snafu};
@@ -26,10 +27,64 @@ mod args {
         pub port: u16,
     }
 
+    #[derive(StructOpt)]
+    pub struct List {
+        /// Only show users whose login contains this string
+        #[structopt(long = "search", short = "s")]
+        pub search: Option<String>,
+        /// Count of users to skip
+        #[structopt(long = "offset")]
+        pub offset: Option<i64>,
+        /// Max count of users to show
+        #[structopt(long = "limit", short = "l")]
+        pub limit: Option<i64>,
+    }
+
+    #[derive(StructOpt)]
+    pub struct Passwd {
+        pub login: String,
+        /// New password
+        pub password: String,
+    }
+
+    #[derive(StructOpt)]
+    pub struct Groups {
+        pub login: String,
+        /// Group to add user to (can be repeated)
+        #[structopt(long = "add")]
+        pub add: Vec<String>,
+        /// Group to remove user from (can be repeated)
+        #[structopt(long = "remove")]
+        pub remove: Vec<String>,
+    }
+
+    #[derive(StructOpt)]
+    pub struct Login {
+        pub login: String,
+    }
+
     #[derive(StructOpt)]
     pub enum Args {
         #[structopt(name = "add")]
         Add(Add),
+        /// List users
+        #[structopt(name = "list")]
+        List(List),
+        /// Set new password and revoke all user's sessions
+        #[structopt(name = "passwd")]
+        Passwd(Passwd),
+        /// Edit user's groups
+        #[structopt(name = "groups")]
+        Groups(Groups),
+        /// Forbid user to log in
+        #[structopt(name = "disable")]
+        Disable(Login),
+        /// Allow disabled user to log in again
+        #[structopt(name = "enable")]
+        Enable(Login),
+        /// Delete user without runs
+        #[structopt(name = "delete")]
+        Delete(Login),
     }
 }
 
@@ -209,8 +264,15 @@ fn fail<E: std::fmt::Display, X>(err: E) -> X {
 
 fn main() {
     let arg: args::Args = args::Args::from_args();
-    let args::Args::Add(arg) = arg;
-    let res = add_users(arg);
+    let res = match arg {
+        args::Args::Add(arg) => add_users(arg),
+        args::Args::List(arg) => manage::list(arg),
+        args::Args::Passwd(arg) => manage::reset_password(arg),
+        args::Args::Groups(arg) => manage::modify_groups(arg),
+        args::Args::Disable(arg) => manage::set_disabled(arg.login, true),
+        args::Args::Enable(arg) => manage::set_disabled(arg.login, false),
+        args::Args::Delete(arg) => manage::delete(arg),
+    };
     match res {
         Ok(_) => (),
         Err(e) => {
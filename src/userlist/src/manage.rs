@@ -0,0 +1,61 @@
+//! Commands operating on existing users
+use crate::{Error, args, client};
+use graphql_client::GraphQLQuery;
+
+fn exec<Q: GraphQLQuery>(vars: Q::Variables) -> Result<Q::ResponseData, Error> {
+    let client = frontend_api::Client::from_env();
+    let req_body = Q::build_query(vars);
+    let resp = client.query::<_, Q::ResponseData>(&req_body)?;
+    resp.into_result()
+        .map_err(|errs| Error::Frontend { inner: errs })
+}
+
+pub(crate) fn list(arg: args::List) -> Result<(), Error> {
+    let vars = client::list_users::Variables {
+        search: arg.search,
+        offset: arg.offset,
+        limit: arg.limit,
+    };
+    let res = exec::<client::ListUsers>(vars)?;
+    for user in res.users {
+        println!(
+            "{}\t{}{}",
+            user.login,
+            user.groups.join(":"),
+            if user.disabled { "\t(disabled)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+pub(crate) fn reset_password(arg: args::Passwd) -> Result<(), Error> {
+    let vars = client::reset_password::Variables {
+        login: arg.login,
+        password: arg.password,
+    };
+    exec::<client::ResetPassword>(vars)?;
+    Ok(())
+}
+
+pub(crate) fn modify_groups(arg: args::Groups) -> Result<(), Error> {
+    let vars = client::modify_user_groups::Variables {
+        login: arg.login,
+        add: Some(arg.add),
+        remove: Some(arg.remove),
+    };
+    let res = exec::<client::ModifyUserGroups>(vars)?;
+    println!("{}", res.modify_user_groups.groups.join(":"));
+    Ok(())
+}
+
+pub(crate) fn set_disabled(login: String, disabled: bool) -> Result<(), Error> {
+    let vars = client::set_user_disabled::Variables { login, disabled };
+    exec::<client::SetUserDisabled>(vars)?;
+    Ok(())
+}
+
+pub(crate) fn delete(arg: args::Login) -> Result<(), Error> {
+    let vars = client::delete_user::Variables { login: arg.login };
+    exec::<client::DeleteUser>(vars)?;
+    Ok(())
+}
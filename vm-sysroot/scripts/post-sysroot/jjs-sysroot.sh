@@ -20,7 +20,7 @@ fi
 sudo chown -R 1:1 "$SYSROOT"/var/lib/jjs/*
 sudo chown root:root "$SYSROOT/var/lib/jjs"
 sudo chmod -R 0700 "$SYSROOT"/var/lib/jjs/*
-sudo chmod 0755 "$SYSROOT"/var/lib/jjs/var{,/submissions}
+sudo chmod 0755 "$SYSROOT"/var/lib/jjs/var{,/blobs}
 
 sudo rm -rf "$SYSROOT/var/lib/jjs/opt"
 sudo mkdir "$SYSROOT/var/lib/jjs/opt"